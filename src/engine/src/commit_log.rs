@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    ops::Range
+};
+
+use narvi_core::event::RegisterFile;
+
+// Harts only run in machine mode
+const PRIVILEGE: u8 = 3;
+
+/// Which retired instructions a commit log keeps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitFilter {
+    /// PCs to log, all of them when unset
+    pub pcs: Option<Range<u64>>,
+    /// Instructions to log by their position among those retired by their hart,
+    /// counting from 0, all of them when unset
+    pub instructions: Option<Range<u128>>,
+}
+
+impl CommitFilter {
+    fn keeps(&self, pc: u64, position: u128) -> bool {
+        self.pcs.as_ref().is_none_or(|pcs| pcs.contains(&pc))
+            && self.instructions.as_ref().is_none_or(|instructions| instructions.contains(&position))
+    }
+}
+
+#[derive(Debug)]
+struct Commit {
+    pc: u64,
+    inst: u32,
+    disassembly: String,
+    writebacks: Vec<String>,
+    accesses: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct HartState {
+    retired: u128,
+    // Memory requests issued while executing an instruction, which is only known once it retires
+    accesses: Vec<String>,
+    // Last instruction retired, kept until its loads write their registers back
+    commit: Option<Commit>,
+}
+
+/// Logs every instruction retired by the harts in the format of Spike's `-l --log-commits`:
+/// a line with its disassembly, then a line with its register writebacks and memory accesses
+#[derive(Debug, Default)]
+pub(crate) struct CommitLog {
+    filter: CommitFilter,
+    harts: HashMap<usize, HartState>,
+    log: String,
+}
+
+impl CommitLog {
+    pub(crate) fn new(filter: CommitFilter) -> Self {
+        Self {
+            filter,
+            ..Self::default()
+        }
+    }
+
+    /// Logs the instructions still waiting for writebacks, and returns the whole log
+    pub(crate) fn into_log(mut self) -> String {
+        let mut harts: Vec<(usize, HartState)> = self.harts.drain().collect();
+        harts.sort_by_key(|(hart, _)| *hart);
+
+        for (hart, state) in harts {
+            if let Some(commit) = state.commit {
+                self.write(hart, commit);
+            }
+        }
+
+        self.log
+    }
+
+    pub(crate) fn load(&mut self, hart: usize, address: usize) {
+        self.harts.entry(hart).or_default().accesses.push(format!("mem 0x{address:016x}"));
+    }
+
+    pub(crate) fn store(&mut self, hart: usize, address: usize, data: &[u8]) {
+        let value = data.iter().rev().fold(String::new(), |value, byte| value + &format!("{byte:02x}"));
+        self.harts.entry(hart).or_default().accesses.push(format!("mem 0x{address:016x} 0x{value}"));
+    }
+
+    pub(crate) fn retire(&mut self, hart: usize, pc: u64, inst: u32, disassembly: &str) {
+        let state = self.harts.entry(hart).or_default();
+        let accesses = std::mem::take(&mut state.accesses);
+        let position = state.retired;
+        state.retired += 1;
+
+        let commit = self.filter.keeps(pc, position).then(|| Commit {
+            pc,
+            inst,
+            disassembly: disassembly.to_string(),
+            writebacks: Vec::new(),
+            accesses,
+        });
+
+        if let Some(previous) = std::mem::replace(&mut state.commit, commit) {
+            self.write(hart, previous);
+        }
+    }
+
+    pub(crate) fn writeback(&mut self, hart: usize, file: RegisterFile, index: u8, value: u64) {
+        let prefix = match file {
+            RegisterFile::X => 'x',
+            RegisterFile::F => 'f',
+        };
+
+        if let Some(commit) = self.harts.entry(hart).or_default().commit.as_mut() {
+            commit.writebacks.push(format!("{prefix}{index:<2} 0x{value:016x}"));
+        }
+    }
+
+    fn write(&mut self, hart: usize, commit: Commit) {
+        let Commit { pc, inst, disassembly, writebacks, accesses } = commit;
+
+        self.log.push_str(format!("core {hart:>3}: 0x{pc:016x} (0x{inst:08x}) {disassembly}\n").as_str());
+        self.log.push_str(format!("core {hart:>3}: {PRIVILEGE} 0x{pc:016x} (0x{inst:08x})").as_str());
+        for effect in writebacks.iter().chain(&accesses) {
+            self.log.push(' ');
+            self.log.push_str(effect);
+        }
+        self.log.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loads_are_logged_with_their_writeback() {
+        let mut log = CommitLog::new(CommitFilter::default());
+
+        // addi a0, zero, 5 / sd a0, 8(sp) / ld a1, 8(sp)
+        log.retire(0, 0x1000, 0x00500513, "addi");
+        log.writeback(0, RegisterFile::X, 10, 5);
+        log.store(0, 0x2008, &[5, 0, 0, 0, 0, 0, 0, 0]);
+        log.retire(0, 0x1004, 0x00a13423, "sd");
+        log.load(0, 0x2008);
+        log.retire(0, 0x1008, 0x00813583, "ld");
+        log.writeback(0, RegisterFile::X, 11, 5);
+
+        assert_eq!(log.into_log(), "core   0: 0x0000000000001000 (0x00500513) addi
+core   0: 3 0x0000000000001000 (0x00500513) x10 0x0000000000000005
+core   0: 0x0000000000001004 (0x00a13423) sd
+core   0: 3 0x0000000000001004 (0x00a13423) mem 0x0000000000002008 0x0000000000000005
+core   0: 0x0000000000001008 (0x00813583) ld
+core   0: 3 0x0000000000001008 (0x00813583) x11 0x0000000000000005 mem 0x0000000000002008
+");
+    }
+
+    #[test]
+    fn filters_by_pc_and_position() {
+        let mut log = CommitLog::new(CommitFilter { pcs: Some(0x1004..0x2000), instructions: Some(1..3) });
+
+        for pc in [0x1000, 0x1004, 0x3000, 0x1008] {
+            log.retire(1, pc, 0x00000013, "addi");
+        }
+
+        let log = log.into_log();
+        assert_eq!(log.lines().count(), 2);
+        assert!(log.starts_with("core   1: 0x0000000000001004"));
+    }
+}
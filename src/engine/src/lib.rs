@@ -1,16 +1,35 @@
-use std::collections::{
-    BinaryHeap,
-    HashMap
+use std::{
+    cmp::Ordering,
+    collections::{
+        BinaryHeap,
+        HashMap
+    },
+    time::Instant
 };
 
-use journal::{CacheJournal, HartJournal, Journal};
+use journal::{Journal, ReuseTracker};
 
-use memory::{CacheLevel, Ram};
+use memory::{CacheLevel, Directory, DramController, Ram, SnoopBus, optimal_misses};
 use narvi_core::{
-    EngineContext, Module, ModuleId, event::{Event, EventPayload, JournalEvent, Target}, serialization::MachineConfig
+    CacheLevelConfig, CoherenceMechanism, EngineContext, Module, ModuleId, elf::ElfImage, event::{AccessKind, Event, EventPayload, JournalEvent, MissClass, StatUpdate, Target}, serialization::MachineConfig, trace::{TraceAccess, TraceError}
 };
 
-use harts::hart::Hart;
+use harts::{disasm::disassemble, hart::Hart};
+
+mod commit_log;
+mod profiler;
+mod trace_replayer;
+mod tracer;
+
+pub use commit_log::CommitFilter;
+pub use trace_replayer::TraceReplayer;
+pub use tracer::RecordedTrace;
+pub use profiler::{PcProfile, Profile};
+pub use journal::{SampleInterval, TimeSeries};
+
+use commit_log::CommitLog;
+use profiler::Profiler;
+use tracer::{Role, Tracer};
 
 trait ProxyResolver {
     fn resolve_requester(self, id: ModuleId) -> Self;
@@ -18,27 +37,86 @@ trait ProxyResolver {
 
 impl ProxyResolver for EventPayload {
     fn resolve_requester(mut self, id: ModuleId) -> Self {
-        match &mut self {
-            EventPayload::MemoryLoadReq { requester, .. } => {
-                if matches!(Target::Myself, requester) {
-                    *requester = Target::Module(id);
-                }
-            },
-            _ => {}
+        if let EventPayload::MemoryLoadReq { requester, .. }
+            | EventPayload::MemoryFetchReq { requester, .. }
+            | EventPayload::MemoryAmoReq { requester, .. }
+            | EventPayload::CoherenceReq { requester, .. }
+            | EventPayload::CoherenceSnoop { requester, .. }
+            | EventPayload::CoherenceEvict { requester, .. } = &mut self
+            && *requester == Target::Myself
+        {
+            *requester = Target::Module(id);
         }
 
         self
     }
 }
 
+// Orders the queue so that the earliest event pops first, and events
+// scheduled for the same time pop in the order they were scheduled
+struct QueuedEvent {
+    sequence: u64,
+    event: Event,
+}
+
+impl Ord for QueuedEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.event.timestamp(), other.sequence).cmp(&(self.event.timestamp(), self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedEvent {}
+
 pub struct ActiveContext<'a> {
     current_time: u64,
     current_module_id: ModuleId,
-    event_queue: &'a mut BinaryHeap<Event>,
+    sequence: &'a mut u64,
+    event_queue: &'a mut BinaryHeap<QueuedEvent>,
     cache_level_map: &'a mut HashMap<ModuleId, usize>,
+    block_streams: &'a mut HashMap<ModuleId, Vec<usize>>,
+    reuse_trackers: &'a mut HashMap<ModuleId, ReuseTracker>,
+    tracer: &'a mut Option<Tracer>,
+    profiler: &'a mut Option<Profiler>,
+    commit_log: &'a mut Option<CommitLog>,
+    role: Role,
+    // Time statistics were last reset at
+    stats_since: u64,
     journal: &'a mut Journal
 }
 
+impl ActiveContext<'_> {
+    fn current_level(&self) -> usize {
+        *self.cache_level_map.get(&self.current_module_id)
+            .unwrap_or_else(|| panic!("could not find level with id {}", self.current_module_id))
+    }
+
+    fn current_module_name(&self) -> String {
+        let id = self.current_module_id;
+
+        if let Some(cache) = self.journal.caches.get(&id) {
+            cache.name.clone()
+        } else if let Some(hart) = self.journal.harts.get(&id) {
+            hart.name.clone()
+        } else if self.role == Role::Memory {
+            format!("mem{id}")
+        } else {
+            format!("module{id}")
+        }
+    }
+}
+
 impl<'a> EngineContext for ActiveContext<'a> {
     fn schedule(&mut self, delay: u64, target: Target, payload: EventPayload) {
         let actual_target = match target {
@@ -48,117 +126,488 @@ impl<'a> EngineContext for ActiveContext<'a> {
 
         let actual_payload = payload.resolve_requester(self.current_module_id);
 
-        self.event_queue.push(Event::new(
-            self.current_time + delay, 
-            actual_target, 
-            actual_payload
-        ));
-    }
+        *self.sequence += 1;
 
-    fn record_journal(&mut self, event: narvi_core::event::JournalEvent) {
-        // TODO: no need for HartJournal and CacheJournal
-        // keeping for now because I'm lazy
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.schedule(*self.sequence, self.current_time + delay, &actual_payload, self.role);
+        }
 
-        let cache_level = self.cache_level_map.get(&self.current_module_id);
+        if let Some(profiler) = self.profiler.as_mut()
+            && let Role::Hart(hart) = self.role
+            && matches!(actual_payload, EventPayload::MemoryFetchReq { .. })
+        {
+            profiler.fetch(hart, self.current_time);
+        }
 
-        let mut hart_journal = HartJournal::new();
-        let mut cache_journal = CacheJournal::new(self.cache_level_map.len());
+        if let Some(commit_log) = self.commit_log.as_mut()
+            && let Role::Hart(hart) = self.role
+        {
+            match &actual_payload {
+                EventPayload::MemoryLoadReq { address, .. } => commit_log.load(hart, *address),
+                EventPayload::MemoryStoreReq { address, data, .. } => commit_log.store(hart, *address, data),
+                _ => (),
+            }
+        }
+
+        self.event_queue.push(QueuedEvent {
+            sequence: *self.sequence,
+            event: Event::new(
+                self.current_time + delay, 
+                actual_target, 
+                actual_payload
+            )
+        });
+    }
+
+    fn current_time(&self) -> u64 {
+        self.current_time
+    }
+
+    fn record_journal(&mut self, event: narvi_core::event::JournalEvent) {
+        let id = self.current_module_id;
 
         match event {
-            JournalEvent::CacheHit => {
-                cache_journal.hit(*cache_level.expect(&format!("could not find level with id {}", self.current_module_id)));
+            JournalEvent::CacheHit { kind } => {
+                let level = self.current_level();
+                self.journal.cache(id).hit(kind == AccessKind::Fetch, matches!(kind, AccessKind::Store | AccessKind::Amo));
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.hit(level);
+                }
             },
-            JournalEvent::CacheMiss => {
-                cache_journal.miss(*cache_level.expect(&format!("could not find level with id {}", self.current_module_id)));
+            JournalEvent::CacheMiss { kind, class, pc } => {
+                let level = self.current_level();
+                if let Some(profiler) = self.profiler.as_mut()
+                    && let Some(pc) = pc
+                {
+                    profiler.miss(level, pc);
+                }
+
+                let cache = self.journal.cache(id);
+                cache.miss(kind == AccessKind::Fetch, matches!(kind, AccessKind::Store | AccessKind::Amo));
+                match class {
+                    Some(MissClass::Compulsory) => cache.compulsory_miss(),
+                    Some(MissClass::Capacity) => cache.capacity_miss(),
+                    Some(MissClass::Conflict) => cache.conflict_miss(),
+                    None => {},
+                }
+            },
+            JournalEvent::CacheFill { bytes } => {
+                self.journal.cache(id).fill(bytes as u128);
+            },
+            JournalEvent::CacheEviction => {
+                self.journal.cache(id).eviction();
+            },
+            JournalEvent::CacheWriteback { bytes } => {
+                self.journal.cache(id).writeback(bytes as u128);
+            },
+            JournalEvent::CacheWriteThrough { bytes } => {
+                self.journal.cache(id).write_through(bytes as u128);
             },
             JournalEvent::Cycles { cycles } => {
-                hart_journal.cycles_done(cycles as u128);
+                if let Some(profiler) = self.profiler.as_mut()
+                    && let Role::Hart(hart) = self.role
+                {
+                    profiler.fetch(hart, self.current_time);
+                }
+
+                let cycles = (cycles as u64).saturating_sub(self.stats_since);
+                self.journal.hart(id).cycles_done(cycles as u128);
             },
             JournalEvent::CyclesLost { cycles } => {
-                hart_journal.lost_cycle(cycles as u128);
+                self.journal.hart(id).lost_cycle(cycles as u128);
+            },
+            JournalEvent::HartInstruction { pc, inst, class, mnemonic } => {
+                if let Some(profiler) = self.profiler.as_mut()
+                    && let Role::Hart(hart) = self.role
+                {
+                    profiler.retire(hart, pc, inst);
+                }
+                if let Some(commit_log) = self.commit_log.as_mut()
+                    && let Role::Hart(hart) = self.role
+                {
+                    commit_log.retire(hart, pc, inst, &disassemble(inst, pc));
+                }
+
+                let hart = self.journal.hart(id);
+                hart.inst_done(1);
+                hart.instruction_mix(class.as_str(), mnemonic);
             },
-            JournalEvent::HartInstruction => {
-                hart_journal.inst_done(1);
+            JournalEvent::HartWriteback { file, index, value } => {
+                if let Some(commit_log) = self.commit_log.as_mut()
+                    && let Role::Hart(hart) = self.role
+                {
+                    commit_log.writeback(hart, file, index, value);
+                }
+            },
+            JournalEvent::CoherenceTransition { from, to } => {
+                self.journal.coherence_transition(from.as_str(), to.as_str());
+            },
+            JournalEvent::CoherenceTransaction { request } => {
+                self.journal.coherence_transaction(request.as_str());
+            },
+            JournalEvent::MshrAllocation { occupancy } => {
+                self.journal.cache(id).mshr_allocation(occupancy);
+            },
+            JournalEvent::MshrMerge => {
+                self.journal.cache(id).mshr_merge();
+            },
+            JournalEvent::MshrStall => {
+                self.journal.cache(id).mshr_stall();
+            },
+            JournalEvent::PrefetchIssued => {
+                self.journal.cache(id).prefetch_issued();
+            },
+            JournalEvent::PrefetchUseful => {
+                self.journal.cache(id).prefetch_useful();
+            },
+            JournalEvent::PrefetchLate => {
+                self.journal.cache(id).prefetch_late();
+            },
+            JournalEvent::PrefetchUseless => {
+                self.journal.cache(id).prefetch_useless();
+            },
+            JournalEvent::BlockAccess { block } => {
+                if let Some(stream) = self.block_streams.get_mut(&id) {
+                    stream.push(block);
+                }
+                if let Some(tracker) = self.reuse_trackers.get_mut(&id) {
+                    tracker.access(block);
+                }
+            },
+            JournalEvent::DramAccess { row, queued, bytes } => {
+                self.journal.dram_access(row.as_str(), queued as u128, bytes as u128);
+            },
+            JournalEvent::DramRefresh => {
+                self.journal.dram_refresh();
+            },
+            JournalEvent::Invalidation => {
+                self.journal.invalidation();
+            },
+            JournalEvent::Intervention => {
+                self.journal.intervention();
             }
         }
+    }
+
+    fn record_stat(&mut self, name: &str, update: StatUpdate) {
+        let path = format!("system.{}.{name}", self.current_module_name());
 
-        self.journal.merge_cache(cache_journal);
-        self.journal.merge_hart(hart_journal);
+        match update {
+            StatUpdate::Count(amount) => self.journal.published.count(&path, amount),
+            StatUpdate::Sample(value) => self.journal.published.sample(&path, value),
+            StatUpdate::Distribute(value) => self.journal.published.distribute(&path, value),
+        }
     }
 }
 
+// Memory controllers and cache levels, the level and name of every cache,
+// and the (instruction, data) ports each hart is connected to
+type Hierarchy = (Vec<Box<dyn Module>>, HashMap<ModuleId, (usize, String)>, Vec<(ModuleId, ModuleId)>);
+
 pub struct Engine {
     modules : Vec<Box<dyn Module>>,
-    event_queue: BinaryHeap<Event>,
+    event_queue: BinaryHeap<QueuedEvent>,
+    sequence: u64,
     time: u64,
     cache_level_map: HashMap<ModuleId, usize>,
+    cache_config: Vec<CacheLevelConfig>,
+    // Block addresses reaching each cache that records them, replayed once the run ends
+    block_streams: HashMap<ModuleId, Vec<usize>>,
+    reuse_trackers: HashMap<ModuleId, ReuseTracker>,
+    // Memory controllers are the first modules
+    memory_controllers: usize,
+    first_hart: ModuleId,
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    commit_log: Option<CommitLog>,
+    // Whether every processed event is written to stderr
+    log_events: bool,
+    stats_since: u64,
+    // Instructions after which the statistics are reset, leaving a warm-up out of them
+    reset_after: Option<u128>,
+    time_series: Option<TimeSeries>,
     journal: Journal,
 }
 
 impl Engine {
     pub fn build_from_config(config: &MachineConfig, assembly: Vec<u8>) -> Self {
+        Self::build_with_program(config, &[(0, assembly)], 0)
+    }
+
+    /// Loads the segments of `image` and starts every hart at its entry point
+    pub fn build_from_elf(config: &MachineConfig, image: &ElfImage) -> Self {
+        Self::build_with_program(config, &image.segments, image.entry)
+    }
+
+    fn build_with_program(config: &MachineConfig, segments: &[(u64, Vec<u8>)], entry: u64) -> Self {
+        let (mut modules, caches, hart_ports) = Self::build_hierarchy(config, segments);
+
+        for (instruction_port, data_port) in hart_ports {
+            let mut hart = Hart::from_extensions(&config.extensions, instruction_port, data_port);
+            hart.set_pc(entry);
+            modules.push(Box::new(hart));
+        }
+
+        Self::from_modules(config, modules, caches)
+    }
+
+    /// Builds the memory hierarchy of `config` without any program, with every hart
+    /// replaced by a replayer issuing its accesses from `trace`. Fails on accesses from
+    /// harts the machine does not have or beyond the end of its memory
+    pub fn build_for_trace(config: &MachineConfig, trace: Vec<TraceAccess>) -> Result<Self, TraceError> {
+        let (mut modules, caches, hart_ports) = Self::build_hierarchy(config, &[]);
+        let block_size = config.cache_config.first().map_or(usize::MAX, |level| level.block_size);
+        let mut accesses = vec![Vec::new(); hart_ports.len()];
+
+        for (record, access) in trace.into_iter().enumerate() {
+            if access.hart >= hart_ports.len() {
+                return Err(TraceError::UnknownHart(record + 1));
+            }
+            if access.address.checked_add(access.size).is_none_or(|end| end > config.ram_size) {
+                return Err(TraceError::OutOfBounds(record + 1));
+            }
+
+            // Caches only serve accesses within a block, so the others are split
+            let mut part = access;
+            while part.size > 0 {
+                let block_end = (part.address / block_size).saturating_add(1).saturating_mul(block_size);
+                let size = part.size.min(block_end - part.address);
+
+                accesses[access.hart].push(TraceAccess { size, ..part });
+                part.address += size;
+                part.size -= size;
+            }
+        }
+
+        for ((instruction_port, data_port), accesses) in hart_ports.into_iter().zip(accesses) {
+            modules.push(Box::new(TraceReplayer::new(accesses, instruction_port, data_port)));
+        }
+
+        Ok(Self::from_modules(config, modules, caches))
+    }
+
+    fn build_hierarchy(config: &MachineConfig, segments: &[(u64, Vec<u8>)]) -> Hierarchy {
+        config.validate().expect("invalid machine config");
+
         let mut modules: Vec<Box<dyn Module>> = Vec::new();
-        let mut cache_level_map: HashMap<ModuleId, usize> = HashMap::new();
+        let mut caches: HashMap<ModuleId, (usize, String)> = HashMap::new();
 
-        let ram = {
-            let mut ram = Ram::new(config.ram_size);
-            ram.write_bytes(0, assembly);
-            ram
-        };
-        let ram_id = modules.len();
-        modules.push(Box::new(ram));
+        let controllers = config.memory_controllers as usize;
+        let mut ram_ids: Vec<ModuleId> = Vec::new();
 
-        let mut previous_store_id = ram_id;
+        for channel in 0..controllers {
+            let block_size = config.cache_config.last().map_or(1, |level| level.block_size);
+            let mut ram = Ram::interleaved(config.ram_size, channel, controllers, config.interleave_size, block_size)
+                .expect("invalid memory interleaving");
+            ram.set_latency(config.ram_latency, config.seed.wrapping_add(channel as u64));
+            for (address, bytes) in segments {
+                ram.load(*address as usize, bytes).expect("program does not fit in memory");
+            }
+            ram_ids.push(modules.len());
 
-        for (level, cache_conf) in config.cache_config.iter().rev().enumerate() {
-            let mut cache = CacheLevel::from(cache_conf);
-            cache.set_backing_store(previous_store_id);
-            previous_store_id = modules.len();
-            modules.push(Box::new(cache));
-            cache_level_map.insert(previous_store_id, level);
+            match config.dram {
+                Some(dram) => modules.push(Box::new(DramController::new(ram, dram))),
+                None => modules.push(Box::new(ram)),
+            }
         }
 
-        for _ in 0..config.hart_count {
-            let mut hart = Hart::from_extensions(&config.extensions, previous_store_id);
-            modules.push(Box::new(hart));
+        // Levels are built from the last one up, so that every instance can be
+        // connected to the instance of the level below shared by the same harts.
+        // Each instance is kept as its (instruction, data) pair of ports, which
+        // are the same module for unified levels
+        let mut previous_ports: Vec<(ModuleId, ModuleId)> = Vec::new();
+        let mut previous_share = 1;
+
+        for (level, cache_conf) in config.cache_config.iter().enumerate().rev() {
+            let share = cache_conf.share_config as usize;
+            let mut level_ports = Vec::new();
+
+            let instances = config.instances_of(level);
+            let sides = if cache_conf.split { 2 } else { 1 };
+            let mut data_sides = Vec::new();
+
+            // Private data caches of the same level are kept coherent through a bus or a directory,
+            // placed right after them. Instruction caches are never written and do not take part
+            let coherence = config.coherence
+                .filter(|_| instances > 1)
+                .map(|coherence| (coherence, modules.len() + instances * sides));
+
+            for instance in 0..instances {
+                let backing_ports = previous_ports.get(instance * share / previous_share).copied();
+                let mut ids = Vec::new();
+
+                for side in 0..sides {
+                    let mut cache = CacheLevel::from(cache_conf);
+                    cache.set_memory_size(config.ram_size);
+
+                    match backing_ports {
+                        // The instruction side of a split level is backed by the instruction side below
+                        Some((instruction_port, data_port)) => cache.set_backing_store(
+                            if side == 0 && sides == 2 { instruction_port } else { data_port }
+                        ),
+                        None => cache.set_interleaved_backing_stores(ram_ids.clone(), config.interleave_size),
+                    }
+
+                    if let Some((coherence, controller_id)) = coherence
+                        && side == sides - 1
+                    {
+                        cache.set_coherence(coherence, controller_id);
+                    }
+
+                    let name = match sides {
+                        2 => format!("l{}{}_{instance}", level + 1, if side == 0 { "i" } else { "d" }),
+                        _ => format!("l{}_{instance}", level + 1),
+                    };
+
+                    let id = modules.len();
+                    modules.push(Box::new(cache));
+                    caches.insert(id, (level, name));
+                    ids.push(id);
+                }
+
+                level_ports.push((ids[0], ids[sides - 1]));
+                data_sides.push(ids[sides - 1]);
+            }
+
+            if let Some((coherence, _)) = coherence {
+                match coherence.mechanism {
+                    CoherenceMechanism::Snooping => modules.push(Box::new(SnoopBus::new(data_sides))),
+                    CoherenceMechanism::Directory { sharers } => modules.push(Box::new(
+                        Directory::new(data_sides, coherence.protocol, sharers, config.home_latency(level))
+                    )),
+                }
+            }
+
+            previous_ports = level_ports;
+            previous_share = share;
         }
 
+        let hart_ports = (0..config.hart_count as usize)
+            .map(|hart_id| previous_ports[hart_id / previous_share])
+            .collect();
+
+        (modules, caches, hart_ports)
+    }
+
+    fn from_modules(
+        config: &MachineConfig,
+        modules: Vec<Box<dyn Module>>,
+        caches: HashMap<ModuleId, (usize, String)>
+    ) -> Self {
+        let modules_len = modules.len();
+        let mut journal = Journal::new();
+
+        for (id, (level, name)) in &caches {
+            journal.add_cache(*id, name, *level);
+        }
 
-        let cache_levels = cache_level_map.len();
+        // Harts, or the replayers standing for them, are the last modules
+        let first_hart = modules_len - config.hart_count as usize;
+        for id in first_hart..modules_len {
+            journal.add_hart(id, &format!("hart{}", id - first_hart));
+        }
 
         let mut engine = Self {
             modules,
             event_queue: Default::default(),
+            sequence: 0,
             time: 0,
-            cache_level_map,
-            journal: Journal::new(cache_levels)
+            cache_level_map: caches.into_iter().map(|(id, (level, _))| (id, level)).collect(),
+            cache_config: config.cache_config.clone(),
+            block_streams: HashMap::new(),
+            reuse_trackers: HashMap::new(),
+            memory_controllers: config.memory_controllers as usize,
+            first_hart,
+            tracer: None,
+            profiler: None,
+            commit_log: None,
+            log_events: false,
+            stats_since: 0,
+            reset_after: None,
+            time_series: None,
+            journal
         };
+
+        // Caches journal their block accesses for either analysis, so each one only keeps its own
+        for (id, level) in &engine.cache_level_map {
+            if engine.cache_config[*level].belady {
+                engine.block_streams.insert(*id, Vec::new());
+            }
+            if engine.cache_config[*level].reuse_histogram {
+                engine.reuse_trackers.insert(*id, ReuseTracker::new());
+            }
+        }
         
-        engine.event_queue.push(Event::new(
-            0,
-            // TODO: find better way to broadcast evens
-            usize::MAX,
-            EventPayload::Reset
-        ));
+        engine.event_queue.push(QueuedEvent {
+            sequence: 0,
+            event: Event::new(
+                0,
+                // TODO: find better way to broadcast evens
+                usize::MAX,
+                EventPayload::Reset
+            )
+        });
 
         engine
     }
 
+    /// Processes events until the queue runs dry, journaling how long the host took
+    /// since the statistics were last reset
+    pub fn run(&mut self) {
+        let mut started = Instant::now();
+
+        while self.update() {
+            if self.reset_after.is_some_and(|instructions| self.journal.num_inst() >= instructions) {
+                self.reset_after = None;
+                self.reset_stats();
+                started = Instant::now();
+            }
+        }
+
+        self.journal.host_micros = started.elapsed().as_micros();
+    }
+
     pub fn update(&mut self) -> bool {
-        if let Some(event) = self.event_queue.pop() {
-            println!("processing event: {event:?}");
+        if let Some(QueuedEvent { sequence, event }) = self.event_queue.pop() {
+            if self.log_events {
+                eprintln!("processing event: {event:?}");
+            }
             
+            // Samples due before this event only cover the events before it
+            while let Some(cycle) = self.time_series.as_ref().and_then(TimeSeries::next_cycle)
+                && cycle <= event.timestamp()
+            {
+                let registry = self.journal.registry();
+                if let Some(series) = self.time_series.as_mut() {
+                    series.sample(cycle, registry);
+                }
+            }
+
             self.time = event.timestamp();
             
             let target_id = event.target();
+            let role = self.role(target_id);
+
+            if let Some(tracer) = &mut self.tracer {
+                tracer.deliver(sequence, &event, role);
+            }
 
             if target_id == usize::MAX {
                 for id in 0..self.modules.len() {
+                    let role = self.role(id);
                     let mut ctx = ActiveContext {
                         current_time: self.time,
                         current_module_id: id,
+                        sequence: &mut self.sequence,
                         event_queue: &mut self.event_queue,
                         cache_level_map: &mut self.cache_level_map,
+                        block_streams: &mut self.block_streams,
+                        reuse_trackers: &mut self.reuse_trackers,
+                        tracer: &mut self.tracer,
+                        profiler: &mut self.profiler,
+                        commit_log: &mut self.commit_log,
+                        role,
+                        stats_since: self.stats_since,
                         journal: &mut self.journal
                     };
                     
@@ -168,21 +617,217 @@ impl Engine {
                 let mut ctx = ActiveContext {
                     current_time: self.time,
                     current_module_id: target_id,
+                    sequence: &mut self.sequence,
                     event_queue: &mut self.event_queue,
                     cache_level_map: &mut self.cache_level_map,
+                    block_streams: &mut self.block_streams,
+                    reuse_trackers: &mut self.reuse_trackers,
+                    tracer: &mut self.tracer,
+                    profiler: &mut self.profiler,
+                    commit_log: &mut self.commit_log,
+                    role,
+                    stats_since: self.stats_since,
                     journal: &mut self.journal
                 };
 
                 module.process_event(event, &mut ctx);
             }
 
+            if self.time_series.as_ref().is_some_and(|series| series.instructions_due(self.journal.num_inst())) {
+                let registry = self.journal.registry();
+                if let Some(series) = self.time_series.as_mut() {
+                    series.sample(self.time, registry);
+                }
+            }
+
             true
         } else {
+            // The optimal replacement and reuse analyses run over the whole stream, outside any sample
+            if self.time_series.is_some() {
+                let registry = self.journal.registry();
+                if let Some(series) = self.time_series.as_mut() {
+                    series.finish(self.time, registry);
+                }
+            }
+
+            self.replay_optimal();
+            self.collect_reuse();
             false
         }
     }
 
+    // Second pass of the optimal replacement comparison, over the streams recorded during the run
+    fn replay_optimal(&mut self) {
+        for (id, stream) in self.block_streams.drain() {
+            let level = self.cache_level_map[&id];
+            let conf = &self.cache_config[level];
+            let misses = optimal_misses(&stream, conf.n_blocks / conf.set_size, conf.set_size, conf.block_size);
+
+            self.journal.cache(id).optimal_misses(misses as u128);
+        }
+    }
+
+    fn role(&self, id: ModuleId) -> Role {
+        if let Some(level) = self.cache_level_map.get(&id) {
+            Role::Cache(*level)
+        } else if id < self.memory_controllers {
+            Role::Memory
+        } else if id >= self.first_hart && id < self.modules.len() {
+            Role::Hart(id - self.first_hart)
+        } else {
+            Role::Other
+        }
+    }
+
+    /// Starts recording every memory request issued by the harts and the level serving it,
+    /// and if `arrivals` is set every request reaching each cache and memory controller
+    pub fn record_trace(&mut self, arrivals: bool) {
+        self.tracer = Some(Tracer::new(arrivals));
+    }
+
+    /// Starts attributing instructions, cycles and cache misses to the PC behind them
+    pub fn profile(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    /// Stops profiling, returning the profile since `profile`
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profiler.take().map(Profiler::into_profile)
+    }
+
+    /// Starts logging the instructions retired by the harts that `filter` keeps
+    pub fn log_commits(&mut self, filter: CommitFilter) {
+        self.commit_log = Some(CommitLog::new(filter));
+    }
+
+    /// Stops logging, returning the commit log since `log_commits`
+    pub fn take_commit_log(&mut self) -> Option<String> {
+        self.commit_log.take().map(CommitLog::into_log)
+    }
+
+    /// Stops recording, returning what was recorded since `record_trace`
+    pub fn take_trace(&mut self) -> Option<RecordedTrace> {
+        self.tracer.take().map(Tracer::into_trace)
+    }
+
+    fn collect_reuse(&mut self) {
+        for (id, tracker) in self.reuse_trackers.drain() {
+            self.journal.cache(id).reuse_histogram(tracker.histogram());
+        }
+    }
+
+    /// Zeroes every statistic, so that the journal only covers the run from now on
+    pub fn reset_stats(&mut self) {
+        self.journal.reset();
+        self.stats_since = self.time;
+
+        if let Some(series) = self.time_series.as_mut() {
+            series.restart(self.time, self.journal.registry());
+        }
+    }
+
+    /// Writes every event to stderr as it is processed
+    pub fn log_events(&mut self) {
+        self.log_events = true;
+    }
+
+    /// Resets the statistics once `instructions` have retired during `run`, as a warm-up
+    pub fn reset_stats_after(&mut self, instructions: u128) {
+        self.reset_after = Some(instructions);
+    }
+
+    /// Starts snapshotting the statistics registry every `interval`, as deltas from the previous snapshot
+    pub fn sample_every(&mut self, interval: SampleInterval) {
+        self.time_series = Some(TimeSeries::new(interval, self.time, self.journal.registry()));
+    }
+
+    /// Stops sampling, returning the samples taken since `sample_every`
+    pub fn take_time_series(&mut self) -> Option<TimeSeries> {
+        self.time_series.take()
+    }
+
     pub fn get_journal(&self) -> &Journal {
         &self.journal
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use narvi_core::{CoherenceConfig, CoherenceProtocol, SharerFormat};
+
+    use super::*;
+
+    // Stands in for a hart, sending `requests` to `port` at fixed times and keeping the data loaded
+    struct Script {
+        port: ModuleId,
+        requests: Vec<(u64, EventPayload)>,
+        loads: Rc<RefCell<Vec<Vec<u8>>>>,
+    }
+
+    impl Module for Script {
+        fn process_event(&mut self, event: Event, engine_context: &mut dyn EngineContext) {
+            match event.payload() {
+                EventPayload::Reset => for (time, payload) in self.requests.drain(..) {
+                    engine_context.schedule(time, Target::Module(self.port), payload);
+                },
+                EventPayload::MemoryLoadRes { data, .. } => self.loads.borrow_mut().push(data.clone()),
+                _ => panic!("cannot process {event}")
+            }
+        }
+    }
+
+    #[test]
+    fn racing_upgrades_keep_both_stores() {
+        for mechanism in [CoherenceMechanism::Snooping, CoherenceMechanism::Directory { sharers: SharerFormat::FullBitVector }] {
+            let config = MachineConfig {
+                hart_count: 2,
+                coherence: Some(CoherenceConfig { protocol: CoherenceProtocol::Mesi, mechanism }),
+                ..MachineConfig::default()
+            };
+            let (mut modules, caches, hart_ports) = Engine::build_hierarchy(&config, &[]);
+            let load = || EventPayload::MemoryLoadReq { address: 0x200, size_in_bytes: 8, requester: Target::Myself, pc: None };
+
+            // Both harts share the block, then write their half of it in the same cycle
+            let loads: Vec<_> = hart_ports.into_iter().enumerate().map(|(hart, (_, data_port))| {
+                let loads = Rc::new(RefCell::new(Vec::new()));
+                let store = EventPayload::MemoryStoreReq { address: 0x200 + 4 * hart, data: vec![hart as u8 + 1; 4], pc: None };
+                modules.push(Box::new(Script {
+                    port: data_port,
+                    requests: vec![(0, load()), (1000, store), (5000, load())],
+                    loads: Rc::clone(&loads),
+                }));
+                loads
+            }).collect();
+
+            let mut engine = Engine::from_modules(&config, modules, caches);
+            engine.run();
+
+            for loads in loads {
+                assert_eq!(loads.borrow().last(), Some(&vec![1, 1, 1, 1, 2, 2, 2, 2]), "{mechanism:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn warm_up_is_left_out_of_the_stats() {
+        // addi a0, a0, 1 four times, then ebreak
+        let program: Vec<u8> = [0x00150513u32, 0x00150513, 0x00150513, 0x00150513, 0x00100073]
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect();
+
+        let mut engine = Engine::build_from_config(&MachineConfig::default(), program.clone());
+        engine.run();
+        let (instructions, cycles) = (engine.get_journal().num_inst(), engine.get_journal().num_cycles());
+
+        let mut engine = Engine::build_from_config(&MachineConfig::default(), program);
+        engine.reset_stats_after(3);
+        engine.run();
+        let journal = engine.get_journal();
+
+        assert_eq!((instructions, journal.num_inst()), (5, 2));
+        assert!(journal.num_cycles() > 0 && journal.num_cycles() < cycles);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{
+    BTreeMap,
+    HashMap
+};
+
+use harts::decode::{decode, IType, Instruction, JType};
+use narvi_core::elf::SymbolTable;
+
+const RA: u8 = 1;
+
+/// What the instructions at a single PC cost
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PcProfile {
+    pub instructions: u128,
+    /// Cycles from fetching each instruction to fetching the next one of its hart
+    pub cycles: u128,
+    /// Misses caused at each cache level
+    pub misses: Vec<u128>,
+}
+
+impl PcProfile {
+    fn merge(&mut self, other: &PcProfile) {
+        self.instructions += other.instructions;
+        self.cycles += other.cycles;
+        if self.misses.len() < other.misses.len() {
+            self.misses.resize(other.misses.len(), 0);
+        }
+        for (level, misses) in other.misses.iter().enumerate() {
+            self.misses[level] += misses;
+        }
+    }
+}
+
+/// Cost of every PC executed during a run, and the cycles spent under each call stack
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub pcs: BTreeMap<u64, PcProfile>,
+    /// Cycles by call stack: the first PC its hart ran, the entry of every function
+    /// called since and not returned from, and the PC that spent them
+    pub stacks: BTreeMap<Vec<u64>, u128>,
+}
+
+impl Profile {
+    /// Functions sorted by the cycles spent in them, or PCs when there are no symbols
+    pub fn hotspots(&self, symbols: &SymbolTable) -> String {
+        let mut functions: HashMap<String, PcProfile> = HashMap::new();
+        for (pc, profile) in &self.pcs {
+            functions.entry(symbols.name_of(*pc)).or_default().merge(profile);
+        }
+
+        let mut functions: Vec<(String, PcProfile)> = functions.into_iter().collect();
+        functions.sort_by(|(name_a, a), (name_b, b)| b.cycles.cmp(&a.cycles).then_with(|| name_a.cmp(name_b)));
+
+        let total_cycles: u128 = functions.iter().map(|(_, profile)| profile.cycles).sum();
+        let levels = functions.iter().map(|(_, profile)| profile.misses.len()).max().unwrap_or(0);
+        let width = functions.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max(8);
+
+        let mut report = format!("{:<width$}  {:>12}  {:>12}  {:>8}", "function", "instructions", "cycles", "cycles%");
+        for level in 0..levels {
+            report.push_str(format!("  {:>10}", format!("l{}_misses", level + 1)).as_str());
+        }
+        report.push('\n');
+
+        for (name, profile) in functions {
+            let share = if total_cycles == 0 { 0.0 } else { profile.cycles as f64 / total_cycles as f64 * 100.0 };
+            report.push_str(format!("{name:<width$}  {:>12}  {:>12}  {share:>7.2}%", profile.instructions, profile.cycles).as_str());
+            for level in 0..levels {
+                report.push_str(format!("  {:>10}", profile.misses.get(level).copied().unwrap_or(0)).as_str());
+            }
+            report.push('\n');
+        }
+
+        report
+    }
+
+    /// One `frame;frame;...;leaf cycles` line per call stack, as read by flamegraph tools
+    pub fn folded(&self, symbols: &SymbolTable) -> String {
+        let mut folded: BTreeMap<String, u128> = BTreeMap::new();
+
+        for (stack, cycles) in &self.stacks {
+            let (leaf, frames) = stack.split_last().expect("empty call stack");
+            let mut names: Vec<String> = frames.iter().map(|pc| symbols.name_of(*pc)).collect();
+
+            // The leaf is usually in the function last called, unless it was jumped to
+            let leaf = symbols.name_of(*leaf);
+            if names.last() != Some(&leaf) {
+                names.push(leaf);
+            }
+
+            *folded.entry(names.join(";")).or_default() += cycles;
+        }
+
+        folded.into_iter()
+            .filter(|(_, cycles)| *cycles > 0)
+            .map(|(stack, cycles)| format!("{stack} {cycles}\n"))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct HartState {
+    // Entries of the functions called and not yet returned from, after the first PC run
+    calls: Vec<u64>,
+    // The last instruction was a call, whose target is the next PC
+    calling: bool,
+    fetched_at: u64,
+    // Stack of the last instruction, until its cycles are known
+    retired: Option<Vec<u64>>,
+}
+
+/// Attributes instructions, cycles and cache misses to the PC behind them.
+///
+/// Calls and returns are told apart by the usual use of `ra`: a `jal` or `jalr` linking
+/// to it calls a function, and a `jalr` to it that does not link returns from one
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    profile: Profile,
+    harts: HashMap<usize, HartState>,
+}
+
+impl Profiler {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn into_profile(self) -> Profile {
+        self.profile
+    }
+
+    /// The hart fetched its next instruction, or halted, so the last one is over
+    pub(crate) fn fetch(&mut self, hart: usize, time: u64) {
+        let state = self.harts.entry(hart).or_default();
+
+        if let Some(stack) = state.retired.take() {
+            let cycles = (time - state.fetched_at) as u128;
+
+            self.profile.pcs.entry(*stack.last().unwrap()).or_default().cycles += cycles;
+            *self.profile.stacks.entry(stack).or_default() += cycles;
+        }
+
+        state.fetched_at = time;
+    }
+
+    pub(crate) fn retire(&mut self, hart: usize, pc: u64, inst: u32) {
+        let state = self.harts.entry(hart).or_default();
+
+        if state.calls.is_empty() || state.calling {
+            state.calls.push(pc);
+            state.calling = false;
+        }
+
+        let mut stack = state.calls.clone();
+        stack.push(pc);
+        state.retired = Some(stack);
+
+        match decode(inst) {
+            Some(Instruction::Jal(JType { rd: RA, .. }) | Instruction::Jalr(IType { rd: RA, .. })) => state.calling = true,
+            Some(Instruction::Jalr(IType { rd: 0, rs1: RA, .. })) if state.calls.len() > 1 => {
+                state.calls.pop();
+            },
+            _ => {},
+        }
+
+        self.profile.pcs.entry(pc).or_default().instructions += 1;
+    }
+
+    pub(crate) fn miss(&mut self, level: usize, pc: u64) {
+        let misses = &mut self.profile.pcs.entry(pc).or_default().misses;
+        if misses.len() <= level {
+            misses.resize(level + 1, 0);
+        }
+        misses[level] += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use narvi_core::elf::Symbol;
+
+    // jal ra, offset / jalr zero, 0(ra) / addi zero, zero, 0
+    const CALL: u32 = 0x0000_00EF;
+    const RET: u32 = 0x0000_8067;
+    const NOP: u32 = 0x0000_0013;
+
+    #[test]
+    fn calls_build_folded_stacks() {
+        let mut profiler = Profiler::new();
+
+        // main calls leaf, which misses in L1 and returns
+        let run = [(0, 0x1000, NOP), (2, 0x1004, CALL), (3, 0x2000, NOP), (13, 0x2004, RET), (14, 0x1008, NOP)];
+        for (time, pc, inst) in run {
+            profiler.fetch(0, time);
+            profiler.retire(0, pc, inst);
+        }
+        profiler.miss(0, 0x2000);
+        profiler.fetch(0, 16);
+
+        let symbols = SymbolTable::new(vec![
+            Symbol { name: "main".to_string(), address: 0x1000, size: 0x100 },
+            Symbol { name: "leaf".to_string(), address: 0x2000, size: 0x100 },
+        ]);
+        let profile = profiler.into_profile();
+
+        assert_eq!(profile.pcs[&0x2000], PcProfile { instructions: 1, cycles: 10, misses: vec![1] });
+        assert_eq!(profile.folded(&symbols), "main 5\nmain;leaf 11\n");
+
+        let hotspots = profile.hotspots(&symbols);
+        let lines: Vec<&str> = hotspots.lines().collect();
+        assert!(lines[1].starts_with("leaf") && lines[1].contains("68.75%"));
+        assert!(lines[2].starts_with("main"));
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use narvi_core::{
+    EngineContext,
+    Module,
+    ModuleId,
+    event::{
+        AccessKind,
+        AmoOp,
+        Event,
+        EventPayload,
+        JournalEvent,
+        Target,
+    },
+    trace::TraceAccess
+};
+
+/// Stands in for a hart when feeding a memory trace to the cache hierarchy.
+///
+/// Accesses are issued one after the other: loads, fetches and atomics wait for their data,
+/// while stores are sent and forgotten, as the harts do. Traces do not record data, so
+/// stores write zeros and atomics swap zeros in
+#[derive(Debug)]
+pub struct TraceReplayer {
+    accesses: VecDeque<TraceAccess>,
+    instruction_port: ModuleId,
+    data_port: ModuleId,
+}
+
+impl Module for TraceReplayer {
+    fn process_event(&mut self, event: Event, engine_context: &mut dyn EngineContext) {
+        match event.payload() {
+            EventPayload::Reset
+            | EventPayload::ReplayNext
+            | EventPayload::MemoryLoadRes { .. } => self.issue_next(engine_context),
+            _ => panic!("cannot process {event}")
+        }
+    }
+}
+
+impl TraceReplayer {
+    pub fn new(accesses: Vec<TraceAccess>, instruction_port: ModuleId, data_port: ModuleId) -> Self {
+        Self {
+            accesses: accesses.into(),
+            instruction_port,
+            data_port,
+        }
+    }
+
+    fn issue_next(&mut self, engine_context: &mut dyn EngineContext) {
+        let Some(access) = self.accesses.pop_front() else {
+            engine_context.record_journal(JournalEvent::Cycles { cycles: engine_context.current_time() as usize });
+            return;
+        };
+
+        let (port, payload) = match access.kind {
+            AccessKind::Fetch => (self.instruction_port, EventPayload::MemoryFetchReq {
+                address: access.address,
+                size_in_bytes: access.size,
+                requester: Target::Myself,
+                pc: access.pc
+            }),
+            AccessKind::Load => (self.data_port, EventPayload::MemoryLoadReq {
+                address: access.address,
+                size_in_bytes: access.size,
+                requester: Target::Myself,
+                pc: access.pc
+            }),
+            AccessKind::Store => (self.data_port, EventPayload::MemoryStoreReq {
+                address: access.address,
+                data: vec![0; access.size],
+                pc: access.pc
+            }),
+            AccessKind::Amo => (self.data_port, EventPayload::MemoryAmoReq {
+                address: access.address,
+                op: AmoOp::Swap,
+                data: vec![0; access.size],
+                requester: Target::Myself,
+                pc: access.pc
+            }),
+        };
+
+        engine_context.schedule(1, Target::Module(port), payload);
+
+        if access.kind == AccessKind::Store {
+            engine_context.schedule(1, Target::Myself, EventPayload::ReplayNext);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use narvi_core::{
+        serialization::MachineConfig,
+        trace::TraceError,
+    };
+
+    use super::*;
+    use crate::Engine;
+
+    #[test]
+    fn atomics_are_replayed_as_atomics() {
+        let trace = vec![
+            TraceAccess::new(0, AccessKind::Load, 0x100, 8),
+            TraceAccess::new(0, AccessKind::Amo, 0x100, 8),
+            TraceAccess::new(0, AccessKind::Load, 0x108, 8),
+        ];
+        let mut engine = Engine::build_for_trace(&MachineConfig::default(), trace).unwrap();
+        engine.record_trace(false);
+        engine.run();
+
+        let replayed = engine.take_trace().unwrap().harts;
+        let kinds: Vec<AccessKind> = replayed.iter().map(|access| access.kind).collect();
+        assert_eq!(kinds, vec![AccessKind::Load, AccessKind::Amo, AccessKind::Load]);
+
+        // The atomic hits in L1 and the next access waits for its answer
+        assert_eq!(replayed[1].hit_level, Some(1));
+        assert!(replayed[2].timestamp > replayed[1].timestamp + 1);
+    }
+
+    #[test]
+    fn malformed_records_are_errors() {
+        let config = MachineConfig::default();
+        let build = |access| Engine::build_for_trace(&config, vec![TraceAccess::new(0, AccessKind::Load, 0, 8), access]);
+
+        assert!(matches!(build(TraceAccess::new(1, AccessKind::Load, 0, 8)), Err(TraceError::UnknownHart(2))));
+        assert!(matches!(build(TraceAccess::new(0, AccessKind::Store, config.ram_size - 4, 8)), Err(TraceError::OutOfBounds(2))));
+        assert!(matches!(build(TraceAccess::new(0, AccessKind::Load, usize::MAX, 8)), Err(TraceError::OutOfBounds(2))));
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{
+    BTreeMap,
+    HashMap
+};
+
+use narvi_core::{
+    ModuleId,
+    event::{
+        AccessKind,
+        Event,
+        EventPayload,
+    },
+    trace::TraceAccess
+};
+
+/// What a module is, as far as tracing is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    Hart(usize),
+    Cache(usize),
+    Memory,
+    Other,
+}
+
+/// Memory accesses recorded during a run
+#[derive(Debug, Default)]
+pub struct RecordedTrace {
+    /// Every request issued by the harts, in order
+    pub harts: Vec<TraceAccess>,
+    /// Requests arriving at each cache and memory controller, when recorded
+    pub arrivals: BTreeMap<ModuleId, Vec<TraceAccess>>,
+}
+
+/// Follows every memory request from the hart that issued it down the hierarchy,
+/// to record the level that served it.
+///
+/// Every event scheduled while handling a request is tagged with that request, so that
+/// misses, coherence messages and refills can be traced back to it. A request merged
+/// into a miss already on its way is never served by a level of its own
+#[derive(Debug, Default)]
+pub(crate) struct Tracer {
+    trace: RecordedTrace,
+    record_arrivals: bool,
+    // Whether each request already found the level serving it
+    served: Vec<bool>,
+    // Request each queued event derives from, by sequence number
+    tags: HashMap<u64, usize>,
+    // Request behind the event being handled, if the event is itself a memory request
+    current: Option<usize>,
+    current_request: bool,
+    // Arrival recorded for the event being handled, to fill its hit level in
+    current_arrival: Option<(ModuleId, usize)>,
+}
+
+fn request_of(payload: &EventPayload) -> Option<(usize, usize, AccessKind, Option<u64>)> {
+    match payload {
+        EventPayload::MemoryFetchReq { address, size_in_bytes, pc, .. } =>
+            Some((*address, *size_in_bytes, AccessKind::Fetch, *pc)),
+        EventPayload::MemoryLoadReq { address, size_in_bytes, pc, .. } =>
+            Some((*address, *size_in_bytes, AccessKind::Load, *pc)),
+        EventPayload::MemoryStoreReq { address, data, pc } =>
+            Some((*address, data.len(), AccessKind::Store, *pc)),
+        EventPayload::MemoryAmoReq { address, data, pc, .. } =>
+            Some((*address, data.len(), AccessKind::Amo, *pc)),
+        _ => None,
+    }
+}
+
+impl Tracer {
+    pub(crate) fn new(record_arrivals: bool) -> Self {
+        Self {
+            record_arrivals,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn into_trace(self) -> RecordedTrace {
+        self.trace
+    }
+
+    /// An event is about to be handled by a module
+    pub(crate) fn deliver(&mut self, sequence: u64, event: &Event, role: Role) {
+        self.current = self.tags.remove(&sequence);
+        self.current_arrival = None;
+
+        let request = request_of(event.payload());
+        self.current_request = request.is_some();
+
+        let Some((address, size, kind, pc)) = request else {
+            return;
+        };
+
+        if role == Role::Memory
+            && let Some(idx) = self.current
+        {
+            self.served[idx] = true;
+        }
+
+        if self.record_arrivals && matches!(role, Role::Cache(_) | Role::Memory) {
+            let arrivals = self.trace.arrivals.entry(event.target()).or_default();
+
+            arrivals.push(TraceAccess {
+                timestamp: event.timestamp(),
+                // Hart 0 when the request cannot be traced back to one
+                hart: self.current.map_or(0, |idx| self.trace.harts[idx].hart),
+                pc,
+                address,
+                size,
+                kind,
+                hit_level: None,
+            });
+
+            self.current_arrival = Some((event.target(), arrivals.len() - 1));
+        }
+    }
+
+    /// The module handling the current event scheduled another one
+    pub(crate) fn schedule(&mut self, sequence: u64, time: u64, payload: &EventPayload, role: Role) {
+        if let Role::Hart(hart) = role
+            && let Some((address, size, kind, pc)) = request_of(payload)
+        {
+            self.trace.harts.push(TraceAccess {
+                timestamp: time,
+                hart,
+                pc,
+                address,
+                size,
+                kind,
+                hit_level: None,
+            });
+            self.served.push(false);
+            self.tags.insert(sequence, self.trace.harts.len() - 1);
+        } else if let Some(idx) = self.current {
+            self.tags.insert(sequence, idx);
+        }
+    }
+
+    /// The cache handling the current event hit at `level`
+    pub(crate) fn hit(&mut self, level: usize) {
+        // Requests retried from a stall queue are served while handling another event
+        if !self.current_request {
+            return;
+        }
+
+        if let Some(idx) = self.current
+            && !self.served[idx]
+        {
+            self.served[idx] = true;
+            self.trace.harts[idx].hit_level = Some(level as u8 + 1);
+        }
+
+        if let Some((module, idx)) = self.current_arrival {
+            self.trace.arrivals.get_mut(&module).expect("arrival without a trace")[idx].hit_level = Some(level as u8 + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use narvi_core::event::Target;
+
+    fn load(address: usize) -> EventPayload {
+        EventPayload::MemoryLoadReq { address, size_in_bytes: 4, requester: Target::Module(3), pc: Some(0x10) }
+    }
+
+    #[test]
+    fn misses_keep_the_level_of_the_first_hit() {
+        let mut tracer = Tracer::new(true);
+
+        // Hart 0 (module 3) asks L1 (module 1), which misses to L2 (module 0) where it hits
+        tracer.deliver(0, &Event::new(0, 3, EventPayload::Reset), Role::Hart(0));
+        tracer.schedule(1, 1, &load(0x40), Role::Hart(0));
+        tracer.deliver(1, &Event::new(1, 1, load(0x40)), Role::Cache(0));
+        tracer.schedule(2, 2, &load(0x40), Role::Cache(0));
+        tracer.deliver(2, &Event::new(2, 0, load(0x40)), Role::Cache(1));
+        tracer.hit(1);
+
+        let trace = tracer.into_trace();
+        assert_eq!(trace.harts.len(), 1);
+        assert_eq!((trace.harts[0].pc, trace.harts[0].hit_level), (Some(0x10), Some(2)));
+        assert_eq!(trace.arrivals[&1][0].hit_level, None);
+        assert_eq!(trace.arrivals[&0][0].hit_level, Some(2));
+    }
+
+    // Harts never send atomics, so a recorded AMO always comes from a replayed trace, as here
+    #[test]
+    fn atomics_round_trip_through_the_trace() {
+        let mut tracer = Tracer::new(false);
+        let amo = EventPayload::MemoryAmoReq {
+            address: 0x80,
+            op: narvi_core::event::AmoOp::Add,
+            data: vec![1; 8],
+            requester: Target::Module(3),
+            pc: Some(0x20)
+        };
+
+        tracer.deliver(0, &Event::new(0, 3, EventPayload::Reset), Role::Hart(0));
+        tracer.schedule(1, 1, &amo, Role::Hart(0));
+
+        let recorded = tracer.into_trace().harts;
+        let mut file = Vec::new();
+        narvi_core::trace::write_narvi_header(&mut file).unwrap();
+        narvi_core::trace::write_narvi_record(&mut file, &recorded[0]).unwrap();
+
+        let replayed = narvi_core::trace::read_narvi(file.as_slice()).unwrap();
+        assert_eq!(replayed, recorded);
+        assert_eq!((replayed[0].kind, replayed[0].address, replayed[0].size), (AccessKind::Amo, 0x80, 8));
+    }
+}
@@ -0,0 +1,613 @@
+use crate::util::{get_bits, sign_extend_64};
+
+/// Operands of register-register instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RType {
+    pub rd: u8,
+    pub rs1: u8,
+    pub rs2: u8,
+}
+
+/// Operands of register-immediate instructions, loads and `jalr`.
+/// Shifts carry their shift amount as `imm`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IType {
+    pub rd: u8,
+    pub rs1: u8,
+    pub imm: i64,
+}
+
+/// Operands of stores, which write `rs2` to `imm` past `rs1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SType {
+    pub rs1: u8,
+    pub rs2: u8,
+    pub imm: i64,
+}
+
+/// Operands of branches, `imm` being the offset from the branch to its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BType {
+    pub rs1: u8,
+    pub rs2: u8,
+    pub imm: i64,
+}
+
+/// Operands of `lui` and `auipc`, `imm` being already shifted into the upper 20 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UType {
+    pub rd: u8,
+    pub imm: i64,
+}
+
+/// Operands of `jal`, `imm` being the offset from the jump to its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JType {
+    pub rd: u8,
+    pub imm: i64,
+}
+
+/// Operands of floating point operations that round their result by `rm`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RmType {
+    pub rd: u8,
+    pub rs1: u8,
+    pub rs2: u8,
+    pub rm: u8,
+}
+
+/// Operands of the fused multiply-add family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct R4Type {
+    pub rd: u8,
+    pub rs1: u8,
+    pub rs2: u8,
+    pub rs3: u8,
+    pub rm: u8,
+}
+
+/// Operands of Zicsr instructions. The immediate forms hold their 5-bit immediate in `rs1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsrType {
+    pub rd: u8,
+    pub rs1: u8,
+    pub csr: u16,
+}
+
+/// Operands of atomic memory operations, along with their ordering bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmoType {
+    pub rd: u8,
+    pub rs1: u8,
+    pub rs2: u8,
+    pub aq: bool,
+    pub rl: bool,
+}
+
+/// Extension an instruction belongs to. Zicsr and Zifencei count as part of the base ISA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extension {
+    I,
+    M,
+    A,
+    F,
+    D,
+}
+
+/// A 32-bit instruction with its operands extracted, as decoded once by `decode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // RV64I
+    Lui(UType), Auipc(UType),
+    Jal(JType), Jalr(IType),
+    Beq(BType), Bne(BType), Blt(BType), Bge(BType), Bltu(BType), Bgeu(BType),
+    Lb(IType), Lh(IType), Lw(IType), Ld(IType), Lbu(IType), Lhu(IType), Lwu(IType),
+    Sb(SType), Sh(SType), Sw(SType), Sd(SType),
+    Addi(IType), Slti(IType), Sltiu(IType), Xori(IType), Ori(IType), Andi(IType),
+    Slli(IType), Srli(IType), Srai(IType),
+    Add(RType), Sub(RType), Sll(RType), Slt(RType), Sltu(RType),
+    Xor(RType), Srl(RType), Sra(RType), Or(RType), And(RType),
+    Addiw(IType), Slliw(IType), Srliw(IType), Sraiw(IType),
+    Addw(RType), Subw(RType), Sllw(RType), Srlw(RType), Sraw(RType),
+    Fence { pred: u8, succ: u8 }, FenceI,
+    Ecall, Ebreak,
+    Csrrw(CsrType), Csrrs(CsrType), Csrrc(CsrType),
+    Csrrwi(CsrType), Csrrsi(CsrType), Csrrci(CsrType),
+
+    // M
+    Mul(RType), Mulh(RType), Mulhsu(RType), Mulhu(RType),
+    Div(RType), Divu(RType), Rem(RType), Remu(RType),
+    Mulw(RType), Divw(RType), Divuw(RType), Remw(RType), Remuw(RType),
+
+    // A
+    LrW(AmoType), ScW(AmoType), AmoswapW(AmoType), AmoaddW(AmoType), AmoxorW(AmoType),
+    AmoandW(AmoType), AmoorW(AmoType), AmominW(AmoType), AmomaxW(AmoType),
+    AmominuW(AmoType), AmomaxuW(AmoType),
+    LrD(AmoType), ScD(AmoType), AmoswapD(AmoType), AmoaddD(AmoType), AmoxorD(AmoType),
+    AmoandD(AmoType), AmoorD(AmoType), AmominD(AmoType), AmomaxD(AmoType),
+    AmominuD(AmoType), AmomaxuD(AmoType),
+
+    // F
+    Flw(IType), Fsw(SType),
+    FmaddS(R4Type), FmsubS(R4Type), FnmsubS(R4Type), FnmaddS(R4Type),
+    FaddS(RmType), FsubS(RmType), FmulS(RmType), FdivS(RmType), FsqrtS(RmType),
+    FsgnjS(RType), FsgnjnS(RType), FsgnjxS(RType), FminS(RType), FmaxS(RType),
+    FcvtWS(RmType), FcvtWuS(RmType), FcvtLS(RmType), FcvtLuS(RmType),
+    FmvXW(RType), FeqS(RType), FltS(RType), FleS(RType), FclassS(RType),
+    FcvtSW(RmType), FcvtSWu(RmType), FcvtSL(RmType), FcvtSLu(RmType),
+    FmvWX(RType),
+
+    // D
+    Fld(IType), Fsd(SType),
+    FmaddD(R4Type), FmsubD(R4Type), FnmsubD(R4Type), FnmaddD(R4Type),
+    FaddD(RmType), FsubD(RmType), FmulD(RmType), FdivD(RmType), FsqrtD(RmType),
+    FsgnjD(RType), FsgnjnD(RType), FsgnjxD(RType), FminD(RType), FmaxD(RType),
+    FcvtSD(RmType), FcvtDS(RmType),
+    FeqD(RType), FltD(RType), FleD(RType), FclassD(RType),
+    FcvtWD(RmType), FcvtWuD(RmType), FcvtLD(RmType), FcvtLuD(RmType),
+    FcvtDW(RmType), FcvtDWu(RmType), FcvtDL(RmType), FcvtDLu(RmType),
+    FmvXD(RType), FmvDX(RType),
+}
+
+fn imm(value: u32, bits: u8) -> i64 {
+    sign_extend_64(value as u64, bits) as i64
+}
+
+/// Decodes a 32-bit instruction of RV64IMAFD, Zicsr or Zifencei, or returns `None` when
+/// `inst` encodes none of them
+pub fn decode(inst: u32) -> Option<Instruction> {
+    use Instruction::*;
+
+    let opcode = get_bits(6, 0, inst);
+    let funct3 = get_bits(14, 12, inst);
+    let funct7 = get_bits(31, 25, inst);
+
+    let rd = get_bits(11, 7, inst) as u8;
+    let rs1 = get_bits(19, 15, inst) as u8;
+    let rs2 = get_bits(24, 20, inst) as u8;
+    let rs3 = get_bits(31, 27, inst) as u8;
+    let rm = funct3 as u8;
+
+    let r = RType { rd, rs1, rs2 };
+    let i = IType { rd, rs1, imm: imm(get_bits(31, 20, inst), 12) };
+    let s = SType { rs1, rs2, imm: imm(get_bits(31, 25, inst) << 5 | get_bits(11, 7, inst), 12) };
+    let b = BType {
+        rs1,
+        rs2,
+        imm: imm(
+            get_bits(31, 31, inst) << 12 | get_bits(7, 7, inst) << 11 | get_bits(30, 25, inst) << 5 | get_bits(11, 8, inst) << 1,
+            13
+        ),
+    };
+    let u = UType { rd, imm: imm(inst & 0xFFFF_F000, 32) };
+    let j = JType {
+        rd,
+        imm: imm(
+            get_bits(31, 31, inst) << 20 | get_bits(19, 12, inst) << 12 | get_bits(20, 20, inst) << 11 | get_bits(30, 21, inst) << 1,
+            21
+        ),
+    };
+    let shift = IType { rd, rs1, imm: get_bits(25, 20, inst) as i64 };
+    let shift_w = IType { rd, rs1, imm: rs2 as i64 };
+    let csr = CsrType { rd, rs1, csr: get_bits(31, 20, inst) as u16 };
+    let amo = AmoType { rd, rs1, rs2, aq: get_bits(26, 26, inst) == 1, rl: get_bits(25, 25, inst) == 1 };
+    let rounded = RmType { rd, rs1, rs2, rm };
+    let fused = R4Type { rd, rs1, rs2, rs3, rm };
+
+    let instruction = match (opcode, funct3, funct7) {
+        (0x37, _, _) => Lui(u),
+        (0x17, _, _) => Auipc(u),
+        (0x6F, _, _) => Jal(j),
+        (0x67, 0, _) => Jalr(i),
+        (0x63, 0, _) => Beq(b),
+        (0x63, 1, _) => Bne(b),
+        (0x63, 4, _) => Blt(b),
+        (0x63, 5, _) => Bge(b),
+        (0x63, 6, _) => Bltu(b),
+        (0x63, 7, _) => Bgeu(b),
+        (0x03, 0, _) => Lb(i),
+        (0x03, 1, _) => Lh(i),
+        (0x03, 2, _) => Lw(i),
+        (0x03, 3, _) => Ld(i),
+        (0x03, 4, _) => Lbu(i),
+        (0x03, 5, _) => Lhu(i),
+        (0x03, 6, _) => Lwu(i),
+        (0x23, 0, _) => Sb(s),
+        (0x23, 1, _) => Sh(s),
+        (0x23, 2, _) => Sw(s),
+        (0x23, 3, _) => Sd(s),
+        (0x13, 0, _) => Addi(i),
+        (0x13, 2, _) => Slti(i),
+        (0x13, 3, _) => Sltiu(i),
+        (0x13, 4, _) => Xori(i),
+        (0x13, 6, _) => Ori(i),
+        (0x13, 7, _) => Andi(i),
+        // RV64 shifts take a 6-bit shift amount, whose top bit is the low bit of funct7
+        (0x13, 1, 0b0000000 | 0b0000001) => Slli(shift),
+        (0x13, 5, 0b0000000 | 0b0000001) => Srli(shift),
+        (0x13, 5, 0b0100000 | 0b0100001) => Srai(shift),
+        (0x33, 0, 0b0000000) => Add(r),
+        (0x33, 0, 0b0100000) => Sub(r),
+        (0x33, 1, 0b0000000) => Sll(r),
+        (0x33, 2, 0b0000000) => Slt(r),
+        (0x33, 3, 0b0000000) => Sltu(r),
+        (0x33, 4, 0b0000000) => Xor(r),
+        (0x33, 5, 0b0000000) => Srl(r),
+        (0x33, 5, 0b0100000) => Sra(r),
+        (0x33, 6, 0b0000000) => Or(r),
+        (0x33, 7, 0b0000000) => And(r),
+        (0x1B, 0, _) => Addiw(i),
+        (0x1B, 1, 0b0000000) => Slliw(shift_w),
+        (0x1B, 5, 0b0000000) => Srliw(shift_w),
+        (0x1B, 5, 0b0100000) => Sraiw(shift_w),
+        (0x3B, 0, 0b0000000) => Addw(r),
+        (0x3B, 0, 0b0100000) => Subw(r),
+        (0x3B, 1, 0b0000000) => Sllw(r),
+        (0x3B, 5, 0b0000000) => Srlw(r),
+        (0x3B, 5, 0b0100000) => Sraw(r),
+        (0x0F, 0, _) => Fence { pred: get_bits(27, 24, inst) as u8, succ: get_bits(23, 20, inst) as u8 },
+        (0x0F, 1, _) => FenceI,
+        (0x73, 0, _) => match inst >> 7 {
+            0x0000 => Ecall,
+            0x2000 => Ebreak,
+            _ => return None,
+        },
+        (0x73, 1, _) => Csrrw(csr),
+        (0x73, 2, _) => Csrrs(csr),
+        (0x73, 3, _) => Csrrc(csr),
+        (0x73, 5, _) => Csrrwi(csr),
+        (0x73, 6, _) => Csrrsi(csr),
+        (0x73, 7, _) => Csrrci(csr),
+
+        (0x33, 0, 0b0000001) => Mul(r),
+        (0x33, 1, 0b0000001) => Mulh(r),
+        (0x33, 2, 0b0000001) => Mulhsu(r),
+        (0x33, 3, 0b0000001) => Mulhu(r),
+        (0x33, 4, 0b0000001) => Div(r),
+        (0x33, 5, 0b0000001) => Divu(r),
+        (0x33, 6, 0b0000001) => Rem(r),
+        (0x33, 7, 0b0000001) => Remu(r),
+        (0x3B, 0, 0b0000001) => Mulw(r),
+        (0x3B, 4, 0b0000001) => Divw(r),
+        (0x3B, 5, 0b0000001) => Divuw(r),
+        (0x3B, 6, 0b0000001) => Remw(r),
+        (0x3B, 7, 0b0000001) => Remuw(r),
+
+        (0x2F, 2 | 3, _) => return decode_amo(funct3, funct7 >> 2, rs2, amo),
+
+        (0x07, 2, _) => Flw(i),
+        (0x27, 2, _) => Fsw(s),
+        (0x07, 3, _) => Fld(i),
+        (0x27, 3, _) => Fsd(s),
+        (0x43 | 0x47 | 0x4B | 0x4F, _, _) => match (opcode, get_bits(26, 25, inst)) {
+            (0x43, 0) => FmaddS(fused),
+            (0x47, 0) => FmsubS(fused),
+            (0x4B, 0) => FnmsubS(fused),
+            (0x4F, 0) => FnmaddS(fused),
+            (0x43, 1) => FmaddD(fused),
+            (0x47, 1) => FmsubD(fused),
+            (0x4B, 1) => FnmsubD(fused),
+            (0x4F, 1) => FnmaddD(fused),
+            _ => return None,
+        },
+        (0x53, _, _) => match (funct7, rs2, funct3) {
+            (0b0000000, _, _) => FaddS(rounded),
+            (0b0000100, _, _) => FsubS(rounded),
+            (0b0001000, _, _) => FmulS(rounded),
+            (0b0001100, _, _) => FdivS(rounded),
+            (0b0101100, 0, _) => FsqrtS(rounded),
+            (0b0010000, _, 0) => FsgnjS(r),
+            (0b0010000, _, 1) => FsgnjnS(r),
+            (0b0010000, _, 2) => FsgnjxS(r),
+            (0b0010100, _, 0) => FminS(r),
+            (0b0010100, _, 1) => FmaxS(r),
+            (0b1100000, 0, _) => FcvtWS(rounded),
+            (0b1100000, 1, _) => FcvtWuS(rounded),
+            (0b1100000, 2, _) => FcvtLS(rounded),
+            (0b1100000, 3, _) => FcvtLuS(rounded),
+            (0b1110000, 0, 0) => FmvXW(r),
+            (0b1010000, _, 2) => FeqS(r),
+            (0b1010000, _, 1) => FltS(r),
+            (0b1010000, _, 0) => FleS(r),
+            (0b1110000, 0, 1) => FclassS(r),
+            (0b1101000, 0, _) => FcvtSW(rounded),
+            (0b1101000, 1, _) => FcvtSWu(rounded),
+            (0b1101000, 2, _) => FcvtSL(rounded),
+            (0b1101000, 3, _) => FcvtSLu(rounded),
+            (0b1111000, 0, 0) => FmvWX(r),
+
+            (0b0000001, _, _) => FaddD(rounded),
+            (0b0000101, _, _) => FsubD(rounded),
+            (0b0001001, _, _) => FmulD(rounded),
+            (0b0001101, _, _) => FdivD(rounded),
+            (0b0101101, 0, _) => FsqrtD(rounded),
+            (0b0010001, _, 0) => FsgnjD(r),
+            (0b0010001, _, 1) => FsgnjnD(r),
+            (0b0010001, _, 2) => FsgnjxD(r),
+            (0b0010101, _, 0) => FminD(r),
+            (0b0010101, _, 1) => FmaxD(r),
+            (0b0100000, 1, _) => FcvtSD(rounded),
+            (0b0100001, 0, _) => FcvtDS(rounded),
+            (0b1010001, _, 2) => FeqD(r),
+            (0b1010001, _, 1) => FltD(r),
+            (0b1010001, _, 0) => FleD(r),
+            (0b1110001, 0, 1) => FclassD(r),
+            (0b1100001, 0, _) => FcvtWD(rounded),
+            (0b1100001, 1, _) => FcvtWuD(rounded),
+            (0b1100001, 2, _) => FcvtLD(rounded),
+            (0b1100001, 3, _) => FcvtLuD(rounded),
+            (0b1101001, 0, _) => FcvtDW(rounded),
+            (0b1101001, 1, _) => FcvtDWu(rounded),
+            (0b1101001, 2, _) => FcvtDL(rounded),
+            (0b1101001, 3, _) => FcvtDLu(rounded),
+            (0b1110001, 0, 0) => FmvXD(r),
+            (0b1111001, 0, 0) => FmvDX(r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(instruction)
+}
+
+fn decode_amo(width: u32, funct5: u32, rs2: u8, amo: AmoType) -> Option<Instruction> {
+    use Instruction::*;
+
+    let instruction = match (funct5, width) {
+        (0b00010, 2) if rs2 == 0 => LrW(amo),
+        (0b00011, 2) => ScW(amo),
+        (0b00001, 2) => AmoswapW(amo),
+        (0b00000, 2) => AmoaddW(amo),
+        (0b00100, 2) => AmoxorW(amo),
+        (0b01100, 2) => AmoandW(amo),
+        (0b01000, 2) => AmoorW(amo),
+        (0b10000, 2) => AmominW(amo),
+        (0b10100, 2) => AmomaxW(amo),
+        (0b11000, 2) => AmominuW(amo),
+        (0b11100, 2) => AmomaxuW(amo),
+        (0b00010, 3) if rs2 == 0 => LrD(amo),
+        (0b00011, 3) => ScD(amo),
+        (0b00001, 3) => AmoswapD(amo),
+        (0b00000, 3) => AmoaddD(amo),
+        (0b00100, 3) => AmoxorD(amo),
+        (0b01100, 3) => AmoandD(amo),
+        (0b01000, 3) => AmoorD(amo),
+        (0b10000, 3) => AmominD(amo),
+        (0b10100, 3) => AmomaxD(amo),
+        (0b11000, 3) => AmominuD(amo),
+        (0b11100, 3) => AmomaxuD(amo),
+        _ => return None,
+    };
+
+    Some(instruction)
+}
+
+impl Instruction {
+    /// Extension the instruction belongs to, which the hart must implement to run it
+    pub fn extension(&self) -> Extension {
+        use Instruction::*;
+
+        match self {
+            Mul(_) | Mulh(_) | Mulhsu(_) | Mulhu(_) | Div(_) | Divu(_) | Rem(_) | Remu(_)
+                | Mulw(_) | Divw(_) | Divuw(_) | Remw(_) | Remuw(_) => Extension::M,
+            LrW(_) | ScW(_) | AmoswapW(_) | AmoaddW(_) | AmoxorW(_) | AmoandW(_) | AmoorW(_)
+                | AmominW(_) | AmomaxW(_) | AmominuW(_) | AmomaxuW(_)
+                | LrD(_) | ScD(_) | AmoswapD(_) | AmoaddD(_) | AmoxorD(_) | AmoandD(_) | AmoorD(_)
+                | AmominD(_) | AmomaxD(_) | AmominuD(_) | AmomaxuD(_) => Extension::A,
+            Flw(_) | Fsw(_) | FmaddS(_) | FmsubS(_) | FnmsubS(_) | FnmaddS(_)
+                | FaddS(_) | FsubS(_) | FmulS(_) | FdivS(_) | FsqrtS(_)
+                | FsgnjS(_) | FsgnjnS(_) | FsgnjxS(_) | FminS(_) | FmaxS(_)
+                | FcvtWS(_) | FcvtWuS(_) | FcvtLS(_) | FcvtLuS(_)
+                | FmvXW(_) | FeqS(_) | FltS(_) | FleS(_) | FclassS(_)
+                | FcvtSW(_) | FcvtSWu(_) | FcvtSL(_) | FcvtSLu(_) | FmvWX(_) => Extension::F,
+            Fld(_) | Fsd(_) | FmaddD(_) | FmsubD(_) | FnmsubD(_) | FnmaddD(_)
+                | FaddD(_) | FsubD(_) | FmulD(_) | FdivD(_) | FsqrtD(_)
+                | FsgnjD(_) | FsgnjnD(_) | FsgnjxD(_) | FminD(_) | FmaxD(_)
+                | FcvtSD(_) | FcvtDS(_) | FeqD(_) | FltD(_) | FleD(_) | FclassD(_)
+                | FcvtWD(_) | FcvtWuD(_) | FcvtLD(_) | FcvtLuD(_)
+                | FcvtDW(_) | FcvtDWu(_) | FcvtDL(_) | FcvtDLu(_)
+                | FmvXD(_) | FmvDX(_) => Extension::D,
+            _ => Extension::I,
+        }
+    }
+
+    /// Assembler mnemonic, without the ordering suffix of atomic memory operations
+    pub fn mnemonic(&self) -> &'static str {
+        use Instruction::*;
+
+        match self {
+            Lui(_) => "lui",
+            Auipc(_) => "auipc",
+            Jal(_) => "jal",
+            Jalr(_) => "jalr",
+            Beq(_) => "beq",
+            Bne(_) => "bne",
+            Blt(_) => "blt",
+            Bge(_) => "bge",
+            Bltu(_) => "bltu",
+            Bgeu(_) => "bgeu",
+            Lb(_) => "lb",
+            Lh(_) => "lh",
+            Lw(_) => "lw",
+            Ld(_) => "ld",
+            Lbu(_) => "lbu",
+            Lhu(_) => "lhu",
+            Lwu(_) => "lwu",
+            Sb(_) => "sb",
+            Sh(_) => "sh",
+            Sw(_) => "sw",
+            Sd(_) => "sd",
+            Addi(_) => "addi",
+            Slti(_) => "slti",
+            Sltiu(_) => "sltiu",
+            Xori(_) => "xori",
+            Ori(_) => "ori",
+            Andi(_) => "andi",
+            Slli(_) => "slli",
+            Srli(_) => "srli",
+            Srai(_) => "srai",
+            Add(_) => "add",
+            Sub(_) => "sub",
+            Sll(_) => "sll",
+            Slt(_) => "slt",
+            Sltu(_) => "sltu",
+            Xor(_) => "xor",
+            Srl(_) => "srl",
+            Sra(_) => "sra",
+            Or(_) => "or",
+            And(_) => "and",
+            Addiw(_) => "addiw",
+            Slliw(_) => "slliw",
+            Srliw(_) => "srliw",
+            Sraiw(_) => "sraiw",
+            Addw(_) => "addw",
+            Subw(_) => "subw",
+            Sllw(_) => "sllw",
+            Srlw(_) => "srlw",
+            Sraw(_) => "sraw",
+            Fence { .. } => "fence",
+            FenceI => "fence.i",
+            Ecall => "ecall",
+            Ebreak => "ebreak",
+            Csrrw(_) => "csrrw",
+            Csrrs(_) => "csrrs",
+            Csrrc(_) => "csrrc",
+            Csrrwi(_) => "csrrwi",
+            Csrrsi(_) => "csrrsi",
+            Csrrci(_) => "csrrci",
+
+            Mul(_) => "mul",
+            Mulh(_) => "mulh",
+            Mulhsu(_) => "mulhsu",
+            Mulhu(_) => "mulhu",
+            Div(_) => "div",
+            Divu(_) => "divu",
+            Rem(_) => "rem",
+            Remu(_) => "remu",
+            Mulw(_) => "mulw",
+            Divw(_) => "divw",
+            Divuw(_) => "divuw",
+            Remw(_) => "remw",
+            Remuw(_) => "remuw",
+
+            LrW(_) => "lr.w",
+            ScW(_) => "sc.w",
+            AmoswapW(_) => "amoswap.w",
+            AmoaddW(_) => "amoadd.w",
+            AmoxorW(_) => "amoxor.w",
+            AmoandW(_) => "amoand.w",
+            AmoorW(_) => "amoor.w",
+            AmominW(_) => "amomin.w",
+            AmomaxW(_) => "amomax.w",
+            AmominuW(_) => "amominu.w",
+            AmomaxuW(_) => "amomaxu.w",
+            LrD(_) => "lr.d",
+            ScD(_) => "sc.d",
+            AmoswapD(_) => "amoswap.d",
+            AmoaddD(_) => "amoadd.d",
+            AmoxorD(_) => "amoxor.d",
+            AmoandD(_) => "amoand.d",
+            AmoorD(_) => "amoor.d",
+            AmominD(_) => "amomin.d",
+            AmomaxD(_) => "amomax.d",
+            AmominuD(_) => "amominu.d",
+            AmomaxuD(_) => "amomaxu.d",
+
+            Flw(_) => "flw",
+            Fsw(_) => "fsw",
+            FmaddS(_) => "fmadd.s",
+            FmsubS(_) => "fmsub.s",
+            FnmsubS(_) => "fnmsub.s",
+            FnmaddS(_) => "fnmadd.s",
+            FaddS(_) => "fadd.s",
+            FsubS(_) => "fsub.s",
+            FmulS(_) => "fmul.s",
+            FdivS(_) => "fdiv.s",
+            FsqrtS(_) => "fsqrt.s",
+            FsgnjS(_) => "fsgnj.s",
+            FsgnjnS(_) => "fsgnjn.s",
+            FsgnjxS(_) => "fsgnjx.s",
+            FminS(_) => "fmin.s",
+            FmaxS(_) => "fmax.s",
+            FcvtWS(_) => "fcvt.w.s",
+            FcvtWuS(_) => "fcvt.wu.s",
+            FcvtLS(_) => "fcvt.l.s",
+            FcvtLuS(_) => "fcvt.lu.s",
+            FmvXW(_) => "fmv.x.w",
+            FeqS(_) => "feq.s",
+            FltS(_) => "flt.s",
+            FleS(_) => "fle.s",
+            FclassS(_) => "fclass.s",
+            FcvtSW(_) => "fcvt.s.w",
+            FcvtSWu(_) => "fcvt.s.wu",
+            FcvtSL(_) => "fcvt.s.l",
+            FcvtSLu(_) => "fcvt.s.lu",
+            FmvWX(_) => "fmv.w.x",
+
+            Fld(_) => "fld",
+            Fsd(_) => "fsd",
+            FmaddD(_) => "fmadd.d",
+            FmsubD(_) => "fmsub.d",
+            FnmsubD(_) => "fnmsub.d",
+            FnmaddD(_) => "fnmadd.d",
+            FaddD(_) => "fadd.d",
+            FsubD(_) => "fsub.d",
+            FmulD(_) => "fmul.d",
+            FdivD(_) => "fdiv.d",
+            FsqrtD(_) => "fsqrt.d",
+            FsgnjD(_) => "fsgnj.d",
+            FsgnjnD(_) => "fsgnjn.d",
+            FsgnjxD(_) => "fsgnjx.d",
+            FminD(_) => "fmin.d",
+            FmaxD(_) => "fmax.d",
+            FcvtSD(_) => "fcvt.s.d",
+            FcvtDS(_) => "fcvt.d.s",
+            FeqD(_) => "feq.d",
+            FltD(_) => "flt.d",
+            FleD(_) => "fle.d",
+            FclassD(_) => "fclass.d",
+            FcvtWD(_) => "fcvt.w.d",
+            FcvtWuD(_) => "fcvt.wu.d",
+            FcvtLD(_) => "fcvt.l.d",
+            FcvtLuD(_) => "fcvt.lu.d",
+            FcvtDW(_) => "fcvt.d.w",
+            FcvtDWu(_) => "fcvt.d.wu",
+            FcvtDL(_) => "fcvt.d.l",
+            FcvtDLu(_) => "fcvt.d.lu",
+            FmvXD(_) => "fmv.x.d",
+            FmvDX(_) => "fmv.d.x",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::Instruction::*;
+
+    #[test]
+    fn extracts_operands_from_each_format() {
+        // sd a0, -8(sp) / ld a1, 8(sp) / bne a0, zero, -4 / jal ra, 4 / lui t0, 0xfffff
+        assert_eq!(decode(0xfea13c23), Some(Sd(SType { rs1: 2, rs2: 10, imm: -8 })));
+        assert_eq!(decode(0x00813583), Some(Ld(IType { rd: 11, rs1: 2, imm: 8 })));
+        assert_eq!(decode(0xfe051ee3), Some(Bne(BType { rs1: 10, rs2: 0, imm: -4 })));
+        assert_eq!(decode(0x004000ef), Some(Jal(JType { rd: 1, imm: 4 })));
+        assert_eq!(decode(0xfffff2b7), Some(Lui(UType { rd: 5, imm: -0x1000 })));
+        // srai a0, a0, 63 / fmadd.d fa0, fa1, fa2, fa3, rtz
+        assert_eq!(decode(0x43f55513), Some(Srai(IType { rd: 10, rs1: 10, imm: 63 })));
+        assert_eq!(decode(0x6ac59543), Some(FmaddD(R4Type { rd: 10, rs1: 11, rs2: 12, rs3: 13, rm: 1 })));
+    }
+
+    #[test]
+    fn tells_instructions_apart_by_their_function_fields() {
+        // sll / mulh share their opcode and funct3, fcvt.s.d / fcvt.d.s are told apart by rs2
+        assert_eq!(decode(0x00b51533).map(|i| i.mnemonic()), Some("sll"));
+        assert_eq!(decode(0x02b51533).map(|i| i.mnemonic()), Some("mulh"));
+        assert_eq!(decode(0x40157553).map(|i| i.mnemonic()), Some("fcvt.s.d"));
+        assert_eq!(decode(0x42057553).map(|i| i.mnemonic()), Some("fcvt.d.s"));
+        assert_eq!(decode(0x0005252f).map(|i| i.extension()), Some(Extension::A));
+        // sll with a reserved funct7, sraiw with the top bit of a 6-bit shift amount
+        assert_eq!(decode(0x20b51533), None);
+        assert_eq!(decode(0x4205551b), None);
+    }
+}
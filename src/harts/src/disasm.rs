@@ -0,0 +1,372 @@
+use crate::{
+    decode::{decode, AmoType, BType, CsrType, IType, Instruction, JType, R4Type, RType, RmType, SType, UType},
+    hart::Reg,
+    util::get_bits
+};
+
+const F_REGS: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7",
+    "fs0", "fs1",
+    "fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7",
+    "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9", "fs10", "fs11",
+    "ft8", "ft9", "ft10", "ft11",
+];
+
+const CSRS: [(u32, &str); 17] = [
+    (0x001, "fflags"), (0x002, "frm"), (0x003, "fcsr"),
+    (0xC00, "cycle"), (0xC01, "time"), (0xC02, "instret"),
+    (0x300, "mstatus"), (0x301, "misa"), (0x304, "mie"), (0x305, "mtvec"),
+    (0x340, "mscratch"), (0x341, "mepc"), (0x342, "mcause"), (0x343, "mtval"), (0x344, "mip"),
+    (0xB00, "mcycle"), (0xF14, "mhartid"),
+];
+
+fn x(reg: impl Into<u32>) -> String {
+    format!("{:?}", Reg::from_index(reg.into()))
+}
+
+fn f(reg: impl Into<u32>) -> &'static str {
+    F_REGS[reg.into() as usize]
+}
+
+// Registers x8 to x15, the only ones most compressed instructions reach
+fn x_prime(reg: u32) -> String {
+    x(reg + 8)
+}
+
+fn f_prime(reg: u32) -> &'static str {
+    f(reg + 8)
+}
+
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    (((value << (32 - bits)) as i32) >> (32 - bits)) as i64
+}
+
+// Bits `high` to `low` of `inst`, moved to start at bit `at`
+fn field(inst: u32, high: u8, low: u8, at: u32) -> u32 {
+    get_bits(high, low, inst) << at
+}
+
+fn target(pc: u64, offset: i64) -> String {
+    format!("{:#x}", pc.wrapping_add(offset as u64))
+}
+
+fn csr(number: u16) -> String {
+    CSRS.iter()
+        .find(|(csr, _)| *csr == number as u32)
+        .map_or(format!("{number:#x}"), |(_, name)| name.to_string())
+}
+
+// Rounding mode operand, left out when dynamic as assemblers do
+fn rounding(rm: u8) -> &'static str {
+    match rm {
+        0 => ", rne",
+        1 => ", rtz",
+        2 => ", rdn",
+        3 => ", rup",
+        4 => ", rmm",
+        _ => "",
+    }
+}
+
+fn fence_set(set: u8) -> String {
+    let set: String = [(8, 'i'), (4, 'o'), (2, 'r'), (1, 'w')]
+        .iter()
+        .filter(|(bit, _)| set & bit != 0)
+        .map(|(_, letter)| letter)
+        .collect();
+
+    if set.is_empty() { "0".to_string() } else { set }
+}
+
+// Mnemonic, with the ordering suffix of atomic memory operations
+fn mnemonic(instruction: &Instruction) -> String {
+    use Instruction::*;
+
+    let ordering = match instruction {
+        LrW(amo) | ScW(amo) | AmoswapW(amo) | AmoaddW(amo) | AmoxorW(amo) | AmoandW(amo) | AmoorW(amo)
+            | AmominW(amo) | AmomaxW(amo) | AmominuW(amo) | AmomaxuW(amo)
+            | LrD(amo) | ScD(amo) | AmoswapD(amo) | AmoaddD(amo) | AmoxorD(amo) | AmoandD(amo) | AmoorD(amo)
+            | AmominD(amo) | AmomaxD(amo) | AmominuD(amo) | AmomaxuD(amo) => match (amo.aq, amo.rl) {
+                (true, true) => ".aqrl",
+                (true, false) => ".aq",
+                (false, true) => ".rl",
+                (false, false) => "",
+            },
+        _ => "",
+    };
+
+    format!("{}{ordering}", instruction.mnemonic())
+}
+
+fn operands(instruction: &Instruction, pc: u64) -> String {
+    use Instruction::*;
+
+    match *instruction {
+        Lui(UType { rd, imm }) | Auipc(UType { rd, imm }) => format!("{}, {:#x}", x(rd), (imm >> 12) & 0xF_FFFF),
+        Jal(JType { rd, imm }) => format!("{}, {}", x(rd), target(pc, imm)),
+        Jalr(IType { rd, rs1, imm })
+            | Lb(IType { rd, rs1, imm }) | Lh(IType { rd, rs1, imm }) | Lw(IType { rd, rs1, imm }) | Ld(IType { rd, rs1, imm })
+            | Lbu(IType { rd, rs1, imm }) | Lhu(IType { rd, rs1, imm }) | Lwu(IType { rd, rs1, imm }) => format!("{}, {imm}({})", x(rd), x(rs1)),
+        Flw(IType { rd, rs1, imm }) | Fld(IType { rd, rs1, imm }) => format!("{}, {imm}({})", f(rd), x(rs1)),
+        Beq(BType { rs1, rs2, imm }) | Bne(BType { rs1, rs2, imm }) | Blt(BType { rs1, rs2, imm })
+            | Bge(BType { rs1, rs2, imm }) | Bltu(BType { rs1, rs2, imm }) | Bgeu(BType { rs1, rs2, imm }) => {
+            format!("{}, {}, {}", x(rs1), x(rs2), target(pc, imm))
+        },
+        Sb(SType { rs1, rs2, imm }) | Sh(SType { rs1, rs2, imm }) | Sw(SType { rs1, rs2, imm }) | Sd(SType { rs1, rs2, imm }) => {
+            format!("{}, {imm}({})", x(rs2), x(rs1))
+        },
+        Fsw(SType { rs1, rs2, imm }) | Fsd(SType { rs1, rs2, imm }) => format!("{}, {imm}({})", f(rs2), x(rs1)),
+        Addi(IType { rd, rs1, imm }) | Slti(IType { rd, rs1, imm }) | Sltiu(IType { rd, rs1, imm }) | Xori(IType { rd, rs1, imm })
+            | Ori(IType { rd, rs1, imm }) | Andi(IType { rd, rs1, imm }) | Slli(IType { rd, rs1, imm }) | Srli(IType { rd, rs1, imm })
+            | Srai(IType { rd, rs1, imm }) | Addiw(IType { rd, rs1, imm }) | Slliw(IType { rd, rs1, imm })
+            | Srliw(IType { rd, rs1, imm }) | Sraiw(IType { rd, rs1, imm }) => format!("{}, {}, {imm}", x(rd), x(rs1)),
+        Add(RType { rd, rs1, rs2 }) | Sub(RType { rd, rs1, rs2 }) | Sll(RType { rd, rs1, rs2 }) | Slt(RType { rd, rs1, rs2 })
+            | Sltu(RType { rd, rs1, rs2 }) | Xor(RType { rd, rs1, rs2 }) | Srl(RType { rd, rs1, rs2 }) | Sra(RType { rd, rs1, rs2 })
+            | Or(RType { rd, rs1, rs2 }) | And(RType { rd, rs1, rs2 }) | Addw(RType { rd, rs1, rs2 }) | Subw(RType { rd, rs1, rs2 })
+            | Sllw(RType { rd, rs1, rs2 }) | Srlw(RType { rd, rs1, rs2 }) | Sraw(RType { rd, rs1, rs2 })
+            | Mul(RType { rd, rs1, rs2 }) | Mulh(RType { rd, rs1, rs2 }) | Mulhsu(RType { rd, rs1, rs2 }) | Mulhu(RType { rd, rs1, rs2 })
+            | Div(RType { rd, rs1, rs2 }) | Divu(RType { rd, rs1, rs2 }) | Rem(RType { rd, rs1, rs2 }) | Remu(RType { rd, rs1, rs2 })
+            | Mulw(RType { rd, rs1, rs2 }) | Divw(RType { rd, rs1, rs2 }) | Divuw(RType { rd, rs1, rs2 })
+            | Remw(RType { rd, rs1, rs2 }) | Remuw(RType { rd, rs1, rs2 }) => format!("{}, {}, {}", x(rd), x(rs1), x(rs2)),
+        Fence { pred, succ } => format!("{}, {}", fence_set(pred), fence_set(succ)),
+        FenceI | Ecall | Ebreak => String::new(),
+        Csrrw(CsrType { rd, rs1, csr: number }) | Csrrs(CsrType { rd, rs1, csr: number })
+            | Csrrc(CsrType { rd, rs1, csr: number }) => format!("{}, {}, {}", x(rd), csr(number), x(rs1)),
+        Csrrwi(CsrType { rd, rs1, csr: number }) | Csrrsi(CsrType { rd, rs1, csr: number })
+            | Csrrci(CsrType { rd, rs1, csr: number }) => format!("{}, {}, {rs1}", x(rd), csr(number)),
+        LrW(AmoType { rd, rs1, .. }) | LrD(AmoType { rd, rs1, .. }) => format!("{}, ({})", x(rd), x(rs1)),
+        ScW(AmoType { rd, rs1, rs2, .. }) | AmoswapW(AmoType { rd, rs1, rs2, .. }) | AmoaddW(AmoType { rd, rs1, rs2, .. })
+            | AmoxorW(AmoType { rd, rs1, rs2, .. }) | AmoandW(AmoType { rd, rs1, rs2, .. }) | AmoorW(AmoType { rd, rs1, rs2, .. })
+            | AmominW(AmoType { rd, rs1, rs2, .. }) | AmomaxW(AmoType { rd, rs1, rs2, .. }) | AmominuW(AmoType { rd, rs1, rs2, .. })
+            | AmomaxuW(AmoType { rd, rs1, rs2, .. })
+            | ScD(AmoType { rd, rs1, rs2, .. }) | AmoswapD(AmoType { rd, rs1, rs2, .. }) | AmoaddD(AmoType { rd, rs1, rs2, .. })
+            | AmoxorD(AmoType { rd, rs1, rs2, .. }) | AmoandD(AmoType { rd, rs1, rs2, .. }) | AmoorD(AmoType { rd, rs1, rs2, .. })
+            | AmominD(AmoType { rd, rs1, rs2, .. }) | AmomaxD(AmoType { rd, rs1, rs2, .. }) | AmominuD(AmoType { rd, rs1, rs2, .. })
+            | AmomaxuD(AmoType { rd, rs1, rs2, .. }) => format!("{}, {}, ({})", x(rd), x(rs2), x(rs1)),
+        FmaddS(R4Type { rd, rs1, rs2, rs3, rm }) | FmsubS(R4Type { rd, rs1, rs2, rs3, rm })
+            | FnmsubS(R4Type { rd, rs1, rs2, rs3, rm }) | FnmaddS(R4Type { rd, rs1, rs2, rs3, rm })
+            | FmaddD(R4Type { rd, rs1, rs2, rs3, rm }) | FmsubD(R4Type { rd, rs1, rs2, rs3, rm })
+            | FnmsubD(R4Type { rd, rs1, rs2, rs3, rm }) | FnmaddD(R4Type { rd, rs1, rs2, rs3, rm }) => {
+            format!("{}, {}, {}, {}{}", f(rd), f(rs1), f(rs2), f(rs3), rounding(rm))
+        },
+        FaddS(RmType { rd, rs1, rs2, rm }) | FsubS(RmType { rd, rs1, rs2, rm }) | FmulS(RmType { rd, rs1, rs2, rm })
+            | FdivS(RmType { rd, rs1, rs2, rm }) | FaddD(RmType { rd, rs1, rs2, rm }) | FsubD(RmType { rd, rs1, rs2, rm })
+            | FmulD(RmType { rd, rs1, rs2, rm }) | FdivD(RmType { rd, rs1, rs2, rm }) => {
+            format!("{}, {}, {}{}", f(rd), f(rs1), f(rs2), rounding(rm))
+        },
+        FsqrtS(RmType { rd, rs1, rm, .. }) | FsqrtD(RmType { rd, rs1, rm, .. })
+            | FcvtSD(RmType { rd, rs1, rm, .. }) | FcvtDS(RmType { rd, rs1, rm, .. }) => format!("{}, {}{}", f(rd), f(rs1), rounding(rm)),
+        FsgnjS(RType { rd, rs1, rs2 }) | FsgnjnS(RType { rd, rs1, rs2 }) | FsgnjxS(RType { rd, rs1, rs2 })
+            | FminS(RType { rd, rs1, rs2 }) | FmaxS(RType { rd, rs1, rs2 })
+            | FsgnjD(RType { rd, rs1, rs2 }) | FsgnjnD(RType { rd, rs1, rs2 }) | FsgnjxD(RType { rd, rs1, rs2 })
+            | FminD(RType { rd, rs1, rs2 }) | FmaxD(RType { rd, rs1, rs2 }) => format!("{}, {}, {}", f(rd), f(rs1), f(rs2)),
+        FeqS(RType { rd, rs1, rs2 }) | FltS(RType { rd, rs1, rs2 }) | FleS(RType { rd, rs1, rs2 })
+            | FeqD(RType { rd, rs1, rs2 }) | FltD(RType { rd, rs1, rs2 }) | FleD(RType { rd, rs1, rs2 }) => {
+            format!("{}, {}, {}", x(rd), f(rs1), f(rs2))
+        },
+        FcvtWS(RmType { rd, rs1, rm, .. }) | FcvtWuS(RmType { rd, rs1, rm, .. }) | FcvtLS(RmType { rd, rs1, rm, .. })
+            | FcvtLuS(RmType { rd, rs1, rm, .. }) | FcvtWD(RmType { rd, rs1, rm, .. }) | FcvtWuD(RmType { rd, rs1, rm, .. })
+            | FcvtLD(RmType { rd, rs1, rm, .. }) | FcvtLuD(RmType { rd, rs1, rm, .. }) => format!("{}, {}{}", x(rd), f(rs1), rounding(rm)),
+        FcvtSW(RmType { rd, rs1, rm, .. }) | FcvtSWu(RmType { rd, rs1, rm, .. }) | FcvtSL(RmType { rd, rs1, rm, .. })
+            | FcvtSLu(RmType { rd, rs1, rm, .. }) | FcvtDW(RmType { rd, rs1, rm, .. }) | FcvtDWu(RmType { rd, rs1, rm, .. })
+            | FcvtDL(RmType { rd, rs1, rm, .. }) | FcvtDLu(RmType { rd, rs1, rm, .. }) => format!("{}, {}{}", f(rd), x(rs1), rounding(rm)),
+        FmvXW(RType { rd, rs1, .. }) | FclassS(RType { rd, rs1, .. })
+            | FmvXD(RType { rd, rs1, .. }) | FclassD(RType { rd, rs1, .. }) => format!("{}, {}", x(rd), f(rs1)),
+        FmvWX(RType { rd, rs1, .. }) | FmvDX(RType { rd, rs1, .. }) => format!("{}, {}", f(rd), x(rs1)),
+    }
+}
+
+/// Disassembles a compressed instruction, in the `c.` syntax it is written with
+pub fn disassemble_compressed(inst: u16, pc: u64) -> String {
+    let inst = inst as u32;
+    let funct3 = get_bits(15, 13, inst);
+    let rd = get_bits(11, 7, inst);
+    let rs2 = get_bits(6, 2, inst);
+    let rd_prime = get_bits(9, 7, inst);
+    let rs2_prime = get_bits(4, 2, inst);
+
+    let imm6 = sign_extend(field(inst, 12, 12, 5) | get_bits(6, 2, inst), 6);
+    let shamt = field(inst, 12, 12, 5) | get_bits(6, 2, inst);
+    // Offsets of the word and double-word accesses, relative to a register and to sp
+    let word = field(inst, 12, 10, 3) | field(inst, 6, 6, 2) | field(inst, 5, 5, 6);
+    let double = field(inst, 12, 10, 3) | field(inst, 6, 5, 6);
+    let word_sp = field(inst, 12, 12, 5) | field(inst, 6, 4, 2) | field(inst, 3, 2, 6);
+    let double_sp = field(inst, 12, 12, 5) | field(inst, 6, 5, 3) | field(inst, 4, 2, 6);
+    let store_word_sp = field(inst, 12, 9, 2) | field(inst, 8, 7, 6);
+    let store_double_sp = field(inst, 12, 10, 3) | field(inst, 9, 7, 6);
+
+    let disassembly = match (get_bits(1, 0, inst), funct3) {
+        (0, 0) if inst != 0 => {
+            let imm = field(inst, 12, 11, 4) | field(inst, 10, 7, 6) | field(inst, 6, 6, 2) | field(inst, 5, 5, 3);
+            (imm != 0).then(|| format!("c.addi4spn {}, sp, {imm}", x_prime(rs2_prime)))
+        },
+        (0, 1) => Some(format!("c.fld {}, {double}({})", f_prime(rs2_prime), x_prime(rd_prime))),
+        (0, 2) => Some(format!("c.lw {}, {word}({})", x_prime(rs2_prime), x_prime(rd_prime))),
+        (0, 3) => Some(format!("c.ld {}, {double}({})", x_prime(rs2_prime), x_prime(rd_prime))),
+        (0, 5) => Some(format!("c.fsd {}, {double}({})", f_prime(rs2_prime), x_prime(rd_prime))),
+        (0, 6) => Some(format!("c.sw {}, {word}({})", x_prime(rs2_prime), x_prime(rd_prime))),
+        (0, 7) => Some(format!("c.sd {}, {double}({})", x_prime(rs2_prime), x_prime(rd_prime))),
+        (1, 0) if rd == 0 => Some("c.nop".to_string()),
+        (1, 0) => Some(format!("c.addi {}, {imm6}", x(rd))),
+        (1, 1) if rd != 0 => Some(format!("c.addiw {}, {imm6}", x(rd))),
+        (1, 2) => Some(format!("c.li {}, {imm6}", x(rd))),
+        (1, 3) if rd == 2 => {
+            let imm = sign_extend(
+                field(inst, 12, 12, 9) | field(inst, 6, 6, 4) | field(inst, 5, 5, 6) | field(inst, 4, 3, 7) | field(inst, 2, 2, 5),
+                10
+            );
+            (imm != 0).then(|| format!("c.addi16sp sp, {imm}"))
+        },
+        (1, 3) => (imm6 != 0).then(|| format!("c.lui {}, {:#x}", x(rd), imm6 as u32 & 0xF_FFFF)),
+        (1, 4) => match (get_bits(11, 10, inst), get_bits(12, 12, inst), get_bits(6, 5, inst)) {
+            (0, _, _) => Some(format!("c.srli {}, {shamt}", x_prime(rd_prime))),
+            (1, _, _) => Some(format!("c.srai {}, {shamt}", x_prime(rd_prime))),
+            (2, _, _) => Some(format!("c.andi {}, {imm6}", x_prime(rd_prime))),
+            (_, high, low) => {
+                let op = ["c.sub", "c.xor", "c.or", "c.and", "c.subw", "c.addw", "", ""][(high * 4 + low) as usize];
+                (!op.is_empty()).then(|| format!("{op} {}, {}", x_prime(rd_prime), x_prime(rs2_prime)))
+            },
+        },
+        (1, 5) => {
+            let offset = sign_extend(
+                field(inst, 12, 12, 11) | field(inst, 11, 11, 4) | field(inst, 10, 9, 8) | field(inst, 8, 8, 10)
+                    | field(inst, 7, 7, 6) | field(inst, 6, 6, 7) | field(inst, 5, 3, 1) | field(inst, 2, 2, 5),
+                12
+            );
+            Some(format!("c.j {}", target(pc, offset)))
+        },
+        (1, 6 | 7) => {
+            let offset = sign_extend(
+                field(inst, 12, 12, 8) | field(inst, 11, 10, 3) | field(inst, 6, 5, 6) | field(inst, 4, 3, 1) | field(inst, 2, 2, 5),
+                9
+            );
+            let op = if funct3 == 6 { "c.beqz" } else { "c.bnez" };
+            Some(format!("{op} {}, {}", x_prime(rd_prime), target(pc, offset)))
+        },
+        (2, 0) if rd != 0 => Some(format!("c.slli {}, {shamt}", x(rd))),
+        (2, 1) => Some(format!("c.fldsp {}, {double_sp}(sp)", f(rd))),
+        (2, 2) if rd != 0 => Some(format!("c.lwsp {}, {word_sp}(sp)", x(rd))),
+        (2, 3) if rd != 0 => Some(format!("c.ldsp {}, {double_sp}(sp)", x(rd))),
+        (2, 4) => match (get_bits(12, 12, inst), rd, rs2) {
+            (0, 0, 0) => None,
+            (0, rs1, 0) => Some(format!("c.jr {}", x(rs1))),
+            (0, rd, rs2) => Some(format!("c.mv {}, {}", x(rd), x(rs2))),
+            (_, 0, 0) => Some("c.ebreak".to_string()),
+            (_, rs1, 0) => Some(format!("c.jalr {}", x(rs1))),
+            (_, rd, rs2) => Some(format!("c.add {}, {}", x(rd), x(rs2))),
+        },
+        (2, 5) => Some(format!("c.fsdsp {}, {store_double_sp}(sp)", f(rs2))),
+        (2, 6) => Some(format!("c.swsp {}, {store_word_sp}(sp)", x(rs2))),
+        (2, 7) => Some(format!("c.sdsp {}, {store_double_sp}(sp)", x(rs2))),
+        _ => None,
+    };
+
+    disassembly.unwrap_or(format!(".half {inst:#06x}"))
+}
+
+/// Disassembles an instruction fetched from `pc` into assembler syntax with ABI register names.
+/// Only the low half of compressed instructions is read. Branch and jump targets are shown as
+/// addresses, and encodings that are not instructions as the directive that would emit them
+pub fn disassemble(inst: u32, pc: u64) -> String {
+    if inst & 0b11 != 0b11 {
+        return disassemble_compressed(inst as u16, pc);
+    }
+
+    match decode(inst) {
+        Some(instruction) => {
+            let mnemonic = mnemonic(&instruction);
+            let operands = operands(&instruction, pc);
+            if operands.is_empty() { mnemonic } else { format!("{mnemonic} {operands}") }
+        },
+        None => format!(".word {inst:#010x}"),
+    }
+}
+
+/// Size in bytes of the instruction whose low half is `low`
+pub fn instruction_size(low: u16) -> usize {
+    if low & 0b11 == 0b11 { 4 } else { 2 }
+}
+
+/// Disassembles `code` as loaded at `address`, as `(address, encoding, disassembly)` for each
+/// instruction. A trailing instruction cut short is left out
+pub fn disassemble_all(code: &[u8], address: u64) -> Vec<(u64, u32, String)> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset + 2 <= code.len() {
+        let size = instruction_size(u16::from_le_bytes([code[offset], code[offset + 1]]));
+        let Some(bytes) = code.get(offset..offset + size) else { break };
+
+        let inst = bytes.iter().rev().fold(0, |inst, byte| inst << 8 | *byte as u32);
+        let pc = address + offset as u64;
+        instructions.push((pc, inst, disassemble(inst, pc)));
+        offset += size;
+    }
+
+    instructions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disassembles_every_extension() {
+        let cases = [
+            (0x00300413, "addi s0, zero, 3"),
+            (0xfff40413, "addi s0, s0, -1"),
+            (0x004000ef, "jal ra, 0x1004"),
+            (0x00008067, "jalr zero, 0(ra)"),
+            (0xfe051ee3, "bne a0, zero, 0xffc"),
+            (0x00a13423, "sd a0, 8(sp)"),
+            (0x00813583, "ld a1, 8(sp)"),
+            (0x000122b7, "lui t0, 0x12"),
+            (0x40b50533, "sub a0, a0, a1"),
+            (0x02848933, "mul s2, s1, s0"),
+            (0x0ff0000f, "fence iorw, iorw"),
+            (0x00100073, "ebreak"),
+            (0x30002573, "csrrs a0, mstatus, zero"),
+            (0x0005252f, "amoadd.w a0, zero, (a0)"),
+            (0x1405352f, "lr.d.aq a0, (a0)"),
+            (0x00b57553, "fadd.s fa0, fa0, fa1"),
+            (0xc2051553, "fcvt.w.d a0, fa0, rtz"),
+            (0x00853507, "fld fa0, 8(a0)"),
+            (0xffffffff, ".word 0xffffffff"),
+        ];
+
+        for (inst, expected) in cases {
+            assert_eq!(disassemble(inst, 0x1000), expected, "{inst:#010x}");
+        }
+    }
+
+    #[test]
+    fn disassembles_compressed_instructions() {
+        let cases = [
+            (0x0001, "c.nop"),
+            (0x0505, "c.addi a0, 1"),
+            (0x557d, "c.li a0, -1"),
+            (0x717d, "c.addi16sp sp, -16"),
+            (0x8082, "c.jr ra"),
+            (0x852e, "c.mv a0, a1"),
+            (0x9002, "c.ebreak"),
+            (0xe406, "c.sdsp ra, 8(sp)"),
+            (0x60a2, "c.ldsp ra, 8(sp)"),
+            (0x6508, "c.ld a0, 8(a0)"),
+            (0x8d0d, "c.sub a0, a1"),
+            (0xa001, "c.j 0x1000"),
+            (0xc119, "c.beqz a0, 0x1006"),
+            (0x0000, ".half 0x0000"),
+        ];
+
+        for (inst, expected) in cases {
+            assert_eq!(disassemble_compressed(inst, 0x1000), expected, "{inst:#06x}");
+        }
+
+        let code = [0x05, 0x05, 0x13, 0x04, 0x30, 0x00, 0x82];
+        let instructions = disassemble_all(&code, 0x100);
+        assert_eq!(instructions, vec![
+            (0x100, 0x0505, "c.addi a0, 1".to_string()),
+            (0x102, 0x00300413, "addi s0, zero, 3".to_string()),
+        ]);
+    }
+}
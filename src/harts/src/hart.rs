@@ -1,3 +1,4 @@
+mod decode_cache;
 mod extensions;
 mod rv64i;
 
@@ -10,6 +11,9 @@ use narvi_core::{
     event::{
         Event,
         EventPayload,
+        InstructionClass,
+        JournalEvent,
+        RegisterFile,
         Target,
     }
 };
@@ -21,11 +25,14 @@ use std::{
     }
 };
 
-use crate::util::{sign_extend_32, sign_extend_64, sign_extend_128};
+use crate::decode::{Extension, Instruction};
+use crate::disasm::disassemble;
+use decode_cache::DecodeCache;
+use crate::util::sign_extend_64;
 
 #[allow(dead_code, unused_variables, non_camel_case_types)]
-#[derive(Debug)]
-enum Reg {
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Reg {
     /*
     * ra = Return Adress
     * sp = Stack Pointer
@@ -41,6 +48,21 @@ enum Reg {
     t3 = 28, t4 = 29, t5 = 30, t6 = 31,
 }
 
+impl Reg {
+    const ALL: [Reg; 32] = [
+        Reg::zero, Reg::ra, Reg::sp, Reg::gp, Reg::tp,
+        Reg::t0, Reg::t1, Reg::t2,
+        Reg::s0, Reg::s1,
+        Reg::a0, Reg::a1, Reg::a2, Reg::a3, Reg::a4, Reg::a5, Reg::a6, Reg::a7,
+        Reg::s2, Reg::s3, Reg::s4, Reg::s5, Reg::s6, Reg::s7, Reg::s8, Reg::s9, Reg::s10, Reg::s11,
+        Reg::t3, Reg::t4, Reg::t5, Reg::t6,
+    ];
+
+    pub(crate) fn from_index(x: u32) -> Reg {
+        Self::ALL[x as usize]
+    }
+}
+
 #[derive(PartialEq, Eq)]
 pub enum HartError {
     RegisterNotFound,
@@ -56,7 +78,7 @@ impl HartError {
     fn as_str(&self) -> String {
         match self {
             Self::RegisterNotFound => "RegisterNotFound".to_string(),
-            Self::InstructionNotFound(opcode) => format!("InstructionNotFound({opcode:X})"),
+            Self::InstructionNotFound(opcode) => format!("InstructionNotFound({opcode:X}: {})", disassemble(*opcode as u32, 0)),
             Self::ExecutionError => "ExecutionError".to_string(),
             Self::ReservedInstruction(inst) => format!("ReservedInstruction({inst})"),
             Self::InstructionAddressMisaligned => "InstructionAddressMisaligned".to_string(),
@@ -121,9 +143,12 @@ enum MemoryWaitState {
 #[allow(dead_code, unused_variables)]
 #[derive(Clone, Debug, PartialEq)]
 pub struct Hart {
-    memory_bus_target: ModuleId,
+    instruction_port: ModuleId,
+    data_port: ModuleId,
 
     memory_wait_state: MemoryWaitState,
+    // Cycle at which the request being waited on was sent
+    waiting_since: u64,
 
     extensions: Extensions,
     // Registers
@@ -135,7 +160,10 @@ pub struct Hart {
     flen: u8,
     fcsr: u32,
     // TODO: temporary flag used by ebreak (see rv64i implementation)
-    break_e: bool
+    break_e: bool,
+    // Registers written since they were last journaled
+    writebacks: Vec<(RegisterFile, u8, u64)>,
+    decode_cache: DecodeCache,
 }
 
 impl Module for Hart { 
@@ -144,32 +172,40 @@ impl Module for Hart {
             EventPayload::HartExecute | EventPayload::Reset => { 
                 engine_context.schedule(
                     1,
-                    Target::Module(self.memory_bus_target),
-                    EventPayload::MemoryLoadReq { 
+                    Target::Module(self.instruction_port),
+                    EventPayload::MemoryFetchReq { 
                         address: self.pc as usize, 
                         size_in_bytes: 4,
-                        requester: Target::Myself
+                        requester: Target::Myself,
+                        pc: Some(self.pc)
                     }
                 );
 
                 self.memory_wait_state = MemoryWaitState::Opcode;
+                self.waiting_since = engine_context.current_time();
             },
-            EventPayload::MemoryLoadRes { data } => { 
+            EventPayload::MemoryLoadRes { data, .. } => { 
                 let current_state = std::mem::replace(&mut self.memory_wait_state, MemoryWaitState::Idle);
 
+                if current_state != MemoryWaitState::Idle {
+                    let waited = engine_context.current_time() - self.waiting_since;
+                    engine_context.record_journal(JournalEvent::CyclesLost { cycles: waited as usize });
+                }
+
                 match current_state {
                     MemoryWaitState::Idle => (),
                     MemoryWaitState::Opcode => {
                         let status = self.execute(data.to_u32().unwrap(), engine_context).unwrap();
 
-                        engine_context.record_journal(narvi_core::event::JournalEvent::HartInstruction);
-
-                        if !status {
-                            engine_context.schedule(
-                                1,
-                                Target::Myself, 
-                                EventPayload::HartExecute
-                            );
+                        if status {
+                            // Halted, every cycle so far was spent by this hart
+                            let cycles = engine_context.current_time() as usize;
+                            engine_context.record_journal(JournalEvent::Cycles { cycles });
+                        } else if self.memory_wait_state == MemoryWaitState::Idle {
+                            self.schedule_next(engine_context);
+                        } else {
+                            // Loads resume execution only once their data arrives
+                            self.waiting_since = engine_context.current_time();
                         }
                     }
                     MemoryWaitState::DataForIReg { 
@@ -177,19 +213,26 @@ impl Module for Hart {
                         size,
                         mode
                     } => {
+                        let value = data.iter().rev().fold(0, |value, byte| value << 8 | *byte as u64);
                         let data = if mode == IMode::Signed {
-                            sign_extend_64(data.to_u64().unwrap(), size as u8) as u64
+                            sign_extend_64(value, size as u8 * 8)
                         } else {
-                            data.to_u64().unwrap()
+                            value
                         };
 
                         self.set_reg(target, data);
+                        self.record_writebacks(engine_context);
+                        self.schedule_next(engine_context);
                     },
                     MemoryWaitState::DataForFReg { target } => {
                         self.set_fp_reg_32_bits(target, data.to_u32().unwrap());
+                        self.record_writebacks(engine_context);
+                        self.schedule_next(engine_context);
                     },
                     MemoryWaitState::DataForDReg { target } => {
                         self.set_fp_reg_64(target, f64::from_bits(data.to_u64().unwrap()));
+                        self.record_writebacks(engine_context);
+                        self.schedule_next(engine_context);
                     }
                 }
             },
@@ -202,7 +245,8 @@ impl Module for Hart {
 impl From<HartConfig> for Hart {
     fn from(config: HartConfig) -> Self {
         Hart{
-            memory_bus_target: None,
+            instruction_port: None,
+            data_port: None,
             memory_wait_state: MemoryWaitState::Opcode,
             extensions: config.extensions,
             regs: vec![0; 32],
@@ -221,10 +265,12 @@ impl From<HartConfig> for Hart {
 */
 
 impl Hart {
-    pub fn from_extensions(extensions: &Extensions, memory_bus_target: ModuleId) -> Hart {
+    pub fn from_extensions(extensions: &Extensions, instruction_port: ModuleId, data_port: ModuleId) -> Hart {
         Hart {
-            memory_bus_target,
+            instruction_port,
+            data_port,
             memory_wait_state: MemoryWaitState::Opcode,
+            waiting_since: 0,
             extensions: *extensions,
             regs: vec![0; 32],
             pc: 0,
@@ -235,10 +281,31 @@ impl Hart {
                 (false, false) => 0,
             },
             fcsr: 0,
-            break_e: false
+            break_e: false,
+            writebacks: Vec::new(),
+            decode_cache: DecodeCache::default(),
+        }
+    }
+
+    /// Sets the address of the first instruction to fetch
+    pub fn set_pc(&mut self, pc: u64) {
+        self.pc = pc;
+    }
+
+    fn record_writebacks(&mut self, engine_context: &mut dyn EngineContext) {
+        for (file, index, value) in self.writebacks.drain(..) {
+            engine_context.record_journal(JournalEvent::HartWriteback { file, index, value });
         }
     }
 
+    fn schedule_next(&self, engine_context: &mut dyn EngineContext) {
+        engine_context.schedule(
+            1,
+            Target::Myself,
+            EventPayload::HartExecute
+        );
+    }
+
     fn get_reg(&self, x: u8) -> Result<u64, HartError> {
         if x > 31 {
             Err(HartError::RegisterNotFound)
@@ -257,6 +324,7 @@ impl Hart {
         }
         else {
             self.regs[x as usize] = value;
+            self.writebacks.push((RegisterFile::X, x, value));
             Ok(())
         }
     }
@@ -299,6 +367,7 @@ impl Hart {
                 32 => {
                     if let FRegs::F(ref mut v) = self.f_regs {
                         v[x as usize] = f32::from_bits(value);
+                        self.writebacks.push((RegisterFile::F, x, value as u64));
                     }
                     else { unreachable!("FLEN not matching FRegs len"); }
                 }
@@ -306,6 +375,7 @@ impl Hart {
                     if let FRegs::D(ref mut v) = self.f_regs {
                         let val:f64 = f64::from_bits( 0xFFFF_FFFF_0000_0000 | (value as u64) );
                         v[x as usize] = val;
+                        self.writebacks.push((RegisterFile::F, x, val.to_bits()));
                     }
                     else { unreachable!("FLEN not matching FRegs len"); }
                 }
@@ -372,6 +442,7 @@ impl Hart {
                 32 => {
                     if let FRegs::F(ref mut v) = self.f_regs {
                         v[x as usize] = value;
+                        self.writebacks.push((RegisterFile::F, x, value.to_bits() as u64));
                     }
                     else { unreachable!("FLEN not matching FRegs len"); }
                 }
@@ -379,6 +450,7 @@ impl Hart {
                     if let FRegs::D(ref mut v) = self.f_regs {
                         let val:f64 = f64::from_bits( 0xFFFF_FFFF_0000_0000 | (value.to_bits() as u64) );
                         v[x as usize] = val;
+                        self.writebacks.push((RegisterFile::F, x, val.to_bits()));
                     }
                     else { unreachable!("FLEN not matching FRegs len"); }
                 }
@@ -400,6 +472,7 @@ impl Hart {
                 64 => {
                     if let FRegs::D(ref mut v) = self.f_regs {
                         v[x as usize] = value;
+                        self.writebacks.push((RegisterFile::F, x, value.to_bits()));
                     }
                     else { unreachable!("FLEN not matching FRegs len"); }
                 }
@@ -411,22 +484,60 @@ impl Hart {
 
     /// Simulates full pipeline execution for one instruction
     pub fn execute(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<bool, HartError> {
-        let mut result = self.execute_rv64i(inst, engine_context);
+        let pc = self.pc;
+        let instruction = self.decode_cache.decode(pc, inst).filter(|instruction| self.implements(instruction));
+
+        let result = match instruction {
+            Some(instruction) => match instruction.extension() {
+                Extension::I => self.execute_rv64i(instruction, engine_context),
+                Extension::M => self.execute_m(instruction),
+                Extension::F => self.execute_f(instruction, engine_context),
+                Extension::D => self.execute_d(instruction, engine_context),
+                Extension::A => Err(HartError::InstructionNotFound(inst as u64)),
+            },
+            None => Err(HartError::InstructionNotFound(inst as u64)),
+        };
 
-        if matches!(result, Err(HartError::InstructionNotFound(_))) && self.extensions.m {
-            result = self.execute_m(inst);
+        if let (Ok(_), Some(instruction)) = (&result, instruction) {
+            let class = self.classify(&instruction, pc);
+            let mnemonic = instruction.mnemonic();
+            engine_context.record_journal(JournalEvent::HartInstruction { pc, inst, class, mnemonic });
         }
+        self.record_writebacks(engine_context);
 
-        if matches!(result, Err(HartError::InstructionNotFound(_))) && self.extensions.f {
-            result = self.execute_f(inst, engine_context);
-        }
+        self.pc = self.pc.wrapping_add(4);
+        
+        result.map(|_| self.break_e)
+    }
 
-        if matches!(result, Err(HartError::InstructionNotFound(_))) && self.extensions.d {
-            result = self.execute_d(inst, engine_context);
+    fn implements(&self, instruction: &Instruction) -> bool {
+        match instruction.extension() {
+            Extension::I => true,
+            Extension::M => self.extensions.m,
+            Extension::A => self.extensions.a,
+            Extension::F => self.extensions.f,
+            Extension::D => self.extensions.d,
         }
+    }
 
-        self.pc = self.pc + 4;
-        
-        result.map(|_| self.break_e)
+    // Class of the instruction just run from `pc`. Branches moved the PC away from `pc` when taken
+    fn classify(&self, instruction: &Instruction, pc: u64) -> InstructionClass {
+        use Instruction::*;
+
+        match instruction {
+            Beq(_) | Bne(_) | Blt(_) | Bge(_) | Bltu(_) | Bgeu(_) if self.pc != pc => InstructionClass::BranchTaken,
+            Beq(_) | Bne(_) | Blt(_) | Bge(_) | Bltu(_) | Bgeu(_) => InstructionClass::BranchNotTaken,
+            Jal(_) | Jalr(_) => InstructionClass::Jump,
+            Lb(_) | Lh(_) | Lw(_) | Ld(_) | Lbu(_) | Lhu(_) | Lwu(_) | Flw(_) | Fld(_) => InstructionClass::Load,
+            Sb(_) | Sh(_) | Sw(_) | Sd(_) | Fsw(_) | Fsd(_) => InstructionClass::Store,
+            Csrrw(_) | Csrrs(_) | Csrrc(_) | Csrrwi(_) | Csrrsi(_) | Csrrci(_) => InstructionClass::Csr,
+            Fence { .. } | FenceI | Ecall | Ebreak => InstructionClass::System,
+            _ => match instruction.extension() {
+                Extension::M => InstructionClass::M,
+                Extension::F => InstructionClass::F,
+                Extension::D => InstructionClass::D,
+                Extension::I | Extension::A => InstructionClass::Alu,
+            },
+        }
     }
 }
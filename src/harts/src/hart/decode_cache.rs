@@ -0,0 +1,71 @@
+use crate::decode::{decode, Instruction};
+
+const ENTRIES: usize = 1024;
+
+/// Instructions decoded so far, in a direct-mapped table indexed by the physical PC they
+/// were fetched from. Entries are tagged with both the PC and the word fetched, so a word
+/// rewritten since it was decoded misses and is decoded again, and stores need not
+/// invalidate anything. FENCE.I clears the table
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct DecodeCache {
+    entries: Vec<Option<(u64, u32, Instruction)>>,
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self { entries: vec![None; ENTRIES] }
+    }
+}
+
+impl DecodeCache {
+    /// The instruction `inst` fetched from `pc`, decoding it unless it was decoded there before
+    pub(super) fn decode(&mut self, pc: u64, inst: u32) -> Option<Instruction> {
+        let slot = (pc / 4) as usize % ENTRIES;
+
+        if let Some((tag, word, instruction)) = self.entries[slot]
+            && tag == pc
+            && word == inst
+        {
+            return Some(instruction);
+        }
+
+        let instruction = decode(inst)?;
+        self.entries[slot] = Some((pc, inst, instruction));
+
+        Some(instruction)
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // addi a0, a0, 1 / addi a0, a0, 2
+    const ADD_ONE: u32 = 0x00150513;
+    const ADD_TWO: u32 = 0x00250513;
+
+    #[test]
+    fn entries_are_tagged_by_pc_and_word() {
+        let mut cache = DecodeCache::default();
+        assert_eq!(cache.decode(0x1000, ADD_ONE), decode(ADD_ONE));
+
+        // Rewritten code is decoded again, and so are PCs sharing the slot
+        assert_eq!(cache.decode(0x1000, ADD_TWO), decode(ADD_TWO));
+        assert_eq!(cache.decode(0x1000 + 4 * ENTRIES as u64, ADD_ONE), decode(ADD_ONE));
+        assert_eq!(cache.entries[0x1000 / 4 % ENTRIES], Some((0x1000 + 4 * ENTRIES as u64, ADD_ONE, decode(ADD_ONE).unwrap())));
+    }
+
+    #[test]
+    fn clear_empties_every_entry() {
+        let mut cache = DecodeCache::default();
+        cache.decode(0x1000, ADD_ONE);
+        cache.decode(0x2004, ADD_TWO);
+
+        cache.clear();
+        assert!(cache.entries.iter().all(Option::is_none));
+    }
+}
@@ -7,6 +7,8 @@ use narvi_core::{
     }
 };
 
+use crate::decode::{IType, Instruction, R4Type, RType, RmType, SType};
+
 use crate::hart::{
     Hart,
     HartError,
@@ -15,68 +17,62 @@ use crate::hart::{
 
 use crate::util::{
     get_bits,
-    sign_extend_64,
     rounding_modes::*,
 };
 
 #[allow(dead_code, unused_variables)]
 impl Hart {
-    pub fn execute_d(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let opcode = get_bits(6, 0, inst);
-        let funct3 = get_bits(14, 12, inst);
-        let funct5 = get_bits(24, 20, inst);
-        let funct2 = get_bits(26, 25, inst);
-        let funct7 = get_bits(31, 25, inst);
-        match (funct7, funct2, funct5, funct3, opcode) {
-            (        _,    _,       _, 0b011, 0b0000111) => self.fld(inst, engine_context),
-            (        _,    _,       _, 0b011, 0b0100111) => self.fsd(inst, engine_context),
-            (        _, 0b01,       _,     _, 0b1000011) => self.fmadd_d(inst),
-            (        _, 0b01,       _,     _, 0b1000111) => self.fmsub_d(inst),
-            (        _, 0b01,       _,     _, 0b1001011) => self.fnmsub_d(inst),
-            (        _, 0b01,       _,     _, 0b1001111) => self.fnmadd_d(inst),
-            (0b0000001,    _,       _,     _, 0b1010011) => self.fadd_d(inst),
-            (0b0000101,    _,       _,     _, 0b1010011) => self.fsub_d(inst),
-            (0b0001001,    _,       _,     _, 0b1010011) => self.fmul_d(inst),
-            (0b0001101,    _,       _,     _, 0b1010011) => self.fdiv_d(inst),
-            (0b0101101,    _, 0b00000,     _, 0b1010011) => self.fsqrt_d(inst),
-            (0b0010001,    _,       _, 0b000, 0b1010011) => self.fsgnj_d(inst),
-            (0b0010001,    _,       _, 0b001, 0b1010011) => self.fsgnjn_d(inst),
-            (0b0010001,    _,       _, 0b010, 0b1010011) => self.fsgnjx_d(inst),
-            (0b0010101,    _,       _, 0b000, 0b1010011) => self.fmin_d(inst),
-            (0b0010101,    _,       _, 0b001, 0b1010011) => self.fmax_d(inst),
-            (0b0100000,    _, 0b00000,     _, 0b1010011) => self.fcvt_s_d(inst),
-            (0b0100001,    _, 0b00001,     _, 0b1010011) => self.fcvt_d_s(inst),
-            (0b1010001,    _, 0b00000, 0b010, 0b1010011) => self.feq_d(inst),
-            (0b1010001,    _,       _, 0b001, 0b1010011) => self.flt_d(inst),
-            (0b1010001,    _,       _, 0b000, 0b1010011) => self.fle_d(inst),
-            (0b1110001,    _,       _, 0b001, 0b1010011) => self.fclass_d(inst),
-            (0b1100001,    _, 0b00000,     _, 0b1010011) => self.fcvt_w_d(inst),
-            (0b1100001,    _, 0b00001,     _, 0b1010011) => self.fcvt_wu_d(inst),
-            (0b1101001,    _, 0b00000,     _, 0b1010011) => self.fcvt_d_w(inst),
-            (0b1101001,    _, 0b00001,     _, 0b1010011) => self.fcvt_d_wu(inst),
-            (0b1100001,    _, 0b00010,     _, 0b1010011) => self.fcvt_l_d(inst),
-            (0b1100001,    _, 0b00011,     _, 0b1010011) => self.fcvt_lu_d(inst),
-            (0b1110001,    _, 0b00000, 0b000, 0b1010011) => self.fmv_x_d(inst),
-            (0b1101001,    _, 0b00010,     _, 0b1010011) => self.fcvt_d_l(inst),
-            (0b1101001,    _, 0b00011,     _, 0b1010011) => self.fcvt_d_lu(inst),
-            (0b1111001,    _, 0b00000, 0b000, 0b1010011) => self.fmv_d_x(inst),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
+    pub fn execute_d(&mut self, instruction: Instruction, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        use Instruction::*;
+
+        match instruction {
+            Fld(i) => self.fld(i, engine_context),
+            Fsd(s) => self.fsd(s, engine_context),
+            FmaddD(r) => self.fmadd_d(r),
+            FmsubD(r) => self.fmsub_d(r),
+            FnmsubD(r) => self.fnmsub_d(r),
+            FnmaddD(r) => self.fnmadd_d(r),
+            FaddD(r) => self.fadd_d(r),
+            FsubD(r) => self.fsub_d(r),
+            FmulD(r) => self.fmul_d(r),
+            FdivD(r) => self.fdiv_d(r),
+            FsqrtD(r) => self.fsqrt_d(r),
+            FsgnjD(r) => self.fsgnj_d(r),
+            FsgnjnD(r) => self.fsgnjn_d(r),
+            FsgnjxD(r) => self.fsgnjx_d(r),
+            FminD(r) => self.fmin_d(r),
+            FmaxD(r) => self.fmax_d(r),
+            FcvtSD(r) => self.fcvt_s_d(r),
+            FcvtDS(r) => self.fcvt_d_s(r),
+            FeqD(r) => self.feq_d(r),
+            FltD(r) => self.flt_d(r),
+            FleD(r) => self.fle_d(r),
+            FclassD(r) => self.fclass_d(r),
+            FcvtWD(r) => self.fcvt_w_d(r),
+            FcvtWuD(r) => self.fcvt_wu_d(r),
+            FcvtDW(r) => self.fcvt_d_w(r),
+            FcvtDWu(r) => self.fcvt_d_wu(r),
+            FcvtLD(r) => self.fcvt_l_d(r),
+            FcvtLuD(r) => self.fcvt_lu_d(r),
+            FmvXD(r) => self.fmv_x_d(r),
+            FcvtDL(r) => self.fcvt_d_l(r),
+            FcvtDLu(r) => self.fcvt_d_lu(r),
+            FmvDX(r) => self.fmv_d_x(r),
+            _ => Err(HartError::ExecutionError),
         }
     }
 
-    fn fld(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = self.get_reg(get_bits(19, 15, inst) as u8)?;
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_64(get_bits(31, 20, inst) as u64, 12);
-        let addr = rs1.wrapping_add(imm);
+    fn fld(&mut self, IType { rd, rs1, imm }: IType, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        let addr = self.get_reg(rs1)?.wrapping_add(imm as u64);
 
         engine_context.schedule(
             1, 
-            Target::Module(self.memory_bus_target),
+            Target::Module(self.data_port),
             EventPayload::MemoryLoadReq { 
                 address: addr as usize,
                 size_in_bytes: 8,
-                requester: Target::Myself
+                requester: Target::Myself,
+                pc: Some(self.pc)
             }
         );
 
@@ -85,21 +81,17 @@ impl Hart {
         Ok(())
     }
 
-    fn fsd(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = self.get_reg(get_bits(19, 15, inst) as u8)?;
-        let imm_bits = get_bits(11, 7, inst) | ( get_bits(31, 25, inst) << 5 );
-        let imm = sign_extend_64(imm_bits as u64, 12);
-        let addr = rs1.wrapping_add(imm);
-        let rs2 = get_bits(19, 15, inst) as u8; // Wait, rs2 is 24-20
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn fsd(&mut self, SType { rs1, rs2, imm }: SType, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        let addr = self.get_reg(rs1)?.wrapping_add(imm as u64);
         let reg_val = self.get_fp_reg_64(rs2)?.to_bits();
         
         engine_context.schedule(
             1,
-            Target::Module(self.memory_bus_target),
+            Target::Module(self.data_port),
             EventPayload::MemoryStoreReq { 
                 address: addr as usize, 
-                data: reg_val.to_le_bytes().to_vec()
+                data: reg_val.to_le_bytes().to_vec(),
+                pc: Some(self.pc)
             }
         );
 
@@ -108,18 +100,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fmadd_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f64 = self.get_fp_reg_64(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmadd_d(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
+        let rs3:f64 = self.get_fp_reg_64(rs3).unwrap();
         let reg_val = match rm {
             0b111 => double_fma(rs1, rs2, rs3, get_bits(7, 5, self.fcsr) as u8),
             _ => double_fma(rs1, rs2, rs3, rm),
@@ -130,18 +114,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fmsub_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f64 = self.get_fp_reg_64(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmsub_d(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
+        let rs3:f64 = self.get_fp_reg_64(rs3).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f64) | (rs2.is_infinite() && rs1 == 0.0f64) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -152,18 +128,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fnmsub_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f64 = self.get_fp_reg_64(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fnmsub_d(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
+        let rs3:f64 = self.get_fp_reg_64(rs3).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f64) | (rs2.is_infinite() && rs1 == 0.0f64) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -174,18 +142,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fnmadd_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f64 = self.get_fp_reg_64(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fnmadd_d(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
+        let rs3:f64 = self.get_fp_reg_64(rs3).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f64) | (rs2.is_infinite() && rs1 == 0.0f64) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -196,15 +156,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fadd_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fadd_d(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = match rm {
             0b111 => double_add(rs1, rs2, get_bits(7, 5, self.fcsr) as u8),
             _ => double_add(rs1, rs2, rm),
@@ -213,15 +167,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsub_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsub_d(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = match rm {
             0b111 => double_sub(rs1, rs2, get_bits(7, 5, self.fcsr) as u8),
             _ => double_sub(rs1, rs2, rm),
@@ -230,15 +178,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fmul_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmul_d(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f64) | (rs2.is_infinite() && rs1 == 0.0f64) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -249,15 +191,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fdiv_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fdiv_d(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = match rm {
             0b111 => double_div(rs1, rs2, get_bits(7, 5, self.fcsr) as u8),
             _ => double_div(rs1, rs2, rm),
@@ -266,12 +202,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fsqrt_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsqrt_d(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
         let reg_val = match rm {
             0b111 => double_sqrt(rs1, get_bits(7, 5, self.fcsr) as u8),
             _ => double_sqrt(rs1, rm),
@@ -280,14 +212,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsgnj_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsgnj_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = if rs2.is_sign_negative() {
             f64::from_bits( rs1.to_bits() | 0x8000_0000_0000_0000 )
         } else {
@@ -297,14 +224,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsgnjn_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsgnjn_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = if rs2.is_sign_positive() {
             f64::from_bits( rs1.to_bits() | 0x8000_0000_0000_0000 )
         } else {
@@ -314,14 +236,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsgnjx_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsgnjx_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = if rs1.is_sign_negative() ^ rs2.is_sign_negative() {
             f64::from_bits( rs1.to_bits() | 0x8000_0000_0000_0000 )
         } else {
@@ -331,38 +248,24 @@ impl Hart {
         Ok(())
     }
 
-    fn fmin_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmin_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         if rs1.is_nan() | rs2.is_nan() { self.fcsr |= 0x0000_0010; }
         self.set_fp_reg_64(rd, f64::min(rs1, rs2))?;
         Ok(())
     }
 
-    fn fmax_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmax_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         if rs1.is_nan() | rs2.is_nan() { self.fcsr |= 0x0000_0010; }
         self.set_fp_reg_64(rd, f64::max(rs1, rs2))?;
         Ok(())
     }
 
-    fn fcvt_s_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
-        let rm = get_bits(14, 12, inst) as u8;
+    fn fcvt_s_d(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f64 = self.get_fp_reg_64(rs1).unwrap();
         let reg_val = match rm {
             0b111 => double_to_float (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                             // get RM from FCSR
@@ -372,25 +275,17 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_d_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_d_s(&mut self, RmType { rd, rs1, .. }: RmType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
         // Casting from an f32 to an f64 is perfect and lossless
         // https://doc.rust-lang.org/reference/expressions/operator-expr.html#numeric-cast
         self.set_fp_reg_64(rd, rs1 as f64)?;
         Ok(())
     }
 
-    fn feq_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn feq_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = 
             if rs1.is_nan() || rs2.is_nan() { 0 }
             else { (rs1 == rs2) as u64 };
@@ -400,14 +295,9 @@ impl Hart {
         Ok(())
     }
 
-    fn flt_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn flt_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = 
             if rs1.is_nan() || rs2.is_nan() { 0 }
             else { (rs1 < rs2) as u64 };
@@ -416,14 +306,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fle_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f64 = self.get_fp_reg_64(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fle_d(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f64 = self.get_fp_reg_64(rs1).unwrap();
+        let rs2:f64 = self.get_fp_reg_64(rs2).unwrap();
         let reg_val = 
             if rs1.is_nan() || rs2.is_nan() { 0 }
             else { (rs1 <= rs2) as u64 };
@@ -432,9 +317,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fclass_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1: f64 = self.get_fp_reg_64(get_bits(19, 15, inst) as u8).expect("should be unreachable, rs1 field is only 4 bits.");
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fclass_d(&mut self, RType { rd, rs1, .. }: RType) -> Result<(), HartError> {
+        let rs1: f64 = self.get_fp_reg_64(rs1).expect("should be unreachable, rs1 field is only 4 bits.");
         let mut reg_val: u64 = 0;
         if rs1.is_sign_positive() {
             if rs1.is_infinite() { reg_val |= 0b00_1000_0000; }
@@ -452,12 +336,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_w_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_w_d(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f64 = self.get_fp_reg_64(rs1).unwrap();
         let reg_val = match rm {
             0b111 => double_to_i32 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -467,12 +347,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_wu_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_wu_d(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f64 = self.get_fp_reg_64(rs1).unwrap();
         let reg_val = match rm {
             0b111 => double_to_u32 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -482,13 +358,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_d_w(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap() as i32;
+    fn fcvt_d_w(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap() as i32;
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => i32_to_double (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -498,13 +370,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_d_wu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap() as u32;
+    fn fcvt_d_wu(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap() as u32;
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => u32_to_double (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -514,12 +382,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_l_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_l_d(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f64 = self.get_fp_reg_64(rs1).unwrap();
         let reg_val = match rm {
             0b111 => double_to_i64 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -529,12 +393,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_lu_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f64 = self.get_fp_reg_64(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_lu_d(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f64 = self.get_fp_reg_64(rs1).unwrap();
         let reg_val = match rm {
             0b111 => double_to_u64 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -544,20 +404,15 @@ impl Hart {
         Ok(())
     }
 
-    fn fmv_x_d(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_fp_reg_64(get_bits(19, 15, inst) as u8).expect("should be unreachable, rs1 field is only 4 bits.").to_bits();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmv_x_d(&mut self, RType { rd, rs1, .. }: RType) -> Result<(), HartError> {
+        let rs1 = self.get_fp_reg_64(rs1).expect("should be unreachable, rs1 field is only 4 bits.").to_bits();
         self.set_reg(rd, rs1)?;
         Ok(())
     }
 
-    fn fcvt_d_l(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap() as i64;
+    fn fcvt_d_l(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap() as i64;
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => i64_to_double (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -567,13 +422,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_d_lu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
+    fn fcvt_d_lu(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap();
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => u64_to_double (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -583,9 +434,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fmv_d_x(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(get_bits(19, 15, inst) as u8).expect("should be unreachable, rs1 field is only 4 bits.");
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmv_d_x(&mut self, RType { rd, rs1, .. }: RType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).expect("should be unreachable, rs1 field is only 4 bits.");
         self.set_fp_reg_64(rd, f64::from_bits(rs1))?;
         Ok(())
     }
@@ -7,6 +7,7 @@ use narvi_core::{
     }
 };
 
+use crate::decode::{IType, Instruction, R4Type, RType, RmType, SType};
 use crate::hart::{Hart, HartError, MemoryWaitState};
 use crate::util::{
     get_bits,
@@ -16,60 +17,55 @@ use crate::util::{
 
 #[allow(dead_code, unused_variables)]
 impl Hart {
-    pub fn execute_f(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let opcode = get_bits(6, 0, inst);
-        let funct3 = get_bits(14, 12, inst);
-        let funct5 = get_bits(24, 20, inst);
-        let funct2 = get_bits(26, 25, inst);
-        let funct7 = get_bits(31, 25, inst);
-        match (funct7, funct2, funct5, funct3, opcode) {
-            (        _,    _,       _, 0b010, 0b0000111) => self.flw(inst, engine_context),
-            (        _,    _,       _, 0b010, 0b0100111) => self.fsw(inst, engine_context),
-            (        _, 0b00,       _,     _, 0b1000011) => self.fmadd_s(inst),
-            (        _, 0b00,       _,     _, 0b1000111) => self.fmsub_s(inst),
-            (        _, 0b00,       _,     _, 0b1001011) => self.fnmsub_s(inst),
-            (        _, 0b00,       _,     _, 0b1001111) => self.fnmadd_s(inst),
-            (0b0000000,    _,       _,     _, 0b1010011) => self.fadd_s(inst),
-            (0b0000100,    _,       _,     _, 0b1010011) => self.fsub_s(inst),
-            (0b0001000,    _,       _,     _, 0b1010011) => self.fmul_s(inst),
-            (0b0001100,    _,       _,     _, 0b1010011) => self.fdiv_s(inst),
-            (0b0101100,    _, 0b00000,     _, 0b1010011) => self.fsqrt_s(inst),
-            (0b0010000,    _,       _, 0b000, 0b1010011) => self.fsgnj_s(inst),
-            (0b0010000,    _,       _, 0b001, 0b1010011) => self.fsgnjn_s(inst),
-            (0b0010000,    _,       _, 0b010, 0b1010011) => self.fsgnjx_s(inst),
-            (0b0010100,    _,       _, 0b000, 0b1010011) => self.fmin_s(inst),
-            (0b0010100,    _,       _, 0b001, 0b1010011) => self.fmax_s(inst),
-            (0b1100000,    _, 0b00000,     _, 0b1010011) => self.fcvt_w_s(inst),
-            (0b1100000,    _, 0b00001,     _, 0b1010011) => self.fcvt_wu_s(inst),
-            (0b1110000,    _, 0b00000, 0b000, 0b1010011) => self.fmv_x_w(inst),
-            (0b1010000,    _,       _, 0b010, 0b1010011) => self.feq_s(inst),
-            (0b1010000,    _,       _, 0b001, 0b1010011) => self.flt_s(inst),
-            (0b1010000,    _,       _, 0b000, 0b1010011) => self.fle_s(inst),
-            (0b1110000,    _, 0b00000, 0b001, 0b1010011) => self.fclass_s(inst),
-            (0b1101000,    _, 0b00000,     _, 0b1010011) => self.fcvt_s_w(inst),
-            (0b1101000,    _, 0b00001,     _, 0b1010011) => self.fcvt_s_wu(inst),
-            (0b1111000,    _, 0b00000, 0b000, 0b1010011) => self.fmv_w_x(inst),
-            (0b1100000,    _, 0b00010,     _, 0b1010011) => self.fcvt_l_s(inst),
-            (0b1100000,    _, 0b00011,     _, 0b1010011) => self.fcvt_lu_s(inst),
-            (0b1101000,    _, 0b00010,     _, 0b1010011) => self.fcvt_s_l(inst),
-            (0b1101000,    _, 0b00011,     _, 0b1010011) => self.fcvt_s_lu(inst),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
+    pub fn execute_f(&mut self, instruction: Instruction, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        use Instruction::*;
+
+        match instruction {
+            Flw(i) => self.flw(i, engine_context),
+            Fsw(s) => self.fsw(s, engine_context),
+            FmaddS(r) => self.fmadd_s(r),
+            FmsubS(r) => self.fmsub_s(r),
+            FnmsubS(r) => self.fnmsub_s(r),
+            FnmaddS(r) => self.fnmadd_s(r),
+            FaddS(r) => self.fadd_s(r),
+            FsubS(r) => self.fsub_s(r),
+            FmulS(r) => self.fmul_s(r),
+            FdivS(r) => self.fdiv_s(r),
+            FsqrtS(r) => self.fsqrt_s(r),
+            FsgnjS(r) => self.fsgnj_s(r),
+            FsgnjnS(r) => self.fsgnjn_s(r),
+            FsgnjxS(r) => self.fsgnjx_s(r),
+            FminS(r) => self.fmin_s(r),
+            FmaxS(r) => self.fmax_s(r),
+            FcvtWS(r) => self.fcvt_w_s(r),
+            FcvtWuS(r) => self.fcvt_wu_s(r),
+            FmvXW(r) => self.fmv_x_w(r),
+            FeqS(r) => self.feq_s(r),
+            FltS(r) => self.flt_s(r),
+            FleS(r) => self.fle_s(r),
+            FclassS(r) => self.fclass_s(r),
+            FcvtSW(r) => self.fcvt_s_w(r),
+            FcvtSWu(r) => self.fcvt_s_wu(r),
+            FmvWX(r) => self.fmv_w_x(r),
+            FcvtLS(r) => self.fcvt_l_s(r),
+            FcvtLuS(r) => self.fcvt_lu_s(r),
+            FcvtSL(r) => self.fcvt_s_l(r),
+            FcvtSLu(r) => self.fcvt_s_lu(r),
+            _ => Err(HartError::ExecutionError),
         }
     }
 
-    fn flw(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = self.get_reg(get_bits(19, 15, inst) as u8)?;
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_64(get_bits(31, 20, inst) as u64, 12);
-        let addr = rs1.wrapping_add(imm);
+    fn flw(&mut self, IType { rd, rs1, imm }: IType, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        let addr = self.get_reg(rs1)?.wrapping_add(imm as u64);
 
         engine_context.schedule(
             1, 
-            Target::Module(self.memory_bus_target),
+            Target::Module(self.data_port),
             EventPayload::MemoryLoadReq { 
                 address: addr as usize, 
                 size_in_bytes: 4,
-                requester: Target::Myself
+                requester: Target::Myself,
+                pc: Some(self.pc)
             }
         );
 
@@ -78,20 +74,17 @@ impl Hart {
         Ok(())
     }
 
-    fn fsw(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = self.get_reg(get_bits(19, 15, inst) as u8)?;
-        let imm_bits = get_bits(11, 7, inst) | ( get_bits(31, 25, inst) << 5 );
-        let imm = sign_extend_64(imm_bits as u64, 12);
-        let addr = rs1.wrapping_add(imm);
-        let rs2 = get_bits(19, 15, inst) as u8;
+    fn fsw(&mut self, SType { rs1, rs2, imm }: SType, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        let addr = self.get_reg(rs1)?.wrapping_add(imm as u64);
         let reg_val = self.get_fp_reg_32_bits(rs2)?;
 
         engine_context.schedule(
             1,
-            Target::Module(self.memory_bus_target),
+            Target::Module(self.data_port),
             EventPayload::MemoryStoreReq { 
                 address: addr as usize, 
                 data: reg_val.to_le_bytes().to_vec(),
+                pc: Some(self.pc)
             }
         );
 
@@ -100,18 +93,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fmadd_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f32 = self.get_fp_reg_32(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmadd_s(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
+        let rs3:f32 = self.get_fp_reg_32(rs3).unwrap();
         let reg_val = match rm {
             0b111 => float_fma(rs1, rs2, rs3, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                             // get RM from FCSR
@@ -123,18 +108,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fmsub_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f32 = self.get_fp_reg_32(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmsub_s(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
+        let rs3:f32 = self.get_fp_reg_32(rs3).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f32) | (rs2.is_infinite() && rs1 == 0.0f32) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -146,18 +123,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fnmsub_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f32 = self.get_fp_reg_32(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fnmsub_s(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
+        let rs3:f32 = self.get_fp_reg_32(rs3).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f32) | (rs2.is_infinite() && rs1 == 0.0f32) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -169,18 +138,10 @@ impl Hart {
         Ok(())
     }
 
-    fn fnmadd_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rs3:f32 = self.get_fp_reg_32(
-            get_bits(31, 27, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fnmadd_s(&mut self, R4Type { rd, rs1, rs2, rs3, rm }: R4Type) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
+        let rs3:f32 = self.get_fp_reg_32(rs3).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f32) | (rs2.is_infinite() && rs1 == 0.0f32) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -192,15 +153,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fadd_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fadd_s(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = match rm {
             0b111 => float_add(rs1, rs2, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -210,15 +165,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsub_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsub_s(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = match rm {
             0b111 => float_sub(rs1, rs2, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -228,15 +177,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fmul_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmul_s(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         if (rs1.is_infinite() && rs2 == 0.0f32) | (rs2.is_infinite() && rs1 == 0.0f32) { self.fcsr |= 0x0000_0010; }
         // Set Invalid Operation flag if multiplying 0 by infinity
         let reg_val = match rm {
@@ -248,15 +191,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fdiv_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fdiv_s(&mut self, RmType { rd, rs1, rs2, rm }: RmType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = match rm {
             0b111 => float_div(rs1, rs2, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -266,12 +203,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fsqrt_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsqrt_s(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
         let reg_val = match rm {
             0b111 => float_sqrt(rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -281,14 +214,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsgnj_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsgnj_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = if rs2.is_sign_negative() {
             f32::from_bits( rs1.to_bits() | 0x8000_0000 )
         } else {
@@ -298,14 +226,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsgnjn_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsgnjn_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = if rs2.is_sign_positive() {
             f32::from_bits( rs1.to_bits() | 0x8000_0000 )
         } else {
@@ -315,14 +238,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fsgnjx_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fsgnjx_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = if rs1.is_sign_negative() ^ rs2.is_sign_negative() {
             f32::from_bits( rs1.to_bits() | 0x8000_0000 )
         } else {
@@ -332,40 +250,26 @@ impl Hart {
         Ok(())
     }
 
-    fn fmin_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmin_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         if rs1.is_nan() | rs2.is_nan() { self.fcsr |= 0x0000_0010; } // If any operand is NaN, set
                                                                         // Invalid Operation flag
         self.set_fp_reg_32(rd, f32::min(rs1, rs2))?;
         Ok(())
     }
 
-    fn fmax_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmax_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         if rs1.is_nan() | rs2.is_nan() { self.fcsr |= 0x0000_0010; } // If any operand is NaN, set
                                                                         // Invalid Operation flag
         self.set_fp_reg_32(rd, f32::max(rs1, rs2))?;
         Ok(())
     }
 
-    fn fcvt_w_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_w_s(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f32 = self.get_fp_reg_32(rs1).unwrap();
         let reg_val = match rm {
             0b111 => float_to_i32 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -375,12 +279,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_wu_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_wu_s(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f32 = self.get_fp_reg_32(rs1).unwrap();
         let reg_val = match rm {
             0b111 => float_to_u32 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -390,22 +290,16 @@ impl Hart {
         Ok(())
     }
 
-    fn fmv_x_w(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1: u32 = self.get_fp_reg_32_bits(get_bits(19, 15, inst) as u8).expect("should be unreachable, rs1 field is only 4 bits.");
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmv_x_w(&mut self, RType { rd, rs1, .. }: RType) -> Result<(), HartError> {
+        let rs1: u32 = self.get_fp_reg_32_bits(rs1).expect("should be unreachable, rs1 field is only 4 bits.");
         let reg_val = sign_extend_64(rs1 as u64, 32);
         self.set_reg(rd, reg_val)?;
         Ok(())
     }
 
-    fn feq_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn feq_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = 
             if rs1.is_nan() || rs2.is_nan() { 0 }
             else { (rs1 == rs2) as u64 };
@@ -416,14 +310,9 @@ impl Hart {
         Ok(())
     }
 
-    fn flt_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn flt_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = 
             if rs1.is_nan() || rs2.is_nan() { 0 }
             else { (rs1 < rs2) as u64 };
@@ -433,14 +322,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fle_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1:f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8
-            ).unwrap();
-        let rs2:f32 = self.get_fp_reg_32(
-            get_bits(24, 20, inst) as u8
-            ).unwrap();
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fle_s(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let rs1:f32 = self.get_fp_reg_32(rs1).unwrap();
+        let rs2:f32 = self.get_fp_reg_32(rs2).unwrap();
         let reg_val = 
             if rs1.is_nan() || rs2.is_nan() { 0 }
             else { (rs1 <= rs2) as u64 };
@@ -450,9 +334,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fclass_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1: f32 = self.get_fp_reg_32(get_bits(19, 15, inst) as u8).expect("should be unreachable, rs1 field is only 4 bits.");
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fclass_s(&mut self, RType { rd, rs1, .. }: RType) -> Result<(), HartError> {
+        let rs1: f32 = self.get_fp_reg_32(rs1).expect("should be unreachable, rs1 field is only 4 bits.");
         let mut reg_val: u64 = 0;
         if rs1.is_sign_positive() {
             if rs1.is_infinite() { reg_val |= 0b00_1000_0000; }
@@ -471,13 +354,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_s_w(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap() as i32;
+    fn fcvt_s_w(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap() as i32;
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => i32_to_float (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -487,13 +366,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_s_wu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap() as u32;
+    fn fcvt_s_wu(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap() as u32;
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => u32_to_float (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -503,19 +378,14 @@ impl Hart {
         Ok(())
     }
 
-    fn fmv_w_x(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1: u32 = self.get_reg(get_bits(19, 15, inst) as u8).expect("should be unreachable, rs1 field is only 4 bits.") as u32;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fmv_w_x(&mut self, RType { rd, rs1, .. }: RType) -> Result<(), HartError> {
+        let rs1: u32 = self.get_reg(rs1).expect("should be unreachable, rs1 field is only 4 bits.") as u32;
         self.set_fp_reg_32_bits(rd, rs1)?;
         Ok(())
     }
 
-    fn fcvt_l_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_l_s(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f32 = self.get_fp_reg_32(rs1).unwrap();
         let reg_val = match rm {
             0b111 => float_to_i64 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -525,12 +395,8 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_lu_s(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 : f32 = self.get_fp_reg_32(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
+    fn fcvt_lu_s(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 : f32 = self.get_fp_reg_32(rs1).unwrap();
         let reg_val = match rm {
             0b111 => float_to_u64 (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -540,13 +406,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_s_l(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap() as i64;
+    fn fcvt_s_l(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap() as i64;
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => i64_to_float (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -556,13 +418,9 @@ impl Hart {
         Ok(())
     }
 
-    fn fcvt_s_lu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = self.get_reg(
-            get_bits(19, 15, inst) as u8   
-        ).unwrap();
+    fn fcvt_s_lu(&mut self, RmType { rd, rs1, rm, .. }: RmType) -> Result<(), HartError> {
+        let rs1 = self.get_reg(rs1).unwrap();
 
-        let rm = get_bits(14, 12, inst) as u8;
-        let rd = get_bits(11, 7, inst) as u8;
         let reg_val = match rm {
             0b111 => u64_to_float (rs1, get_bits(7, 5, self.fcsr) as u8), // if RM == DYN,
                                                                         // get RM from FCSR
@@ -1,43 +1,38 @@
+use crate::decode::{Instruction, RType};
 use crate::hart::{Hart, HartError};
-use crate::util::{get_bits, sign_extend_64, sign_extend_128};
+use crate::util::{sign_extend_64, sign_extend_128};
 
 #[allow(dead_code)]
 impl Hart {
-    pub fn execute_m(&mut self, inst: u32) -> Result<(), HartError> {
-        let opcode = get_bits(6, 0, inst);
-        let funct3 = get_bits(14, 12, inst);
-        match (opcode, funct3) {
-            (0b0110011, 0b000) => self.mul(inst),
-            (0b0110011, 0b001) => self.mulh(inst),
-            (0b0110011, 0b010) => self.mulhsu(inst),
-            (0b0110011, 0b011) => self.mulhu(inst),
-            (0b0110011, 0b100) => self.div(inst),
-            (0b0110011, 0b101) => self.divu(inst),
-            (0b0110011, 0b110) => self.rem(inst),
-            (0b0110011, 0b111) => self.remu(inst),
-            (0b0111011, 0b000) => self.mulw(inst),
-            (0b0111011, 0b100) => self.divw(inst),
-            (0b0111011, 0b101) => self.divuw(inst),
-            (0b0111011, 0b110) => self.remw(inst),
-            (0b0111011, 0b111) => self.remuw(inst),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
+    pub fn execute_m(&mut self, instruction: Instruction) -> Result<(), HartError> {
+        use Instruction::*;
+
+        match instruction {
+            Mul(r) => self.mul(r),
+            Mulh(r) => self.mulh(r),
+            Mulhsu(r) => self.mulhsu(r),
+            Mulhu(r) => self.mulhu(r),
+            Div(r) => self.div(r),
+            Divu(r) => self.divu(r),
+            Rem(r) => self.rem(r),
+            Remu(r) => self.remu(r),
+            Mulw(r) => self.mulw(r),
+            Divw(r) => self.divw(r),
+            Divuw(r) => self.divuw(r),
+            Remw(r) => self.remw(r),
+            Remuw(r) => self.remuw(r),
+            _ => Err(HartError::ExecutionError),
         }
     }
 
-    fn mul(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn mul(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)?;
         let rhs = self.get_reg(rs2)?;
         self.set_reg(rd, lhs.wrapping_mul(rhs))?;
         Ok(())
     }
 
-    fn mulh(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn mulh(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = sign_extend_128(self.get_reg(rs1)? as u128, 64);
         let rhs = sign_extend_128(self.get_reg(rs2)? as u128, 64);
         let result = lhs.wrapping_mul(rhs);
@@ -45,10 +40,7 @@ impl Hart {
         Ok(())
     }
 
-    fn mulhsu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn mulhsu(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = sign_extend_128(self.get_reg(rs1)? as u128, 64) as i128;
         let rhs = self.get_reg(rs2)? as i128;
         let result = lhs.wrapping_mul(rhs);
@@ -56,10 +48,7 @@ impl Hart {
         Ok(())
     }
 
-    fn mulhu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn mulhu(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as u128;
         let rhs = self.get_reg(rs2)? as u128;
         let result = lhs * rhs;
@@ -67,10 +56,7 @@ impl Hart {
         Ok(())
     }
 
-    fn div(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn div(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as i64;
         let rhs = self.get_reg(rs2)? as i64;
         if rhs == 0 {
@@ -83,10 +69,7 @@ impl Hart {
         Ok(())
     }
 
-    fn divu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn divu(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)?;
         let rhs = self.get_reg(rs2)?;
         if rhs == 0 {
@@ -97,10 +80,7 @@ impl Hart {
         Ok(())
     }
 
-    fn rem(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn rem(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as i64;
         let rhs = self.get_reg(rs2)? as i64;
         if rhs == 0 {
@@ -113,10 +93,7 @@ impl Hart {
         Ok(())
     }
 
-    fn remu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn remu(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as u64;
         let rhs = self.get_reg(rs2)? as u64;
         if rhs == 0 {
@@ -127,10 +104,7 @@ impl Hart {
         Ok(())
     }
 
-    fn mulw(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn mulw(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as i32;
         let rhs = self.get_reg(rs2)? as i32;
         let result = lhs.wrapping_mul(rhs) as u64;
@@ -138,10 +112,7 @@ impl Hart {
         Ok(())
     }
 
-    fn divw(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn divw(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as i32;
         let rhs = self.get_reg(rs2)? as i32;
         if rhs == 0 {
@@ -155,10 +126,7 @@ impl Hart {
         Ok(())
     }
 
-    fn divuw(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn divuw(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as u32;
         let rhs = self.get_reg(rs2)? as u32;
         if rhs == 0 {
@@ -169,10 +137,7 @@ impl Hart {
         Ok(())
     }
 
-    fn remw(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn remw(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as i32;
         let rhs = self.get_reg(rs2)? as i32;
         if rhs == 0 {
@@ -186,10 +151,7 @@ impl Hart {
         Ok(())
     }
 
-    fn remuw(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn remuw(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let lhs = self.get_reg(rs1)? as u32;
         let rhs = self.get_reg(rs2)? as u32;
         if rhs == 0 {
@@ -206,8 +168,9 @@ impl Hart {
 mod m_tests {
     use std::u64;
 
-    use crate::hart::extensions::Extensions;
+    use narvi_core::{Extensions, event::InstructionClass};
 
+    use crate::decode::decode;
     use crate::hart::{Hart, HartError, Reg};
 
     static EXTENSIONS: Extensions = Extensions {
@@ -220,14 +183,14 @@ mod m_tests {
 
     #[test]
     fn mul1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, u64::MAX)?;
         hart.set_reg(Reg::s1 as u8, u64::MAX)?;
 
         let inst = 0x02848933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 1);
 
         Ok(())
@@ -235,14 +198,14 @@ mod m_tests {
 
     #[test]
     fn mul2() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, -1i64 as u64)?;
         hart.set_reg(Reg::s1 as u8, -1i64 as u64)?;
 
         let inst = 0x02848933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 1);
 
         Ok(())
@@ -250,13 +213,13 @@ mod m_tests {
 
     #[test]
     fn mulh1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x80000000000000)?;
         hart.set_reg(Reg::s1 as u8, (-0x80000000000000i64) as u64)?;
 
         let inst = 0x02941933;
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFC00000000000);
 
         Ok(())
@@ -264,14 +227,14 @@ mod m_tests {
 
     #[test]
     fn mulhsu1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x80000000000000)?;
         hart.set_reg(Reg::s1 as u8, (-0x80000000000000i64) as u64)?;
 
         let inst = 0x02942933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x7FC00000000000);
 
         Ok(())
@@ -279,14 +242,14 @@ mod m_tests {
 
     #[test]
     fn mulhsu2() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, (-0x80000000000000i64) as u64)?;
         hart.set_reg(Reg::s1 as u8, 0x80000000000000)?;
 
         let inst = 0x02942933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFC00000000000);
 
         Ok(())
@@ -294,14 +257,14 @@ mod m_tests {
 
     #[test]
     fn mulhu1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x80000000000000)?;
         hart.set_reg(Reg::s1 as u8, (-0x80000000000000i64) as u64)?;
 
         let inst = 0x02943933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x7FC00000000000);
 
         Ok(())
@@ -309,14 +272,14 @@ mod m_tests {
 
     #[test]
     fn div1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x80000000000000)?;
         hart.set_reg(Reg::s1 as u8, (-0x80000000000000i64) as u64)?;
 
         let inst = 0x2944933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFFFFFFFFFF);
 
         Ok(())
@@ -324,14 +287,14 @@ mod m_tests {
 
     #[test]
     fn divu1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, (-0x80000000000000i64) as u64)?;
         hart.set_reg(Reg::s1 as u8, 0xF)?;
 
         let inst = 0x2945933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x1108888888888888);
 
         Ok(())
@@ -339,14 +302,14 @@ mod m_tests {
 
     #[test]
     fn rem1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x5)?;
         hart.set_reg(Reg::s1 as u8, 0x4)?;
 
         let inst = 0x2946933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x1);
 
         Ok(())
@@ -354,14 +317,14 @@ mod m_tests {
 
     #[test]
     fn rem2() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, (-0x5i64) as u64)?;
         hart.set_reg(Reg::s1 as u8, 0x4)?;
 
         let inst = 0x2946933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFFFFFFFFFF);
 
         Ok(())
@@ -369,14 +332,14 @@ mod m_tests {
 
     #[test]
     fn rem3() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x5)?;
         hart.set_reg(Reg::s1 as u8, (-0x4i64) as u64)?;
 
         let inst = 0x2946933;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x1);
 
         Ok(())
@@ -384,14 +347,14 @@ mod m_tests {
 
     #[test]
     fn mulw1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x10000)?;
         hart.set_reg(Reg::s1 as u8, 0x10000)?;
 
         let inst = 0x294093B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x0);
 
         Ok(())
@@ -399,14 +362,14 @@ mod m_tests {
 
     #[test]
     fn mulw2() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x1000)?;
         hart.set_reg(Reg::s1 as u8, (-0x1000i64) as u64)?;
 
         let inst = 0x294093B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFFFF000000);
 
         Ok(())
@@ -414,14 +377,14 @@ mod m_tests {
 
     #[test]
     fn divw1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0x1)?;
         hart.set_reg(Reg::s1 as u8, (-0x1i64) as u64)?;
 
         let inst = 0x294493B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFFFFFFFFFF);
 
         Ok(())
@@ -429,14 +392,14 @@ mod m_tests {
 
     #[test]
     fn divw2() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0xFFFFFFFF00000004)?;
         hart.set_reg(Reg::s1 as u8, 0xFFFFFFFF00000002)?;
 
         let inst = 0x294493B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x2);
 
         Ok(())
@@ -444,14 +407,14 @@ mod m_tests {
 
     #[test]
     fn divuw1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, (-0x1i64) as u64)?;
         hart.set_reg(Reg::s1 as u8, 0x1)?;
 
         let inst = 0x294593B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFF);
 
         Ok(())
@@ -459,14 +422,14 @@ mod m_tests {
 
     #[test]
     fn divuw2() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0xFFFFFFFFFFFFFFFF)?;
         hart.set_reg(Reg::s1 as u8, 0xFFFFFFFF00000001)?;
 
         let inst = 0x294593B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFF);
 
         Ok(())
@@ -474,14 +437,14 @@ mod m_tests {
 
     #[test]
     fn remw1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, (-0x5i64) as u64)?;
         hart.set_reg(Reg::s1 as u8, 0x4)?;
 
         let inst = 0x294693B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFFFFFFFFFF);
 
         Ok(())
@@ -489,14 +452,14 @@ mod m_tests {
 
     #[test]
     fn remw2() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, (-0x5i64) as u64)?;
         hart.set_reg(Reg::s1 as u8, 0xFFFFFFFF00000004)?;
 
         let inst = 0x294693B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0xFFFFFFFFFFFFFFFF);
 
         Ok(())
@@ -504,16 +467,31 @@ mod m_tests {
 
     #[test]
     fn remuw1() -> Result<(), HartError> {
-        let mut hart = Hart::from_extensions(&EXTENSIONS);
+        let mut hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
 
         hart.set_reg(Reg::s0 as u8, 0xFFFFFFFF00000005)?;
         hart.set_reg(Reg::s1 as u8, 0xFFFFFFFF00000004)?;
 
         let inst = 0x294793B;
 
-        assert!(hart.execute_m(inst).is_ok());
+        assert!(hart.execute_m(decode(inst).unwrap()).is_ok());
         assert_eq!(hart.regs[Reg::s2 as usize], 0x1);
 
         Ok(())
     }
+
+    #[test]
+    fn classify_tells_m_from_the_base_isa() {
+        let hart = Hart::from_extensions(&EXTENSIONS, 0, 0);
+
+        // mul s2, s1, s0 / add s2, s1, s0 / beq zero, zero, 8
+        let mul = decode(0x02848933).unwrap();
+        let add = decode(0x00848933).unwrap();
+        let beq = decode(0x00000463).unwrap();
+
+        assert_eq!((hart.classify(&mul, 0), mul.mnemonic()), (InstructionClass::M, "mul"));
+        assert_eq!((hart.classify(&add, 0), add.mnemonic()), (InstructionClass::Alu, "add"));
+        assert_eq!((hart.classify(&beq, 0), beq.mnemonic()), (InstructionClass::BranchNotTaken, "beq"));
+        assert_eq!((hart.classify(&beq, 0x100), beq.mnemonic()), (InstructionClass::BranchTaken, "beq"));
+    }
 }
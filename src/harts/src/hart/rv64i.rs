@@ -1,590 +1,173 @@
 use narvi_core::{
-    EngineContext, 
-    ModuleId,
+    EngineContext,
     event::{
-        Event, 
         EventPayload,
         Target,
     }
 };
 
 use super::{
-    Hart, 
-    HartError, 
+    Hart,
+    HartError,
     IMode,
     MemoryWaitState
 };
 
 use crate::{
-    util::{
-        get_bits, 
-        sign_extend_32, 
-        sign_extend_64
-    }
+    decode::{
+        BType,
+        IType,
+        Instruction,
+        JType,
+        RType,
+        SType,
+        UType,
+    },
+    util::sign_extend_64
 };
 
 #[allow(dead_code, unused_variables)]
 impl Hart {
-    pub(super) fn execute_rv64i(&mut self, inst: u32, engin_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let opcode = get_bits(6, 0, inst);
-        match opcode {
-            0x37 => self.lui(inst),
-            0x17 => self.auipc(inst),
-            0x6F => self.jal(inst),
-            0x67 => self.jalr(inst),
-            0x63 => self.branch(inst),
-            0x03 => self.load(inst, engin_context),
-            0x23 => self.store(inst, engin_context),
-            0x13 => self.al_imm(inst),
-            0x33 => self.al(inst),
-            0x0F => self.fence(inst),
-            0x73 => self.environment(inst),
-            0x1B => self.al_imm_w(inst),
-            0x3B => self.al_w(inst),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
-        }
-    }
-
-    fn branch(&mut self, inst: u32) -> Result<(), HartError> {
-        let funct3 = get_bits(14, 12, inst);
-        match funct3 {
-            0 => self.beq(inst),
-            1 => self.bne(inst),
-            4 => self.blt(inst),
-            5 => self.bge(inst),
-            6 => self.bltu(inst),
-            7 => self.bgeu(inst),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
-        }
-    }
-
-    fn load(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let funct3 = get_bits(14, 12, inst);
-        match funct3 {
-            0 => self.lb(inst, engine_context),
-            1 => self.lh(inst, engine_context),
-            2 => self.lw(inst, engine_context),
-            4 => self.lbu(inst, engine_context),
-            5 => self.lhu(inst, engine_context),
-            6 => self.lwu(inst, engine_context),
-            3 => self.ld(inst, engine_context),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
-        }
-    }
-
-    fn store(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let funct3 = get_bits(14, 12, inst);
-        match funct3 {
-            0 => self.sb(inst, engine_context),
-            1 => self.sh(inst, engine_context),
-            2 => self.sw(inst, engine_context),
-            3 => self.sd(inst, engine_context),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
-        }
-    }
-
-    fn al_imm(&mut self, inst: u32) -> Result<(), HartError> {
-        let funct7 = get_bits(31, 25, inst);
-        let funct3 = get_bits(14, 12, inst);
-        match funct3 {
-            0 => self.addi(inst),
-            2 => self.slti(inst),
-            3 => self.sltiu(inst),
-            4 => self.xori(inst),
-            6 => self.ori(inst),
-            7 => self.andi(inst),
-            1 => self.slli(inst),
-            5 => if funct7 == 0 {
-                self.srli(inst)
-            } else if funct7 == 0b100000 {
-                self.srai(inst)
-            } else {
-                Err(HartError::ExecutionError)
-            },
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
-        }
-    }
-
-    fn al(&mut self, inst: u32) -> Result<(), HartError> {
-        let funct7 = get_bits(31, 25, inst);
-        let funct3 = get_bits(14, 12, inst);
-        match funct3 {
-            0 => if funct7 == 0 {
-                self.add(inst)   
-            } else if funct7 == 0b100000 {
-                self.sub(inst)
-            } else {
-                Err(HartError::InstructionNotFound(inst as u64)) 
-            },
-            1 => self.sll(inst),
-            2 => self.slt(inst),
-            3 => self.sltu(inst),
-            4 => self.xor(inst),
-            5 => if funct7 == 0 {
-                self.srl(inst)
-            } else if funct7 == 0b100000 {
-                self.sra(inst)
-            } else {
-                Err(HartError::InstructionNotFound(inst as u64)) 
-            },
-            6 => self.or(inst),
-            7 => self.and(inst),
-            _ => Err(HartError::InstructionNotFound(inst as u64)),
-        }
-    }
-
-    fn environment(&mut self, inst: u32) -> Result<(), HartError> {
-        let func12 = get_bits(31, 20, inst);
-        if func12 == 0 {
-            self.ecall(inst)
-        } else if func12 == 1 {
-            self.ebreak(inst)
-        } else {
-            Err(HartError::ExecutionError)
-        }
-    }
-
-    fn al_imm_w(&mut self, inst: u32) -> Result<(), HartError> {
-        let funct7 = get_bits(31, 25, inst);
-        let funct3 = get_bits(14, 12, inst);
-        match funct3 {
-            0 => self.addiw(inst),
-            1 => self.slliw(inst),
-            5 => if funct7 == 0 {
-                self.srliw(inst)
-            } else if funct7 == 0b10000 {
-                self.sraiw(inst)
-            } else {
-                Err(HartError::ExecutionError)
-            },
-            _ => Err(HartError::ExecutionError),
-        }
-    }
-
-    fn al_w(&mut self, inst: u32) -> Result<(), HartError> {
-        let funct7 = get_bits(31, 25, inst);
-        let funct3 = get_bits(14, 12, inst);
-        match funct3 {
-            0 => if funct7 == 0 {
-                self.addw(inst)
-            } else if funct7 == 0b100000 {
-                self.subw(inst)
-            } else {
-                Err(HartError::ExecutionError)
-            },
-            1 => self.sllw(inst),
-            5 => if funct7 == 0 {
-                self.srlw(inst)
-            } else if funct7 == 0b100000 {
-                self.sraw(inst)
-            } else {
-                Err(HartError::ExecutionError)
-            },
+    pub(super) fn execute_rv64i(&mut self, instruction: Instruction, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        use Instruction::*;
+
+        match instruction {
+            Lui(u) => self.lui(u),
+            Auipc(u) => self.auipc(u),
+            Jal(j) => self.jal(j),
+            Jalr(i) => self.jalr(i),
+            Beq(b) => self.branch(b, |lhs, rhs| lhs == rhs),
+            Bne(b) => self.branch(b, |lhs, rhs| lhs != rhs),
+            Blt(b) => self.branch(b, |lhs, rhs| (lhs as i64) < (rhs as i64)),
+            Bge(b) => self.branch(b, |lhs, rhs| (lhs as i64) >= (rhs as i64)),
+            Bltu(b) => self.branch(b, |lhs, rhs| lhs < rhs),
+            Bgeu(b) => self.branch(b, |lhs, rhs| lhs >= rhs),
+            Lb(i) => self.load(i, 1, IMode::Signed, engine_context),
+            Lh(i) => self.load(i, 2, IMode::Signed, engine_context),
+            Lw(i) => self.load(i, 4, IMode::Signed, engine_context),
+            Ld(i) => self.load(i, 8, IMode::Unsigned, engine_context),
+            Lbu(i) => self.load(i, 1, IMode::Unsigned, engine_context),
+            Lhu(i) => self.load(i, 2, IMode::Unsigned, engine_context),
+            Lwu(i) => self.load(i, 4, IMode::Unsigned, engine_context),
+            Sb(s) => self.store(s, 1, engine_context),
+            Sh(s) => self.store(s, 2, engine_context),
+            Sw(s) => self.store(s, 4, engine_context),
+            Sd(s) => self.store(s, 8, engine_context),
+            Addi(i) => self.addi(i),
+            Slti(i) => self.slti(i),
+            Sltiu(i) => self.sltiu(i),
+            Xori(i) => self.xori(i),
+            Ori(i) => self.ori(i),
+            Andi(i) => self.andi(i),
+            Slli(i) => self.slli(i),
+            Srli(i) => self.srli(i),
+            Srai(i) => self.srai(i),
+            Add(r) => self.add(r),
+            Sub(r) => self.sub(r),
+            Sll(r) => self.sll(r),
+            Slt(r) => self.slt(r),
+            Sltu(r) => self.sltu(r),
+            Xor(r) => self.xor(r),
+            Srl(r) => self.srl(r),
+            Sra(r) => self.sra(r),
+            Or(r) => self.or(r),
+            And(r) => self.and(r),
+            Addiw(i) => self.addiw(i),
+            Slliw(i) => self.slliw(i),
+            Srliw(i) => self.srliw(i),
+            Sraiw(i) => self.sraiw(i),
+            Addw(r) => self.addw(r),
+            Subw(r) => self.subw(r),
+            Sllw(r) => self.sllw(r),
+            Srlw(r) => self.srlw(r),
+            Sraw(r) => self.sraw(r),
+            Fence { .. } => self.fence(),
+            FenceI => self.fence_i(),
+            Ecall => self.ecall(),
+            Ebreak => self.ebreak(),
             _ => Err(HartError::ExecutionError),
         }
     }
 
-    fn fence(&mut self, inst: u32) -> Result<(), HartError> {
+    fn fence(&mut self) -> Result<(), HartError> {
         todo!("fence")
     }
 
-    fn lui(&mut self, inst: u32) -> Result<(), HartError> {
-        // let imm = get_bits(31, 12, inst) << 12;
-        let imm = inst & 0xFFFFF000;
-        let rd = get_bits(11, 7, inst) as u8;
-        self.set_reg(rd, sign_extend_64(imm as u64, 32))?;
+    // Only drops the decoded instructions. The L1 instruction caches are not coherent with the
+    // data caches and are left alone, so self-modifying code is unsupported: it may run stale
+    // instructions still cached in L1I, or in a level below while the new ones sit dirty in L1D
+    fn fence_i(&mut self) -> Result<(), HartError> {
+        self.decode_cache.clear();
         Ok(())
     }
 
-    fn auipc(&mut self, inst: u32) -> Result<(), HartError> {
-        let imm = inst & 0xFFFFF000;
-        let rd = get_bits(11, 7, inst) as u8;
-        let val:u64 = imm as u64 + self.pc;
-        self.set_reg(rd, val)?;
+    fn lui(&mut self, UType { rd, imm }: UType) -> Result<(), HartError> {
+        self.set_reg(rd, imm as u64)?;
         Ok(())
     }
 
-    fn jal(&mut self, inst: u32) -> Result<(), HartError> {
-        let imm_19_12 = (get_bits(19, 12, inst) << 12) as u64;
-        let imm_11 = (get_bits(20, 20, inst) << 11) as u64;
-        let imm_10_1 = (get_bits(30, 21, inst) << 1) as u64;
-        let imm_20 = (get_bits(31, 31, inst) << 20) as u64;
-        let rd = get_bits(11, 7, inst) as u8;
-        let offset = sign_extend_64(imm_20 | imm_19_12 | imm_11 | imm_10_1, 21); 
-        self.set_reg(rd, self.pc + 4)?;
-        self.pc += offset - 4; // considering that PC will move +4 after instruction
+    fn auipc(&mut self, UType { rd, imm }: UType) -> Result<(), HartError> {
+        self.set_reg(rd, self.pc.wrapping_add(imm as u64))?;
         Ok(())
     }
 
-    fn jalr(&mut self, inst: u32) -> Result<(), HartError> {
-        let imm = sign_extend_64(get_bits(31, 20, inst) as u64, 12);
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let source_val = self.get_reg(rs1)?;
-        self.set_reg(rd, self.pc + 4)?;
-
-        // TODO: this addition sometimes leads to an overflow, but I'm not sure how it should be treated yet
-        self.pc = (source_val + imm) & !1u64;
+    fn jal(&mut self, JType { rd, imm }: JType) -> Result<(), HartError> {
+        self.set_reg(rd, self.pc.wrapping_add(4))?;
+        self.pc = self.pc.wrapping_add(imm as u64).wrapping_sub(4); // considering that PC will move +4 after instruction
         Ok(())
     }
 
-    fn beq(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-
-        if self.get_reg(rs1)? != self.get_reg(rs2)? {
-            return Ok(())
-        }
-
-        let imm_11 = (get_bits(7, 7, inst) << 11) as u64;
-        let imm_4_1 = (get_bits(11, 8, inst) << 1) as u64;
-        let imm_10_5 = (get_bits(30, 25, inst) << 5) as u64;
-        let imm_12 = (get_bits(31, 31, inst) << 12) as u64;
-        let offset = sign_extend_64(imm_12 | imm_11 | imm_10_5 | imm_4_1, 13);
-        self.pc += offset - 4; // considering that PC will move +4 after instruction
-        Ok(())
-    }
-
-    fn bne(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-
-        if self.get_reg(rs1)? == self.get_reg(rs2)? {
-            return Ok(())
-        }
-
-        let imm_11 = (get_bits(7, 7, inst) << 11) as u64;
-        let imm_4_1 = (get_bits(11, 8, inst) << 1) as u64;
-        let imm_10_5 = (get_bits(30, 25, inst) << 5) as u64;
-        let imm_12 = (get_bits(31, 31, inst) << 12) as u64;
-        let offset = sign_extend_64(imm_12 | imm_11 | imm_10_5 | imm_4_1, 13);
-        self.pc += offset - 4; // considering that PC will move +4 after instruction
-        Ok(())
-    }
-
-    fn blt(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-
-        if (self.get_reg(rs1)? as i64) >= (self.get_reg(rs2)? as i64) {
-            return Ok(())
-        }
-
-        let imm_11 = (get_bits(7, 7, inst) << 11) as u64;
-        let imm_4_1 = (get_bits(11, 8, inst) << 1) as u64;
-        let imm_10_5 = (get_bits(30, 25, inst) << 5) as u64;
-        let imm_12 = (get_bits(31, 31, inst) << 12) as u64;
-        let offset = sign_extend_64(imm_12 | imm_11 | imm_10_5 | imm_4_1, 13);
-        self.pc += offset - 4; // considering that PC will move +4 after instruction
-        Ok(())
-    }
-
-    fn bge(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-
-        if (self.get_reg(rs1)? as i64) < (self.get_reg(rs2)? as i64) {
-            return Ok(())
-        }
-
-        let imm_11 = (get_bits(7, 7, inst) << 11) as u64;
-        let imm_4_1 = (get_bits(11, 8, inst) << 1) as u64;
-        let imm_10_5 = (get_bits(30, 25, inst) << 5) as u64;
-        let imm_12 = (get_bits(31, 31, inst) << 12) as u64;
-        let offset = sign_extend_64(imm_12 | imm_11 | imm_10_5 | imm_4_1, 13);
-        self.pc += offset - 4; // considering that PC will move +4 after instruction
-        Ok(())
-    }
-
-    fn bltu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-
-        if self.get_reg(rs1)? >= self.get_reg(rs2)? {
-            return Ok(())
-        }
-
-        let imm_11 = (get_bits(7, 7, inst) << 11) as u64;
-        let imm_4_1 = (get_bits(11, 8, inst) << 1) as u64;
-        let imm_10_5 = (get_bits(30, 25, inst) << 5) as u64;
-        let imm_12 = (get_bits(31, 31, inst) << 12) as u64;
-        let offset = sign_extend_64(imm_12 | imm_11 | imm_10_5 | imm_4_1, 13);
-        self.pc += offset - 4; // considering that PC will move +4 after instruction
+    fn jalr(&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
+        let source_val = self.get_reg(rs1)?;
+        self.set_reg(rd, self.pc.wrapping_add(4))?;
+        let target = source_val.wrapping_add(imm as u64) & !1u64;
+        self.pc = target.wrapping_sub(4); // considering that PC will move +4 after instruction
         Ok(())
     }
 
-    fn bgeu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-
-        if self.get_reg(rs1)? < self.get_reg(rs2)? {
+    fn branch(&mut self, BType { rs1, rs2, imm }: BType, taken: fn(u64, u64) -> bool) -> Result<(), HartError> {
+        if !taken(self.get_reg(rs1)?, self.get_reg(rs2)?) {
             return Ok(())
         }
 
-        let imm_11 = (get_bits(7, 7, inst) << 11) as u64;
-        let imm_4_1 = (get_bits(11, 8, inst) << 1) as u64;
-        let imm_10_5 = (get_bits(30, 25, inst) << 5) as u64;
-        let imm_12 = (get_bits(31, 31, inst) << 12) as u64;
-        let offset = sign_extend_64(imm_12 | imm_11 | imm_10_5 | imm_4_1, 13);
-        self.pc += offset - 4; // considering that PC will move +4 after instruction
-        Ok(())
-    }
-
-    fn lb(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst);
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_32(get_bits(31, 20, inst), 12);
-        let addr = rs1 + imm;
-
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target),
-            EventPayload::MemoryLoadReq { 
-                address: addr as usize, 
-                size_in_bytes: 1,
-                requester: Target::Myself
-            }
-        );
-        
-        self.memory_wait_state = MemoryWaitState::DataForIReg { 
-            target: rd, 
-            size: 1, 
-            mode: IMode::Signed 
-        };
-        
-        Ok(())
-    }
-
-    fn lh(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst);
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_32(get_bits(31, 20, inst), 12);
-        let addr = rs1 + imm;
-
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target),
-            EventPayload::MemoryLoadReq { 
-                address: addr as usize,
-                size_in_bytes: 2, 
-                requester: Target::Myself
-            }
-        );
-        
-        self.memory_wait_state = MemoryWaitState::DataForIReg { 
-            target: rd, 
-            size: 2, 
-            mode: IMode::Signed 
-        };
-        
-        Ok(())
-    }
-
-    fn lw(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst);
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_32(get_bits(31, 20, inst), 12);
-        let addr = rs1 + imm;
-
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target),
-            EventPayload::MemoryLoadReq { 
-                address: addr as usize,
-                size_in_bytes: 4, 
-                requester: Target::Myself
-            }
-        );
-        
-        self.memory_wait_state = MemoryWaitState::DataForIReg { 
-            target: rd, 
-            size: 4, 
-            mode: IMode::Signed 
-        };
-        
-        Ok(())
-    }
-
-    fn lwu(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst);
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_32(get_bits(31, 20, inst), 12);
-        let addr = rs1 + imm;
-
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target),
-            EventPayload::MemoryLoadReq { 
-                address: addr as usize,
-                size_in_bytes: 4,
-                requester: Target::Myself
-            }
-        );
-        
-        self.memory_wait_state = MemoryWaitState::DataForIReg { 
-            target: rd, 
-            size: 4, 
-            mode: IMode::Unsigned 
-        };
-        
+        self.pc = self.pc.wrapping_add(imm as u64).wrapping_sub(4); // considering that PC will move +4 after instruction
         Ok(())
     }
 
-    fn lbu(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst);
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_32(get_bits(31, 20, inst), 12);
-        let addr = rs1 + imm;
+    fn load(&mut self, IType { rd, rs1, imm }: IType, size: usize, mode: IMode, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        let addr = self.get_reg(rs1)?.wrapping_add(imm as u64);
 
         engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target),
-            EventPayload::MemoryLoadReq { 
+            1,
+            Target::Module(self.data_port),
+            EventPayload::MemoryLoadReq {
                 address: addr as usize,
-                size_in_bytes: 1,
-                requester: Target::Myself
+                size_in_bytes: size,
+                requester: Target::Myself,
+                pc: Some(self.pc)
             }
         );
-        
-        self.memory_wait_state = MemoryWaitState::DataForIReg { 
-            target: rd, 
-            size: 1, 
-            mode: IMode::Unsigned 
-        };
-        
-        Ok(())
-    }
-
-    fn lhu(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst);
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_32(get_bits(31, 20, inst), 12);
-        let addr = rs1 + imm;
 
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target),
-            EventPayload::MemoryLoadReq { 
-                address: addr as usize,
-                size_in_bytes: 2,
-                requester: Target::Myself
-            }
-        );
-        
-        self.memory_wait_state = MemoryWaitState::DataForIReg { 
-            target: rd, 
-            size: 2, 
-            mode: IMode::Unsigned 
+        self.memory_wait_state = MemoryWaitState::DataForIReg {
+            target: rd,
+            size,
+            mode
         };
-        
-        Ok(())
-    }
 
-    fn ld(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
-        let rs1 = get_bits(19, 15, inst);
-        let rd = get_bits(11, 7, inst) as u8;
-        let imm = sign_extend_32(get_bits(31, 20, inst), 12);
-        let addr = rs1 + imm;
-        
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target),
-            EventPayload::MemoryLoadReq { 
-                address: addr as usize,
-                size_in_bytes: 8,
-                requester: Target::Myself
-            }
-        );
-        
-        self.memory_wait_state = MemoryWaitState::DataForIReg { 
-            target: rd, 
-            size: 8, 
-            mode: IMode::Unsigned 
-        };
-        
         Ok(())
     }
 
-    fn sb(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> { 
-        let rs1 = get_bits(19, 15, inst);
-        let imm_bits = get_bits(11, 7, inst) | ( get_bits(31, 25, inst) << 5 );
-        let imm = sign_extend_32(imm_bits, 12);
-        let addr = rs1 + imm;
-        let rs2 = get_bits(19, 15, inst) as u8;
+    fn store(&mut self, SType { rs1, rs2, imm }: SType, size: usize, engine_context: &mut dyn EngineContext) -> Result<(), HartError> {
+        let addr = self.get_reg(rs1)?.wrapping_add(imm as u64);
         let reg_val = self.get_reg(rs2)?;
 
         engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target), 
-            EventPayload::MemoryStoreReq { 
-                address: addr as usize, 
-                data: reg_val.to_le_bytes().to_vec()
-            }
-        );
-
-        self.memory_wait_state = MemoryWaitState::Idle;
-
-        Ok(())
-    }
-
-    fn sh(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> { 
-        let rs1 = get_bits(19, 15, inst);
-        let imm_bits = get_bits(11, 7, inst) | ( get_bits(31, 25, inst) << 5 );
-        let imm = sign_extend_32(imm_bits, 12);
-        let addr = rs1 + imm;
-        let rs2 = get_bits(19, 15, inst) as u8;
-        let reg_val = self.get_reg(rs2)?;
-
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target), 
-            EventPayload::MemoryStoreReq { 
-                address: addr as usize, 
-                data: reg_val.to_le_bytes().to_vec()
-            }
-        );
-
-        self.memory_wait_state = MemoryWaitState::Idle;
-
-        Ok(())
-    }
-
-    fn sw(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> { 
-        let rs1 = get_bits(19, 15, inst);
-        let imm_bits = get_bits(11, 7, inst) | ( get_bits(31, 25, inst) << 5 );
-        let imm = sign_extend_32(imm_bits, 12);
-        let addr = rs1 + imm;
-        let rs2 = get_bits(19, 15, inst) as u8;
-        let reg_val = self.get_reg(rs2)?;
-
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target), 
-            EventPayload::MemoryStoreReq { 
-                address: addr as usize, 
-                data: reg_val.to_le_bytes().to_vec() 
-            }
-        );
-
-        self.memory_wait_state = MemoryWaitState::Idle;
-
-        Ok(())
-    }
-
-    fn sd(&mut self, inst: u32, engine_context: &mut dyn EngineContext) -> Result<(), HartError> { 
-        let rs1 = get_bits(19, 15, inst);
-        let imm_bits = get_bits(11, 7, inst) | ( get_bits(31, 25, inst) << 5 );
-        let imm = sign_extend_32(imm_bits, 12);
-        let addr = rs1 + imm;
-        let rs2 = get_bits(19, 15, inst) as u8;
-        let reg_val = self.get_reg(rs2)?;
-
-        engine_context.schedule(
-            1, 
-            Target::Module(self.memory_bus_target), 
-            EventPayload::MemoryStoreReq { 
-                address: addr as usize, 
-                data: reg_val.to_le_bytes().to_vec() 
+            1,
+            Target::Module(self.data_port),
+            EventPayload::MemoryStoreReq {
+                address: addr as usize,
+                data: reg_val.to_le_bytes()[..size].to_vec(),
+                pc: Some(self.pc)
             }
         );
 
@@ -593,125 +176,84 @@ impl Hart {
         Ok(())
     }
 
-    fn addi(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let imm = get_bits(31, 20, inst);
+    fn addi(&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
-        self.set_reg(rd, source.wrapping_add(sign_extend_64(imm as u64, 12)))?;
+        self.set_reg(rd, source.wrapping_add(imm as u64))?;
         Ok(())
     }
 
-    fn slti(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let imm = get_bits(31, 20, inst);
+    fn slti(&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
-        let value = (source as i64) < (sign_extend_64(imm as u64, 12) as i64);
+        let value = (source as i64) < imm;
         self.set_reg(rd, value as u64)?;
         Ok(())
     }
 
-    fn sltiu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let imm = get_bits(31, 20, inst);
+    fn sltiu(&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
-        let value = source < sign_extend_64(imm as u64, 12);
+        let value = source < imm as u64;
         self.set_reg(rd, value as u64)?;
         Ok(())
     }
 
-    fn xori(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let imm = get_bits(31, 20, inst);
+    fn xori(&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
-        self.set_reg(rd, source ^ sign_extend_64(imm as u64, 12))?; 
+        self.set_reg(rd, source ^ imm as u64)?;
         Ok(())
     }
 
-    fn ori(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let imm = get_bits(31, 20, inst);
+    fn ori(&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
-        self.set_reg(rd, source | sign_extend_64(imm as u64, 12))?; 
+        self.set_reg(rd, source | imm as u64)?;
         Ok(())
     }
 
-    fn andi(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let imm = get_bits(31, 20, inst);
+    fn andi(&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
-        self.set_reg(rd, source & sign_extend_64(imm as u64, 12))?; 
+        self.set_reg(rd, source & imm as u64)?;
         Ok(())
     }
 
-    fn slli(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let shamt = get_bits(24, 20, inst);
+    fn slli(&mut self, IType { rd, rs1, imm: shamt }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
         self.set_reg(rd, source << shamt)?;
         Ok(())
     }
 
-    fn srli(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let shamt = get_bits(24, 20, inst);
+    fn srli(&mut self, IType { rd, rs1, imm: shamt }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
         self.set_reg(rd, source >> shamt)?;
         Ok(())
     }
 
-    fn srai(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        // let rs2 = get_bits(24, 20, inst) as u8;
-        // let shamt = self.get_reg(rs2)?;
-        let shamt = get_bits(25, 20, inst);
+    fn srai(&mut self, IType { rd, rs1, imm: shamt }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
         self.set_reg(rd, ((source as i64) >> shamt) as u64)?;
         Ok(())
     }
 
-    fn add(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn add(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)?;
         let source2 = self.get_reg(rs2)?;
         self.set_reg(rd, source1.wrapping_add(source2))?;
         Ok(())
     }
 
-    fn sub(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn sub(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)?;
         let source2 = self.get_reg(rs2)?;
         self.set_reg(rd, source1.wrapping_sub(source2))?;
         Ok(())
     }
 
-    fn sll(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-        let shamt = self.get_reg(rs2)?;
+    fn sll(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let shamt = self.get_reg(rs2)? & 0x3F;
         let source = self.get_reg(rs1)?;
         self.set_reg(rd, source << shamt)?;
         Ok(())
     }
 
-    fn slt(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn slt(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)?;
         let source2 = self.get_reg(rs2)?;
         let value = (source1 as i64) < (source2 as i64);
@@ -719,10 +261,7 @@ impl Hart {
         Ok(())
     }
 
-    fn sltu(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn sltu(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)?;
         let source2 = self.get_reg(rs2)?;
         let value = source1 < source2;
@@ -730,166 +269,121 @@ impl Hart {
         Ok(())
     }
 
-    fn xor(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn xor(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)?;
         let source2 = self.get_reg(rs2)?;
         self.set_reg(rd, source1 ^ source2)?;
         Ok(())
     }
 
-    fn srl(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-        let shamt = self.get_reg(rs2)?;
+    fn srl(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let shamt = self.get_reg(rs2)? & 0x3F;
         let source = self.get_reg(rs1)?;
         self.set_reg(rd, source >> shamt)?;
         Ok(())
     }
 
-    fn sra(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
-        let shamt = self.get_reg(rs2)?;
+    fn sra(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let shamt = self.get_reg(rs2)? & 0x3F;
         let source = self.get_reg(rs1)?;
-        self.set_reg(rd, 
+        self.set_reg(rd,
             (source as i64 >> shamt) as u64
         )?;
         Ok(())
     }
 
-    fn or(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn or(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)?;
         let source2 = self.get_reg(rs2)?;
         self.set_reg(rd, source1 | source2)?;
         Ok(())
     }
 
-    fn and(&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn and(&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)?;
         let source2 = self.get_reg(rs2)?;
         self.set_reg(rd, source1 & source2)?;
         Ok(())
     }
 
-    fn ecall(&mut self, inst: u32) -> Result<(), HartError> {
+    fn ecall(&mut self) -> Result<(), HartError> {
         todo!("ecall")
     }
 
-    fn ebreak(&mut self, inst: u32) -> Result<(), HartError> {
+    fn ebreak(&mut self) -> Result<(), HartError> {
         // TODO: temporarily using ebreak as a halt
         self.break_e = true;
         Ok(())
     }
 
-    fn addiw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let imm = get_bits(31, 20, inst);
-        let source = self.get_reg(rs1)? & 0x0000_0000_FFFF_FFFF;
-        self.set_reg(rd, source.wrapping_add(sign_extend_64(imm as u64, 12)))?;
+    fn addiw (&mut self, IType { rd, rs1, imm }: IType) -> Result<(), HartError> {
+        let source = self.get_reg(rs1)? as u32;
+        self.set_reg(rd, sign_extend_64(source.wrapping_add(imm as u32) as u64, 32))?;
         Ok(())
     }
 
-    fn slliw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let shamt = get_bits(24, 20, inst);
+    fn slliw (&mut self, IType { rd, rs1, imm: shamt }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
         let res: u32 = (source as u32) << shamt; // Cast to 32 bit, then sign extend
         self.set_reg(rd, sign_extend_64(res as u64, 32) )?;
         Ok(())
     }
 
-    fn srliw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let shamt = get_bits(24, 20, inst);
+    fn srliw (&mut self, IType { rd, rs1, imm: shamt }: IType) -> Result<(), HartError> {
         let source = self.get_reg(rs1)?;
         let res: u32 = (source as u32) >> shamt; // Cast to 32 bit, then sign extend
         self.set_reg(rd, sign_extend_64(res as u64, 32) )?;
         Ok(())
     }
-    
-    fn sraiw (&mut self, inst: u32) -> Result<(), HartError> {
-        if (inst & 0x0200_0000) == 0x0200_0000 {
-            return Err(HartError::ReservedInstruction(String::from("SRAIW With 25th bit as 1 is a reserved instruction.")));
-        }
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let shamt = get_bits(24, 20, inst);
+
+    fn sraiw (&mut self, IType { rd, rs1, imm: shamt }: IType) -> Result<(), HartError> {
         let source = ((self.get_reg(rs1)?) & 0x0000_0000_FFFF_FFFF) as u32;
-        self.set_reg(rd, 
+        self.set_reg(rd,
             sign_extend_64(((source as i32) >> shamt) as u64, 32)
         )?;
         Ok(())
     }
 
-    fn addw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn addw (&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)? as u32;
         let source2 = self.get_reg(rs2)? as u32;
-        self.set_reg(rd, 
-            sign_extend_64(source1.wrapping_add(source2) as u64, 32) 
+        self.set_reg(rd,
+            sign_extend_64(source1.wrapping_add(source2) as u64, 32)
         )?;
         Ok(())
     }
 
-    fn subw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = get_bits(24, 20, inst) as u8;
+    fn subw (&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
         let source1 = self.get_reg(rs1)? as u32;
         let source2 = self.get_reg(rs2)? as u32;
-        self.set_reg(rd, 
-            sign_extend_64(source1.wrapping_sub(source2) as u64, 32) 
+        self.set_reg(rd,
+            sign_extend_64(source1.wrapping_sub(source2) as u64, 32)
         )?;
         Ok(())
     }
 
-    fn sllw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = (get_bits(24, 20, inst) as u8) & 0x0F;
-        let shamt = self.get_reg(rs2)?;
+    fn sllw (&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let shamt = self.get_reg(rs2)? & 0x1F;
         let source = self.get_reg(rs1)? as u32;
-        self.set_reg(rd, 
+        self.set_reg(rd,
             sign_extend_64((source << shamt) as u64, 32)
         )?;
         Ok(())
     }
 
-    fn srlw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = (get_bits(24, 20, inst) as u8) & 0x0F;
-        let shamt = self.get_reg(rs2)?;
+    fn srlw (&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let shamt = self.get_reg(rs2)? & 0x1F;
         let source = self.get_reg(rs1)? as u32;
-        self.set_reg(rd, 
+        self.set_reg(rd,
             sign_extend_64((source >> shamt) as u64, 32)
         )?;
         Ok(())
     }
-    
-    fn sraw (&mut self, inst: u32) -> Result<(), HartError> {
-        let rd = get_bits(11, 7, inst) as u8;
-        let rs1 = get_bits(19, 15, inst) as u8;
-        let rs2 = (get_bits(24, 20, inst) as u8) & 0x0F;
-        let shamt = self.get_reg(rs2)?;
+
+    fn sraw (&mut self, RType { rd, rs1, rs2 }: RType) -> Result<(), HartError> {
+        let shamt = self.get_reg(rs2)? & 0x1F;
         let source = self.get_reg(rs1)? as u32;
-        self.set_reg(rd, 
+        self.set_reg(rd,
             sign_extend_64((source as i32 >> shamt) as u64, 32)
         )?;
         Ok(())
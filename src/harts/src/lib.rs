@@ -1,2 +1,4 @@
 mod util;
+pub mod decode;
+pub mod disasm;
 pub mod hart;
@@ -69,20 +69,6 @@ pub fn sign_extend_64(value: u64, original_size: u8) -> u64 {
     }
 }
 
-pub fn sign_extend_32(value: u32, original_size: u8) -> u32 {
-    if original_size == 0 || original_size > 64 {
-        panic!("cannot sign-extend value with original size of {original_size}")
-    }
-
-    let msb = value & (1 << (original_size - 1));
-
-    if msb == 0 {
-        value
-    } else {
-        value | (u32::MAX << original_size)
-    }
-}
-
 pub mod rounding_modes {
 
     const E63 : f32 = 9223372036854775808.0; // 2^63
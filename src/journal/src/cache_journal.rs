@@ -1,27 +1,229 @@
-#[derive(Default, Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+use crate::reuse::ReuseHistogram;
+
+/// Statistics of a single cache, or of a whole level once merged
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct CacheJournal {
-    cache_miss: Vec<u128>,
-    cache_hit: Vec<u128>,
+    pub name: String,
+    pub level: usize,
+    pub hits: u128,
+    pub misses: u128,
+    pub fetch_hits: u128,
+    pub fetch_misses: u128,
+    /// Hits and misses of loads and fetches
+    pub read_hits: u128,
+    pub read_misses: u128,
+    /// Hits and misses of stores and atomics
+    pub write_hits: u128,
+    pub write_misses: u128,
+    /// Misses on blocks never accessed before
+    pub compulsory_misses: u128,
+    /// Misses a fully associative LRU cache of the same size would also have had
+    pub capacity_misses: u128,
+    pub conflict_misses: u128,
+    pub fills: u128,
+    pub evictions: u128,
+    /// Dirty blocks written back, on eviction or when snooped
+    pub dirty_writebacks: u128,
+    /// Stores forwarded by write-through caches
+    pub write_throughs: u128,
+    /// Bytes brought in from the level below
+    pub bytes_up: u128,
+    /// Bytes sent to the level below, by writebacks and write-throughs
+    pub bytes_down: u128,
+    pub mshr_allocations: u128,
+    /// Sum of the MSHRs in use right after each allocation, for the average occupancy
+    pub mshr_occupancy: u128,
+    pub mshr_peak: usize,
+    pub mshr_merges: u128,
+    pub mshr_stalls: u128,
+    pub prefetches_issued: u128,
+    /// Prefetched blocks hit by a demand access before leaving the cache
+    pub prefetches_useful: u128,
+    /// Demand misses to a block a prefetch was still bringing in
+    pub prefetches_late: u128,
+    /// Prefetched blocks evicted or invalidated without ever being used
+    pub prefetches_useless: u128,
+    /// Misses Belady's optimal replacement would have had, for caches recording their block stream
+    pub optimal_misses: u128,
+    /// Reuse and stack distances of the blocks reaching the cache, when collected
+    pub reuse: ReuseHistogram,
 }
 
 impl CacheJournal {
-
-    pub fn new(cache_levels: usize) -> Self {
+    pub fn new(name: &str, level: usize) -> Self {
         CacheJournal {
-            cache_miss: vec![0; cache_levels],
-            cache_hit: vec![0; cache_levels],
+            name: name.to_string(),
+            level,
+            ..Default::default()
+        }
+    }
+
+    pub fn accesses(&self) -> u128 {
+        self.hits + self.misses
+    }
+
+    pub fn hit(&mut self, fetch: bool, write: bool) {
+        self.hits += 1;
+        if fetch {
+            self.fetch_hits += 1;
+        }
+        if write {
+            self.write_hits += 1;
+        } else {
+            self.read_hits += 1;
+        }
+    }
+
+    pub fn miss(&mut self, fetch: bool, write: bool) {
+        self.misses += 1;
+        if fetch {
+            self.fetch_misses += 1;
         }
+        if write {
+            self.write_misses += 1;
+        } else {
+            self.read_misses += 1;
+        }
+    }
+
+    pub fn compulsory_miss(&mut self) {
+        self.compulsory_misses += 1;
+    }
+
+    pub fn capacity_miss(&mut self) {
+        self.capacity_misses += 1;
+    }
+
+    pub fn conflict_miss(&mut self) {
+        self.conflict_misses += 1;
+    }
+
+    pub fn fill(&mut self, bytes: u128) {
+        self.fills += 1;
+        self.bytes_up += bytes;
+    }
+
+    pub fn eviction(&mut self) {
+        self.evictions += 1;
+    }
+
+    pub fn writeback(&mut self, bytes: u128) {
+        self.dirty_writebacks += 1;
+        self.bytes_down += bytes;
+    }
+
+    pub fn write_through(&mut self, bytes: u128) {
+        self.write_throughs += 1;
+        self.bytes_down += bytes;
+    }
+
+    pub fn mshr_allocation(&mut self, occupancy: usize) {
+        self.mshr_allocations += 1;
+        self.mshr_occupancy += occupancy as u128;
+        self.mshr_peak = self.mshr_peak.max(occupancy);
+    }
+
+    pub fn mshr_merge(&mut self) {
+        self.mshr_merges += 1;
+    }
+
+    pub fn mshr_stall(&mut self) {
+        self.mshr_stalls += 1;
+    }
+
+    pub fn prefetch_issued(&mut self) {
+        self.prefetches_issued += 1;
+    }
+
+    pub fn prefetch_useful(&mut self) {
+        self.prefetches_useful += 1;
+    }
+
+    pub fn prefetch_late(&mut self) {
+        self.prefetches_late += 1;
+    }
+
+    pub fn prefetch_useless(&mut self) {
+        self.prefetches_useless += 1;
+    }
+
+    pub fn optimal_misses(&mut self, misses: u128) {
+        self.optimal_misses += misses;
+    }
+
+    pub fn reuse_histogram(&mut self, histogram: &ReuseHistogram) {
+        self.reuse.merge(histogram);
     }
 
-    pub fn get(self) -> (Vec<u128>, Vec<u128>) {
-        (self.cache_miss, self.cache_hit)
+    /// Every counter, by the name it is published under
+    pub fn counters(&self) -> [(&'static str, u128); 27] {
+        [
+            ("hits", self.hits),
+            ("misses", self.misses),
+            ("fetch_hits", self.fetch_hits),
+            ("fetch_misses", self.fetch_misses),
+            ("read_hits", self.read_hits),
+            ("read_misses", self.read_misses),
+            ("write_hits", self.write_hits),
+            ("write_misses", self.write_misses),
+            ("compulsory_misses", self.compulsory_misses),
+            ("capacity_misses", self.capacity_misses),
+            ("conflict_misses", self.conflict_misses),
+            ("fills", self.fills),
+            ("evictions", self.evictions),
+            ("dirty_writebacks", self.dirty_writebacks),
+            ("write_throughs", self.write_throughs),
+            ("bytes_up", self.bytes_up),
+            ("bytes_down", self.bytes_down),
+            ("mshr_allocations", self.mshr_allocations),
+            ("mshr_occupancy", self.mshr_occupancy),
+            ("mshr_peak", self.mshr_peak as u128),
+            ("mshr_merges", self.mshr_merges),
+            ("mshr_stalls", self.mshr_stalls),
+            ("prefetches_issued", self.prefetches_issued),
+            ("prefetches_useful", self.prefetches_useful),
+            ("prefetches_late", self.prefetches_late),
+            ("prefetches_useless", self.prefetches_useless),
+            ("optimal_misses", self.optimal_misses),
+        ]
     }
 
-    pub fn miss(&mut self, level: usize) {
-        self.cache_miss[level] += 1;
+    /// Zeroes every counter, keeping the name and level
+    pub fn reset(&mut self) {
+        *self = Self::new(&self.name, self.level);
     }
 
-    pub fn hit(&mut self, level: usize) {
-        self.cache_hit[level] += 1;
+    /// Adds the counters of `other` to these ones. Peaks take the largest of both
+    pub fn merge(&mut self, other: &CacheJournal) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.fetch_hits += other.fetch_hits;
+        self.fetch_misses += other.fetch_misses;
+        self.read_hits += other.read_hits;
+        self.read_misses += other.read_misses;
+        self.write_hits += other.write_hits;
+        self.write_misses += other.write_misses;
+        self.compulsory_misses += other.compulsory_misses;
+        self.capacity_misses += other.capacity_misses;
+        self.conflict_misses += other.conflict_misses;
+        self.fills += other.fills;
+        self.evictions += other.evictions;
+        self.dirty_writebacks += other.dirty_writebacks;
+        self.write_throughs += other.write_throughs;
+        self.bytes_up += other.bytes_up;
+        self.bytes_down += other.bytes_down;
+        self.mshr_allocations += other.mshr_allocations;
+        self.mshr_occupancy += other.mshr_occupancy;
+        self.mshr_peak = self.mshr_peak.max(other.mshr_peak);
+        self.mshr_merges += other.mshr_merges;
+        self.mshr_stalls += other.mshr_stalls;
+        self.prefetches_issued += other.prefetches_issued;
+        self.prefetches_useful += other.prefetches_useful;
+        self.prefetches_late += other.prefetches_late;
+        self.prefetches_useless += other.prefetches_useless;
+        self.optimal_misses += other.optimal_misses;
+        self.reuse.merge(&other.reuse);
     }
 }
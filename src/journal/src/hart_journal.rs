@@ -1,23 +1,29 @@
-#[derive(Default, Debug, Clone)]
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Statistics of a single hart
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct HartJournal {
-    cycles_lost: u128,
-    num_cycles: u128,
-    num_inst: u128,
+    pub name: String,
+    pub cycles_lost: u128,
+    /// Cycles until the hart halted, zero while it runs
+    pub num_cycles: u128,
+    pub num_inst: u128,
+    /// Instructions retired by class, such as `load` or `branch_taken`
+    pub classes: BTreeMap<String, u128>,
+    /// Instructions retired by mnemonic
+    pub mnemonics: BTreeMap<String, u128>,
 }
 
 impl HartJournal {
-    pub fn new() -> Self {
+    pub fn new(name: &str) -> Self {
         HartJournal {
-            cycles_lost: 0,
-            num_cycles: 0,
-            num_inst: 0,
+            name: name.to_string(),
+            ..Default::default()
         }
     }
 
-    pub fn get(self) -> (u128, u128, u128) {
-        (self.cycles_lost, self.num_cycles, self.num_inst)
-    }
-
     pub fn lost_cycle(&mut self, amount: u128) {
         self.cycles_lost += amount;
     }
@@ -29,4 +35,20 @@ impl HartJournal {
     pub fn inst_done(&mut self, amount: u128) {
         self.num_inst += amount;
     }
+
+    /// Counts a retired instruction in the instruction mix
+    pub fn instruction_mix(&mut self, class: &str, mnemonic: &str) {
+        *self.classes.entry(class.to_string()).or_default() += 1;
+        *self.mnemonics.entry(mnemonic.to_string()).or_default() += 1;
+    }
+
+    /// Zeroes every counter, keeping the name
+    pub fn reset(&mut self) {
+        *self = Self::new(&self.name);
+    }
+
+    /// Instructions per cycle, unknown until the hart has run a cycle
+    pub fn ipc(&self) -> Option<f64> {
+        (self.num_cycles > 0).then(|| self.num_inst as f64 / self.num_cycles as f64)
+    }
 }
@@ -1,56 +1,258 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
     hart_journal::HartJournal,
-    cache_journal::CacheJournal
+    cache_journal::CacheJournal,
+    stats::{Formula, StatsRegistry}
 };
 
-#[derive(Default, Debug, Clone)]
+// Ratios of counters that are still zero are shown as n/a rather than NaN
+fn ratio(part: u128, whole: u128) -> String {
+    if whole == 0 { "n/a".to_string() } else { (part as f64 / whole as f64).to_string() }
+}
+
+fn percent(part: u128, whole: u128) -> String {
+    if whole == 0 { "n/a".to_string() } else { format!("{}%", part as f64 / whole as f64 * 100.0) }
+}
+
+// Most frequent first, ties by name
+fn by_count(counts: &BTreeMap<String, u128>) -> Vec<(&str, u128)> {
+    let mut sorted: Vec<(&str, u128)> = counts.iter().map(|(name, count)| (name.as_str(), *count)).collect();
+    sorted.sort_by(|(name_a, a), (name_b, b)| b.cmp(a).then_with(|| name_a.cmp(name_b)));
+
+    sorted
+}
+
+/// Statistics of a run. Harts and caches are kept apart, keyed by module id,
+/// and the totals of the machine and of each cache level are derived from them
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Journal {
-    pub cache_miss: Vec<u128>,
-    pub cache_hit: Vec<u128>,
-    pub cycles_lost: u128,
-    pub num_cycles: u128,
-    pub num_inst: u128,
+    pub harts: BTreeMap<usize, HartJournal>,
+    pub caches: BTreeMap<usize, CacheJournal>,
+    pub coherence_transitions: BTreeMap<String, u128>,
+    pub coherence_transactions: BTreeMap<String, u128>,
+    pub invalidations: u128,
+    pub interventions: u128,
+    /// DRAM accesses by row buffer outcome: hit, miss or conflict
+    pub dram_rows: BTreeMap<String, u128>,
+    pub dram_queue_cycles: u128,
+    pub dram_bytes: u128,
+    pub dram_refreshes: u128,
+    /// Wall-clock time the host spent simulating, in microseconds
+    pub host_micros: u128,
+    /// Statistics modules publish on their own, under `system.<module>`
+    pub published: StatsRegistry,
 }
 
 impl Journal {
-    pub fn new(cache_levels: usize) -> Self {
-        Journal {
-            cache_miss: vec![0; cache_levels],
-            cache_hit: vec![0; cache_levels],
-            cycles_lost: 0,
-            num_cycles: 0,
-            num_inst: 0,
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_hart(&mut self, id: usize, name: &str) {
+        self.harts.insert(id, HartJournal::new(name));
+    }
+
+    pub fn add_cache(&mut self, id: usize, name: &str, level: usize) {
+        self.caches.insert(id, CacheJournal::new(name, level));
+    }
+
+    pub fn hart(&mut self, id: usize) -> &mut HartJournal {
+        self.harts.get_mut(&id).unwrap_or_else(|| panic!("no hart journal for module {id}"))
+    }
+
+    pub fn cache(&mut self, id: usize) -> &mut CacheJournal {
+        self.caches.get_mut(&id).unwrap_or_else(|| panic!("no cache journal for module {id}"))
+    }
+
+    pub fn num_inst(&self) -> u128 {
+        self.harts.values().map(|hart| hart.num_inst).sum()
+    }
+
+    /// Cycles until the last hart halted
+    pub fn num_cycles(&self) -> u128 {
+        self.harts.values().map(|hart| hart.num_cycles).max().unwrap_or(0)
+    }
+
+    pub fn cycles_lost(&self) -> u128 {
+        self.harts.values().map(|hart| hart.cycles_lost).sum()
+    }
+
+    pub fn levels(&self) -> usize {
+        self.caches.values().map(|cache| cache.level + 1).max().unwrap_or(0)
+    }
+
+    /// Every cache of `level` merged together
+    pub fn level(&self, level: usize) -> CacheJournal {
+        let mut total = CacheJournal::new(&format!("L{}", level + 1), level);
+
+        for cache in self.caches.values().filter(|cache| cache.level == level) {
+            total.merge(cache);
         }
+
+        total
     }
-    
-    pub fn merge_hart(&mut self, hart: HartJournal) {
-        let (cycles_lost, num_cycles, num_inst) = hart.get();
-        self.cycles_lost += cycles_lost;
-        self.num_cycles += num_cycles;
-        self.num_inst += num_inst;
+
+    /// Zeroes every statistic, keeping the harts, caches and published statistics known so far
+    pub fn reset(&mut self) {
+        self.harts.values_mut().for_each(HartJournal::reset);
+        self.caches.values_mut().for_each(CacheJournal::reset);
+        self.published.reset();
+
+        *self = Self {
+            harts: std::mem::take(&mut self.harts),
+            caches: std::mem::take(&mut self.caches),
+            published: std::mem::take(&mut self.published),
+            ..Self::default()
+        };
     }
-    
-    pub fn merge_cache(&mut self, cache: CacheJournal) {
-        let (cache_miss, cache_hit) = cache.get();
 
-        let n = cache_miss.len();
+    /// Every statistic of the run under its path: those published by modules, those of each
+    /// hart, cache and level, the DRAM and coherence counters, and formulas derived from them
+    pub fn registry(&self) -> StatsRegistry {
+        let mut registry = self.published.clone();
+        let stat = |prefix: &str, name: &str| Formula::stat(&format!("{prefix}.{name}"));
+
+        registry.count("system.instructions", self.num_inst());
+        registry.count("system.cycles", self.num_cycles());
+        registry.count("system.cycles_lost", self.cycles_lost());
+        registry.formula("system.ipc", stat("system", "instructions") / stat("system", "cycles"));
+        registry.count("system.host_micros", self.host_micros);
+        registry.formula("system.host_mips", stat("system", "instructions") / stat("system", "host_micros"));
 
-        for i in 0..n {
-            self.cache_miss[i] += cache_miss[i];
-            self.cache_hit[i] += cache_hit[i];
+        for hart in self.harts.values() {
+            let prefix = format!("system.{}", hart.name);
+            registry.count(&format!("{prefix}.instructions"), hart.num_inst);
+            registry.count(&format!("{prefix}.cycles"), hart.num_cycles);
+            registry.count(&format!("{prefix}.cycles_lost"), hart.cycles_lost);
+            registry.formula(&format!("{prefix}.ipc"), stat(&prefix, "instructions") / stat(&prefix, "cycles"));
+
+            for (class, count) in &hart.classes {
+                registry.count(&format!("{prefix}.class.{class}"), *count);
+            }
+            // Dots of mnemonics such as `fadd.s` would read as levels of the path
+            for (mnemonic, count) in &hart.mnemonics {
+                registry.count(&format!("{prefix}.mnemonic.{}", mnemonic.replace('.', "_")), *count);
+            }
         }
+
+        let levels = (0..self.levels()).map(|level| self.level(level));
+        for cache in self.caches.values().cloned().chain(levels) {
+            let prefix = format!("system.{}", cache.name.to_lowercase());
+
+            for (name, value) in cache.counters() {
+                registry.count(&format!("{prefix}.{name}"), value);
+            }
+            registry.formula(
+                &format!("{prefix}.miss_rate"),
+                stat(&prefix, "misses") / (stat(&prefix, "hits") + stat(&prefix, "misses"))
+            );
+            registry.formula(
+                &format!("{prefix}.mshr_average_occupancy"),
+                stat(&prefix, "mshr_occupancy") / stat(&prefix, "mshr_allocations")
+            );
+
+            if cache.reuse.accesses() > 0 {
+                registry.count(&format!("{prefix}.cold_accesses"), cache.reuse.cold);
+                for (distance, count) in &cache.reuse.stack {
+                    registry.distribute_many(&format!("{prefix}.stack_distance"), *distance as u64, *count);
+                }
+                for (distance, count) in &cache.reuse.reuse {
+                    registry.distribute_many(&format!("{prefix}.reuse_distance"), *distance as u64, *count);
+                }
+            }
+        }
+
+        for (row, count) in &self.dram_rows {
+            registry.count(&format!("system.dram.row_{row}s"), *count);
+        }
+        registry.count("system.dram.queue_cycles", self.dram_queue_cycles);
+        registry.count("system.dram.bytes", self.dram_bytes);
+        registry.count("system.dram.refreshes", self.dram_refreshes);
+
+        registry.count("system.coherence.invalidations", self.invalidations);
+        registry.count("system.coherence.interventions", self.interventions);
+        for (request, count) in &self.coherence_transactions {
+            registry.count(&format!("system.coherence.{request}"), *count);
+        }
+        for (transition, count) in &self.coherence_transitions {
+            registry.count(&format!("system.coherence.{}", transition.replace("->", "_to_")), *count);
+        }
+
+        registry
+    }
+
+    pub fn dram_access(&mut self, row: &str, queued: u128, bytes: u128) {
+        *self.dram_rows.entry(row.to_string()).or_default() += 1;
+        self.dram_queue_cycles += queued;
+        self.dram_bytes += bytes;
+    }
+
+    pub fn dram_refresh(&mut self) {
+        self.dram_refreshes += 1;
+    }
+
+    pub fn coherence_transition(&mut self, from: &str, to: &str) {
+        *self.coherence_transitions.entry(format!("{from}->{to}")).or_default() += 1;
     }
 
-    pub fn lost_cycle(&mut self, amount: u128) {
-        self.cycles_lost += amount;
+    pub fn coherence_transaction(&mut self, request: &str) {
+        *self.coherence_transactions.entry(request.to_string()).or_default() += 1;
     }
 
-    pub fn cycles_done(&mut self, amount: u128) {
-        self.num_cycles += amount;
+    pub fn invalidation(&mut self) {
+        self.invalidations += 1;
     }
 
-    pub fn inst_done(&mut self, amount: u128) {
-        self.num_inst += amount;
+    pub fn intervention(&mut self) {
+        self.interventions += 1;
+    }
+
+    /// Reuse histograms of every level as `level,metric,distance,count` rows,
+    /// the distance of first accesses being `inf`
+    pub fn reuse_csv(&self) -> String {
+        let mut csv = String::from("level,metric,distance,count\n");
+
+        for i in 0..self.levels() {
+            let histogram = self.level(i).reuse;
+            if histogram.accesses() == 0 {
+                continue;
+            }
+
+            for (metric, distances) in [("stack", &histogram.stack), ("reuse", &histogram.reuse)] {
+                for (distance, count) in distances {
+                    csv.push_str(format!("L{},{metric},{distance},{count}\n", i + 1).as_str());
+                }
+                csv.push_str(format!("L{},{metric},inf,{}\n", i + 1, histogram.cold).as_str());
+            }
+        }
+
+        csv
+    }
+
+    /// Reuse histograms as a JSON object keyed by level, each holding the `cold` accesses
+    /// and the `stack` and `reuse` distance histograms as `[distance, count]` pairs
+    pub fn reuse_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Level {
+            cold: u128,
+            stack: Vec<(usize, u128)>,
+            reuse: Vec<(usize, u128)>,
+        }
+
+        let levels: BTreeMap<String, Level> = (0..self.levels())
+            .map(|i| (i, self.level(i).reuse))
+            .filter(|(_, histogram)| histogram.accesses() > 0)
+            .map(|(i, histogram)| (format!("L{}", i + 1), Level {
+                cold: histogram.cold,
+                stack: histogram.stack.into_iter().collect(),
+                reuse: histogram.reuse.into_iter().collect(),
+            }))
+            .collect();
+
+        serde_json::to_string(&levels).expect("reuse histograms are always serializable") + "\n"
     }
 
     #[rustfmt::skip]
@@ -59,45 +261,277 @@ impl Journal {
 Total instructions: {}
 Total cycles: {}
 Total cycles lost: {}
-IPC: {}\n
-___ Memory Results ___\n",
-            self.num_inst,
-            self.num_cycles,
-            self.cycles_lost,
-            (self.num_inst as f64/ self.num_cycles as f64)
+IPC: {}
+Host MIPS: {}\n",
+            self.num_inst(),
+            self.num_cycles(),
+            self.cycles_lost(),
+            ratio(self.num_inst(), self.num_cycles()),
+            ratio(self.num_inst(), self.host_micros)
         );
+        if self.harts.len() > 1 {
+            for hart in self.harts.values() {
+                dump.push_str(format!("{}: {} instructions, {} cycles, {} cycles lost, IPC {}\n",
+                    hart.name, hart.num_inst, hart.num_cycles, hart.cycles_lost, ratio(hart.num_inst, hart.num_cycles)
+                ).as_str());
+            }
+        }
+        for hart in self.harts.values().filter(|hart| !hart.classes.is_empty()) {
+            dump.push_str(format!("\n___ Instruction Mix ({}) ___\n", hart.name).as_str());
+            for (class, count) in by_count(&hart.classes) {
+                dump.push_str(format!("{class}: {count} ({})\n", percent(count, hart.num_inst)).as_str());
+            }
+            let mnemonics: Vec<String> = by_count(&hart.mnemonics)
+                .into_iter()
+                .map(|(mnemonic, count)| format!("{mnemonic} {count}"))
+                .collect();
+            dump.push_str(format!("Mnemonics: {}\n", mnemonics.join(", ")).as_str());
+        }
+        dump.push_str("\n___ Memory Results ___\n");
         let mut miss_total: u128 = 0;
         let mut hit_total: u128 = 0;
-        for i in 0..self.cache_miss.len() {
+        for i in 0..self.levels() {
+            let level = self.level(i);
             dump.push_str(format!(" __ L{} __
 Hits: {}
 Misses: {}
 Total accesses: {}
-Miss rate: {}%\n",
-                i+1, self.cache_hit[i], self.cache_miss[i],
-                self.cache_hit[i] + self.cache_miss[i],
-                (self.cache_miss[i] as f64 / (self.cache_hit[i] + self.cache_miss[i]) as f64) * 100.0
+Miss rate: {}\n",
+                i+1, level.hits, level.misses,
+                level.accesses(),
+                percent(level.misses, level.accesses())
+            ).as_str());
+            dump.push_str(format!("Read hits: {}
+Read misses: {}
+Write hits: {}
+Write misses: {}
+Fills: {}
+Evictions: {}
+Dirty writebacks: {}
+Write-throughs: {}
+Bytes from below: {}
+Bytes to below: {}\n",
+                level.read_hits, level.read_misses, level.write_hits, level.write_misses,
+                level.fills, level.evictions, level.dirty_writebacks, level.write_throughs,
+                level.bytes_up, level.bytes_down
             ).as_str());
-            miss_total += self.cache_miss[i];
-            hit_total += self.cache_hit[i];
+            if level.compulsory_misses + level.capacity_misses + level.conflict_misses > 0 {
+                dump.push_str(format!("Compulsory misses: {} ({})
+Capacity misses: {} ({})
+Conflict misses: {} ({})\n",
+                    level.compulsory_misses, percent(level.compulsory_misses, level.misses),
+                    level.capacity_misses, percent(level.capacity_misses, level.misses),
+                    level.conflict_misses, percent(level.conflict_misses, level.misses)
+                ).as_str());
+            }
+            if level.optimal_misses > 0 {
+                dump.push_str(format!("Optimal (Belady) misses: {}
+Optimal miss rate: {}\n",
+                    level.optimal_misses,
+                    percent(level.optimal_misses, level.accesses())
+                ).as_str());
+            }
+            if level.reuse.accesses() > 0 {
+                dump.push_str(format!("Reuse histogram accesses: {} ({} cold)\n",
+                    level.reuse.accesses(), level.reuse.cold
+                ).as_str());
+            }
+            if level.fetch_hits + level.fetch_misses > 0 {
+                let data_hit = level.hits - level.fetch_hits;
+                let data_miss = level.misses - level.fetch_misses;
+                dump.push_str(format!("Instruction miss rate: {} ({} of {})
+Data miss rate: {} ({} of {})\n",
+                    percent(level.fetch_misses, level.fetch_hits + level.fetch_misses),
+                    level.fetch_misses, level.fetch_hits + level.fetch_misses,
+                    percent(data_miss, data_hit + data_miss),
+                    data_miss, data_hit + data_miss
+                ).as_str());
+            }
+            if level.mshr_allocations > 0 {
+                dump.push_str(format!("MSHR allocations: {}
+MSHR average occupancy: {}
+MSHR peak occupancy: {}
+MSHR merges: {}
+MSHR stalls: {}\n",
+                    level.mshr_allocations,
+                    ratio(level.mshr_occupancy, level.mshr_allocations),
+                    level.mshr_peak, level.mshr_merges, level.mshr_stalls
+                ).as_str());
+            }
+            if level.prefetches_issued > 0 {
+                dump.push_str(format!("Prefetches issued: {}
+Prefetches useful: {}
+Prefetches late: {}
+Prefetches useless: {}
+Prefetch accuracy: {}\n",
+                    level.prefetches_issued, level.prefetches_useful,
+                    level.prefetches_late, level.prefetches_useless,
+                    percent(level.prefetches_useful + level.prefetches_late, level.prefetches_issued)
+                ).as_str());
+            }
+            let instances: Vec<&CacheJournal> = self.caches.values().filter(|cache| cache.level == i).collect();
+            if instances.len() > 1 {
+                for cache in instances {
+                    dump.push_str(format!("{}: {} hits, {} misses, miss rate {}\n",
+                        cache.name, cache.hits, cache.misses,
+                        percent(cache.misses, cache.accesses())
+                    ).as_str());
+                }
+            }
+            miss_total += level.misses;
+            hit_total += level.hits;
         }
         dump.push_str(format!(" __ Total __
 Hits: {}
 Misses: {}
 Total accesses: {}
-Miss rate: {}%\n",
+Miss rate: {}\n",
             hit_total, miss_total,
             hit_total + miss_total,
-            (miss_total as f64 / (hit_total + miss_total) as f64) * 100.0
+            percent(miss_total, hit_total + miss_total)
         ).as_str());
+        let dram_accesses: u128 = self.dram_rows.values().sum();
+        if dram_accesses > 0 {
+            let row_hits = self.dram_rows.get("hit").copied().unwrap_or(0);
+            dump.push_str(format!("\n___ DRAM Results ___
+Accesses: {}
+Row buffer hit rate: {}
+Average queueing latency: {} cycles
+Bandwidth: {} bytes/cycle
+Refreshes: {}\n",
+                dram_accesses,
+                percent(row_hits, dram_accesses),
+                ratio(self.dram_queue_cycles, dram_accesses),
+                ratio(self.dram_bytes, self.num_cycles()),
+                self.dram_refreshes
+            ).as_str());
+            for (row, count) in &self.dram_rows {
+                dump.push_str(format!("Row {row}: {count}\n").as_str());
+            }
+        }
+        if !self.coherence_transactions.is_empty() {
+            dump.push_str(format!("\n___ Coherence Results ___
+Invalidations: {}
+Interventions: {}\n",
+                self.invalidations, self.interventions
+            ).as_str());
+            for (request, count) in &self.coherence_transactions {
+                dump.push_str(format!("{request}: {count}\n").as_str());
+            }
+            for (transition, count) in &self.coherence_transitions {
+                dump.push_str(format!("{transition}: {count}\n").as_str());
+            }
+        }
         dump
     }
 }
 
-/*
-    cache_miss: Vec<u128>,
-    cache_hit: Vec<u128>,
-    cycles_lost: u128,
-    num_cycles: u128,
-    num_inst: u128,
-*/
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn levels_and_harts_aggregate_instances() {
+        let mut journal = Journal::new();
+        journal.add_hart(4, "hart0");
+        journal.add_hart(5, "hart1");
+        journal.add_cache(1, "l1d_0", 0);
+        journal.add_cache(2, "l1d_1", 0);
+        journal.add_cache(0, "l2_0", 1);
+
+        journal.hart(4).inst_done(10);
+        journal.hart(4).cycles_done(20);
+        journal.hart(5).inst_done(30);
+        journal.hart(5).cycles_done(40);
+
+        journal.cache(1).hit(false, false);
+        journal.cache(2).miss(false, true);
+        journal.cache(2).mshr_allocation(3);
+        journal.cache(1).mshr_allocation(1);
+
+        assert_eq!((journal.num_inst(), journal.num_cycles()), (40, 40));
+        assert_eq!(journal.harts[&4].ipc(), Some(0.5));
+        assert_eq!(HartJournal::new("idle").ipc(), None);
+
+        let l1 = journal.level(0);
+        assert_eq!((l1.hits, l1.misses, l1.write_misses, l1.mshr_peak), (1, 1, 1, 3));
+        assert_eq!(journal.levels(), 2);
+        assert_eq!(journal.level(1).accesses(), 0);
+    }
+
+    #[test]
+    fn dump_shows_empty_ratios_as_unavailable() {
+        let mut journal = Journal::new();
+        journal.add_hart(2, "hart0");
+        journal.add_cache(0, "l1i_0", 0);
+        journal.add_cache(1, "l1d_0", 0);
+        journal.cache(1).miss(false, false);
+
+        let dump = journal.dump();
+        assert!(!dump.contains("NaN"));
+        assert!(dump.contains("IPC: n/a"));
+        assert!(dump.contains("Host MIPS: n/a"));
+        assert!(dump.contains("l1i_0: 0 hits, 0 misses, miss rate n/a"));
+        assert!(dump.contains("l1d_0: 0 hits, 1 misses, miss rate 100%"));
+    }
+
+    #[test]
+    fn registry_derives_formulas_and_survives_reset() {
+        let mut journal = Journal::new();
+        journal.add_hart(1, "hart0");
+        journal.add_cache(0, "l1d_0", 0);
+        journal.hart(1).inst_done(6);
+        journal.hart(1).cycles_done(12);
+        journal.cache(0).miss(false, false);
+        journal.published.count("system.mem0.reads", 1);
+
+        let registry = journal.registry();
+        assert_eq!(registry.value("system.hart0.ipc"), Some(0.5));
+        assert_eq!(registry.value("system.l1.miss_rate"), Some(1.0));
+        assert_eq!(registry.value("system.l1d_0.misses"), Some(1.0));
+        assert_eq!(registry.value("system.mem0.reads"), Some(1.0));
+
+        journal.host_micros = 3;
+        assert_eq!(journal.registry().value("system.host_mips"), Some(2.0));
+
+        journal.reset();
+        let registry = journal.registry();
+        assert_eq!(registry.value("system.hart0.instructions"), Some(0.0));
+        assert_eq!(registry.value("system.l1d_0.miss_rate"), None);
+        assert_eq!(registry.value("system.mem0.reads"), Some(0.0));
+    }
+
+    #[test]
+    fn reuse_json_lists_distance_pairs_by_level() {
+        let mut journal = Journal::new();
+        journal.add_cache(0, "l1d_0", 0);
+        journal.add_cache(1, "l2_0", 1);
+
+        let mut tracker = crate::ReuseTracker::new();
+        for block in [0, 1, 0] {
+            tracker.access(block);
+        }
+        journal.cache(0).reuse_histogram(tracker.histogram());
+
+        let json: serde_json::Value = serde_json::from_str(&journal.reuse_json()).unwrap();
+        assert_eq!(json, serde_json::json!({ "L1": { "cold": 2, "stack": [[1, 1]], "reuse": [[1, 1]] } }));
+    }
+
+    #[test]
+    fn instruction_mix_is_kept_per_hart() {
+        let mut journal = Journal::new();
+        journal.add_hart(1, "hart0");
+        for (class, mnemonic) in [("load", "ld"), ("alu", "addi"), ("alu", "addi"), ("f", "fadd.s")] {
+            journal.hart(1).inst_done(1);
+            journal.hart(1).instruction_mix(class, mnemonic);
+        }
+
+        let registry = journal.registry();
+        assert_eq!(registry.value("system.hart0.class.alu"), Some(2.0));
+        assert_eq!(registry.value("system.hart0.mnemonic.fadd_s"), Some(1.0));
+
+        let dump = journal.dump();
+        assert!(dump.contains("alu: 2 (50%)\nf: 1 (25%)\nload: 1 (25%)\n"));
+        assert!(dump.contains("Mnemonics: addi 2, fadd.s 1, ld 1\n"));
+    }
+}
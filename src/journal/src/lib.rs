@@ -1,9 +1,15 @@
 mod journal;
 mod cache_journal;
 mod hart_journal;
+mod reuse;
+mod stats;
+mod time_series;
 
 pub use {
     journal::Journal,
     cache_journal::CacheJournal,
-    hart_journal::HartJournal
+    hart_journal::HartJournal,
+    reuse::{ReuseHistogram, ReuseTracker},
+    stats::{Formula, Stat, StatsRegistry},
+    time_series::{SampleInterval, TimeSeries}
 };
@@ -0,0 +1,178 @@
+use std::collections::{
+    BTreeMap,
+    HashMap
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Reuse and LRU stack distance histograms of the block accesses reaching a cache.
+///
+/// The reuse distance of an access counts the accesses since the last one to the
+/// same block, and its stack distance the distinct blocks accessed in between.
+/// A fully associative LRU cache of `n` blocks hits exactly the accesses with a
+/// stack distance below `n`, so one histogram predicts the misses of every size
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReuseHistogram {
+    pub stack: BTreeMap<usize, u128>,
+    pub reuse: BTreeMap<usize, u128>,
+    /// First accesses to a block, whose distances are infinite
+    pub cold: u128,
+}
+
+impl ReuseHistogram {
+    pub fn accesses(&self) -> u128 {
+        self.cold + self.stack.values().sum::<u128>()
+    }
+
+    /// Misses of a fully associative LRU cache of `n_blocks` blocks
+    pub fn misses_for(&self, n_blocks: usize) -> u128 {
+        self.cold + self.stack.range(n_blocks..).map(|(_, count)| count).sum::<u128>()
+    }
+
+    pub fn merge(&mut self, other: &ReuseHistogram) {
+        for (distance, count) in &other.stack {
+            *self.stack.entry(*distance).or_default() += count;
+        }
+        for (distance, count) in &other.reuse {
+            *self.reuse.entry(*distance).or_default() += count;
+        }
+        self.cold += other.cold;
+    }
+}
+
+/// Computes the histograms of a single block stream as it goes.
+///
+/// Only the latest access to each block is marked, in a Fenwick tree indexed by
+/// slot, so the stack distance of an access is the number of marks after the slot
+/// of the previous access to its block. Accesses take the next slot, and when the
+/// tree runs out of them the live blocks are renumbered from 0 in the same order,
+/// keeping the tree within twice the number of distinct blocks
+#[derive(Default, Debug, Clone)]
+pub struct ReuseTracker {
+    // Slot and access number of the latest access to every block
+    last_access: HashMap<usize, (usize, usize)>,
+    slots: usize,
+    tree: Vec<u32>,
+    accesses: usize,
+    histogram: ReuseHistogram,
+}
+
+impl ReuseTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn access(&mut self, block: usize) {
+        match self.last_access.remove(&block) {
+            Some((slot, previous)) => {
+                let stack = (self.prefix(self.slots) - self.prefix(slot + 1)) as usize;
+
+                *self.histogram.stack.entry(stack).or_default() += 1;
+                *self.histogram.reuse.entry(self.accesses - previous - 1).or_default() += 1;
+
+                self.add(slot, -1);
+            },
+            None => self.histogram.cold += 1,
+        }
+
+        if self.slots == self.tree.len() {
+            self.compact();
+        }
+
+        self.add(self.slots, 1);
+        self.last_access.insert(block, (self.slots, self.accesses));
+        self.slots += 1;
+        self.accesses += 1;
+    }
+
+    pub fn histogram(&self) -> &ReuseHistogram {
+        &self.histogram
+    }
+
+    // Renumbers the live blocks by last access, in a tree twice their number
+    fn compact(&mut self) {
+        let mut live: Vec<&mut (usize, usize)> = self.last_access.values_mut().collect();
+        live.sort_unstable_by_key(|(slot, _)| *slot);
+        for (new_slot, (slot, _)) in live.into_iter().enumerate() {
+            *slot = new_slot;
+        }
+        self.slots = self.last_access.len();
+
+        // Every node sums the marks it covers, which are all set below `slots`
+        self.tree = vec![0; (2 * self.slots).max(64)];
+        for i in 0..self.tree.len() {
+            self.tree[i] += (i < self.slots) as u32;
+
+            let parent = i | (i + 1);
+            if parent < self.tree.len() {
+                self.tree[parent] += self.tree[i];
+            }
+        }
+    }
+
+    fn add(&mut self, mut idx: usize, delta: i32) {
+        while idx < self.tree.len() {
+            self.tree[idx] = self.tree[idx].wrapping_add_signed(delta);
+            idx |= idx + 1;
+        }
+    }
+
+    // Number of marks before `end`
+    fn prefix(&self, end: usize) -> u32 {
+        let mut sum = 0;
+        let mut idx = end;
+
+        while idx > 0 {
+            sum += self.tree[idx - 1];
+            idx &= idx - 1;
+        }
+
+        sum
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stack_distance_counts_distinct_blocks() {
+        let mut tracker = ReuseTracker::new();
+        for block in [0, 1, 1, 2, 0, 2] {
+            tracker.access(block);
+        }
+
+        let histogram = tracker.histogram();
+        assert_eq!(histogram.cold, 3);
+        assert_eq!(histogram.stack, BTreeMap::from([(0, 1), (1, 1), (2, 1)]));
+        assert_eq!(histogram.reuse, BTreeMap::from([(0, 1), (1, 1), (3, 1)]));
+
+        assert_eq!(histogram.misses_for(1), 5);
+        assert_eq!(histogram.misses_for(3), 3);
+    }
+
+    #[test]
+    fn survives_tree_growth() {
+        let mut tracker = ReuseTracker::new();
+        for i in 0..1000 {
+            tracker.access(i % 100);
+        }
+
+        let histogram = tracker.histogram();
+        assert_eq!(histogram.cold, 100);
+        assert_eq!(histogram.stack, BTreeMap::from([(99, 900)]));
+        assert_eq!(histogram.accesses(), 1000);
+    }
+
+    #[test]
+    fn tree_is_bounded_by_the_live_blocks() {
+        let mut tracker = ReuseTracker::new();
+        for i in 0..100_000 {
+            tracker.access(i % 10);
+        }
+
+        assert_eq!(tracker.tree.len(), 64);
+        assert_eq!(tracker.histogram().stack, BTreeMap::from([(9, 99_990)]));
+        assert_eq!(tracker.histogram().reuse, BTreeMap::from([(9, 99_990)]));
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::BTreeMap,
+    ops
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Value computed from other statistics whenever it is read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Formula {
+    Stat(String),
+    Constant(f64),
+    Add(Box<Formula>, Box<Formula>),
+    Sub(Box<Formula>, Box<Formula>),
+    Mul(Box<Formula>, Box<Formula>),
+    Div(Box<Formula>, Box<Formula>),
+}
+
+impl Formula {
+    pub fn stat(path: &str) -> Self {
+        Self::Stat(path.to_string())
+    }
+
+    /// None when a statistic it reads is missing or has no value, or when dividing by zero
+    pub fn evaluate(&self, registry: &StatsRegistry) -> Option<f64> {
+        match self {
+            Self::Stat(path) => registry.value(path),
+            Self::Constant(value) => Some(*value),
+            Self::Add(a, b) => Some(a.evaluate(registry)? + b.evaluate(registry)?),
+            Self::Sub(a, b) => Some(a.evaluate(registry)? - b.evaluate(registry)?),
+            Self::Mul(a, b) => Some(a.evaluate(registry)? * b.evaluate(registry)?),
+            Self::Div(a, b) => {
+                let divisor = b.evaluate(registry)?;
+                (divisor != 0.0).then_some(a.evaluate(registry)? / divisor)
+            },
+        }
+    }
+}
+
+macro_rules! formula_op {
+    ($trait:ident, $method:ident, $variant:ident) => {
+        impl ops::$trait for Formula {
+            type Output = Formula;
+
+            fn $method(self, other: Formula) -> Formula {
+                Formula::$variant(Box::new(self), Box::new(other))
+            }
+        }
+    };
+}
+
+formula_op!(Add, add, Add);
+formula_op!(Sub, sub, Sub);
+formula_op!(Mul, mul, Mul);
+formula_op!(Div, div, Div);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Stat {
+    Counter(u128),
+    Average { sum: f64, samples: u128 },
+    /// Number of samples of each value
+    Distribution(BTreeMap<u64, u128>),
+    Formula(Formula),
+}
+
+impl Stat {
+    fn reset(&mut self) {
+        match self {
+            Self::Counter(count) => *count = 0,
+            Self::Average { sum, samples } => {
+                *sum = 0.0;
+                *samples = 0;
+            },
+            Self::Distribution(buckets) => buckets.clear(),
+            Self::Formula(_) => (),
+        }
+    }
+}
+
+fn distribution_mean(buckets: &BTreeMap<u64, u128>) -> Option<f64> {
+    let samples: u128 = buckets.values().sum();
+    let sum: f64 = buckets.iter().map(|(value, count)| *value as f64 * *count as f64).sum();
+
+    (samples > 0).then(|| sum / samples as f64)
+}
+
+/// Statistics kept under hierarchical paths such as `system.hart0.ipc`.
+///
+/// Statistics are created by their first update, and keep their kind from then on.
+/// Resetting zeroes every one of them without forgetting any
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsRegistry {
+    stats: BTreeMap<String, Stat>,
+}
+
+impl StatsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn count(&mut self, path: &str, amount: u128) {
+        match self.stats.entry(path.to_string()).or_insert(Stat::Counter(0)) {
+            Stat::Counter(count) => *count += amount,
+            stat => panic!("{path} is not a counter: {stat:?}"),
+        }
+    }
+
+    pub fn sample(&mut self, path: &str, value: f64) {
+        match self.stats.entry(path.to_string()).or_insert(Stat::Average { sum: 0.0, samples: 0 }) {
+            Stat::Average { sum, samples } => {
+                *sum += value;
+                *samples += 1;
+            },
+            stat => panic!("{path} is not an average: {stat:?}"),
+        }
+    }
+
+    pub fn distribute(&mut self, path: &str, value: u64) {
+        self.distribute_many(path, value, 1);
+    }
+
+    pub fn distribute_many(&mut self, path: &str, value: u64, samples: u128) {
+        match self.stats.entry(path.to_string()).or_insert(Stat::Distribution(BTreeMap::new())) {
+            Stat::Distribution(buckets) => *buckets.entry(value).or_default() += samples,
+            stat => panic!("{path} is not a distribution: {stat:?}"),
+        }
+    }
+
+    pub fn formula(&mut self, path: &str, formula: Formula) {
+        self.stats.insert(path.to_string(), Stat::Formula(formula));
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Stat> {
+        self.stats.get(path)
+    }
+
+    /// Scalar value of a statistic. Distributions give their mean
+    pub fn value(&self, path: &str) -> Option<f64> {
+        match self.stats.get(path)? {
+            Stat::Counter(count) => Some(*count as f64),
+            Stat::Average { sum, samples } => (*samples > 0).then(|| sum / *samples as f64),
+            Stat::Distribution(buckets) => distribution_mean(buckets),
+            Stat::Formula(formula) => formula.evaluate(self),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.stats.values_mut().for_each(Stat::reset);
+    }
+
+    /// What was recorded since `earlier`, a previous snapshot of this registry.
+    /// Formulas are kept, so that they are evaluated over the difference
+    pub fn delta(&self, earlier: &StatsRegistry) -> StatsRegistry {
+        let stats = self.stats.iter().map(|(path, stat)| {
+            let delta = match (stat, earlier.stats.get(path)) {
+                (Stat::Counter(count), Some(Stat::Counter(before))) => Stat::Counter(count.saturating_sub(*before)),
+                (Stat::Average { sum, samples }, Some(Stat::Average { sum: sum_before, samples: samples_before })) => Stat::Average {
+                    sum: sum - sum_before,
+                    samples: samples.saturating_sub(*samples_before),
+                },
+                (Stat::Distribution(buckets), Some(Stat::Distribution(before))) => Stat::Distribution(
+                    buckets.iter()
+                        .map(|(value, count)| (*value, count.saturating_sub(before.get(value).copied().unwrap_or(0))))
+                        .filter(|(_, count)| *count > 0)
+                        .collect()
+                ),
+                _ => stat.clone(),
+            };
+
+            (path.clone(), delta)
+        });
+
+        StatsRegistry { stats: stats.collect() }
+    }
+
+    /// Every statistic as `(path, value)` pairs, distributions being split into their
+    /// `::samples` and `::mean`, and the count of each value as `::<value>`
+    pub fn rows(&self) -> Vec<(String, Option<f64>)> {
+        let mut rows = Vec::new();
+
+        for (path, stat) in &self.stats {
+            match stat {
+                Stat::Distribution(buckets) => {
+                    rows.push((format!("{path}::samples"), Some(buckets.values().sum::<u128>() as f64)));
+                    rows.push((format!("{path}::mean"), distribution_mean(buckets)));
+                    for (value, count) in buckets {
+                        rows.push((format!("{path}::{value}"), Some(*count as f64)));
+                    }
+                },
+                _ => rows.push((path.clone(), self.value(path))),
+            }
+        }
+
+        rows
+    }
+
+    /// One statistic per line, values without one being shown as `n/a`
+    pub fn to_text(&self) -> String {
+        let rows = self.rows();
+        let width = rows.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+
+        let mut text = String::from("---------- Begin Simulation Statistics ----------\n");
+        for (path, value) in rows {
+            let value = value.map_or("n/a".to_string(), |value| value.to_string());
+            text.push_str(format!("{path:<width$}  {value}\n").as_str());
+        }
+        text.push_str("---------- End Simulation Statistics   ----------\n");
+
+        text
+    }
+
+    /// A flat object from path to value, values without one being null
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.json_object()).expect("statistics are always serializable") + "\n"
+    }
+
+    // Whole values are written as integers, as in the other formats
+    pub(crate) fn json_object(&self) -> serde_json::Map<String, serde_json::Value> {
+        self.rows()
+            .into_iter()
+            .map(|(path, value)| {
+                let value = match value {
+                    Some(value) if value.fract() == 0.0 && value.abs() < (1u64 << 53) as f64 => (value as i64).into(),
+                    Some(value) => serde_json::Number::from_f64(value).map_or(serde_json::Value::Null, serde_json::Value::Number),
+                    None => serde_json::Value::Null,
+                };
+
+                (path, value)
+            })
+            .collect()
+    }
+
+    /// `stat,value` rows, values without one being left empty
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("stat,value\n");
+
+        for (path, value) in self.rows() {
+            csv.push_str(format!("{path},{}\n", value.map_or(String::new(), |value| value.to_string())).as_str());
+        }
+
+        csv
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formulas_read_other_stats() {
+        let mut registry = StatsRegistry::new();
+        registry.count("system.l1.hits", 3);
+        registry.count("system.l1.misses", 1);
+        registry.formula(
+            "system.l1.miss_rate",
+            Formula::stat("system.l1.misses") / (Formula::stat("system.l1.hits") + Formula::stat("system.l1.misses"))
+        );
+
+        assert_eq!(registry.value("system.l1.miss_rate"), Some(0.25));
+
+        registry.reset();
+        assert_eq!(registry.value("system.l1.hits"), Some(0.0));
+        assert_eq!(registry.value("system.l1.miss_rate"), None);
+    }
+
+    #[test]
+    fn outputs_flatten_distributions() {
+        let mut registry = StatsRegistry::new();
+        registry.distribute("system.mem0.access_size", 4);
+        registry.distribute("system.mem0.access_size", 8);
+        registry.sample("system.hart0.latency", 2.0);
+        registry.formula("system.hart0.ratio", Formula::Constant(1.0) / Formula::Constant(0.0));
+
+        assert_eq!(registry.to_csv(), "stat,value
+system.hart0.latency,2
+system.hart0.ratio,
+system.mem0.access_size::samples,2
+system.mem0.access_size::mean,6
+system.mem0.access_size::4,1
+system.mem0.access_size::8,1
+");
+        assert!(registry.to_json().contains("\"system.hart0.ratio\":null"));
+
+        // Paths are escaped
+        registry.count("system.\"quoted\"", 1);
+        let json: serde_json::Value = serde_json::from_str(&registry.to_json()).unwrap();
+        assert_eq!((json["system.\"quoted\""].clone(), json["system.hart0.latency"].clone()), (1.into(), 2.into()));
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeSet;
+
+use crate::stats::{Formula, StatsRegistry};
+
+/// How often a time series takes a sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleInterval {
+    Cycles(u64),
+    /// Instructions retired by all harts together
+    Instructions(u64),
+}
+
+/// Statistics registry deltas taken at regular intervals of a run.
+///
+/// Each sample holds what was recorded since the previous one, along with
+/// `interval.cycles`, the cycles it spans, and `interval.ipc`
+#[derive(Debug, Clone)]
+pub struct TimeSeries {
+    interval: SampleInterval,
+    previous: StatsRegistry,
+    previous_cycle: u64,
+    // Cycle or instruction count the next sample is due at
+    next: u64,
+    samples: Vec<(u64, StatsRegistry)>,
+}
+
+impl TimeSeries {
+    /// Starts sampling at `cycle`, `registry` holding the statistics so far
+    pub fn new(interval: SampleInterval, cycle: u64, registry: StatsRegistry) -> Self {
+        let mut series = TimeSeries {
+            interval,
+            previous: StatsRegistry::new(),
+            previous_cycle: 0,
+            next: 0,
+            samples: Vec::new(),
+        };
+        series.restart(cycle, registry);
+
+        series
+    }
+
+    /// Takes the next samples from `registry`, once its statistics were reset at `cycle`
+    pub fn restart(&mut self, cycle: u64, registry: StatsRegistry) {
+        self.next = match self.interval {
+            SampleInterval::Cycles(cycles) => cycle + cycles.max(1),
+            SampleInterval::Instructions(instructions) => instructions.max(1),
+        };
+        self.previous = registry;
+        self.previous_cycle = cycle;
+    }
+
+    /// Cycle the next sample is due at, when sampling every N cycles
+    pub fn next_cycle(&self) -> Option<u64> {
+        matches!(self.interval, SampleInterval::Cycles(_)).then_some(self.next)
+    }
+
+    /// Whether `instructions` retired reach the next sample, when sampling every N instructions
+    pub fn instructions_due(&self, instructions: u128) -> bool {
+        matches!(self.interval, SampleInterval::Instructions(_)) && instructions >= self.next as u128
+    }
+
+    pub fn sample(&mut self, cycle: u64, registry: StatsRegistry) {
+        let mut delta = registry.delta(&self.previous);
+        delta.count("interval.cycles", (cycle - self.previous_cycle) as u128);
+        delta.formula("interval.ipc", Formula::stat("system.instructions") / Formula::stat("interval.cycles"));
+
+        self.samples.push((cycle, delta));
+        self.previous = registry;
+        self.previous_cycle = cycle;
+        self.next += match self.interval {
+            SampleInterval::Cycles(cycles) => cycles.max(1),
+            SampleInterval::Instructions(instructions) => instructions.max(1),
+        };
+    }
+
+    /// Samples what is left once the run ends, unless nothing ran since the last sample
+    pub fn finish(&mut self, cycle: u64, registry: StatsRegistry) {
+        if cycle > self.previous_cycle {
+            self.sample(cycle, registry);
+        }
+    }
+
+    /// Every sample, by the cycle it was taken at
+    pub fn samples(&self) -> &[(u64, StatsRegistry)] {
+        &self.samples
+    }
+
+    /// One row per sample, with a column per statistic found in any of them
+    pub fn to_csv(&self) -> String {
+        let rows: Vec<_> = self.samples.iter().map(|(cycle, delta)| (cycle, delta.rows())).collect();
+        let columns: BTreeSet<&String> = rows.iter().flat_map(|(_, row)| row.iter().map(|(path, _)| path)).collect();
+
+        let mut csv = String::from("cycle");
+        for column in &columns {
+            csv.push_str(format!(",{column}").as_str());
+        }
+        csv.push('\n');
+
+        for (cycle, row) in &rows {
+            csv.push_str(cycle.to_string().as_str());
+            for column in &columns {
+                let value = row.iter().find(|(path, _)| path == *column).and_then(|(_, value)| *value);
+                csv.push_str(format!(",{}", value.map_or(String::new(), |value| value.to_string())).as_str());
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// One JSON object per line and per sample
+    pub fn to_json_lines(&self) -> String {
+        self.samples
+            .iter()
+            .map(|(cycle, delta)| {
+                let mut object = delta.json_object();
+                object.insert("cycle".to_string(), (*cycle).into());
+
+                serde_json::to_string(&object).expect("statistics are always serializable") + "\n"
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn registry(instructions: u128, misses: u128) -> StatsRegistry {
+        let mut registry = StatsRegistry::new();
+        registry.count("system.instructions", instructions);
+        registry.count("system.l1.misses", misses);
+
+        registry
+    }
+
+    #[test]
+    fn samples_hold_deltas() {
+        let mut series = TimeSeries::new(SampleInterval::Cycles(10), 0, registry(0, 0));
+        assert_eq!(series.next_cycle(), Some(10));
+
+        series.sample(10, registry(5, 2));
+        series.sample(20, registry(15, 2));
+        series.finish(25, registry(15, 3));
+        series.finish(25, registry(15, 3));
+
+        assert_eq!(series.next_cycle(), Some(40));
+        assert_eq!(series.to_csv(), "cycle,interval.cycles,interval.ipc,system.instructions,system.l1.misses
+10,10,0.5,5,2
+20,10,1,10,0
+25,5,0,0,1
+");
+        assert!(series.to_json_lines().starts_with("{\"cycle\":10,\"interval.cycles\":10,"));
+    }
+
+    #[test]
+    fn instruction_intervals_restart_with_the_registry() {
+        let mut series = TimeSeries::new(SampleInterval::Instructions(4), 0, registry(0, 0));
+        assert!(!series.instructions_due(3));
+        assert!(series.instructions_due(4));
+
+        series.sample(8, registry(4, 1));
+        assert!(!series.instructions_due(7));
+
+        series.restart(9, registry(0, 0));
+        assert!(series.instructions_due(4));
+        series.sample(12, registry(4, 0));
+
+        assert_eq!(series.samples()[1].1.value("interval.cycles"), Some(3.0));
+        assert_eq!(series.samples()[1].1.value("system.l1.misses"), Some(0.0));
+    }
+}
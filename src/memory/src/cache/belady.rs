@@ -0,0 +1,72 @@
+use std::collections::{
+    BTreeSet,
+    HashMap
+};
+
+/// Misses of Belady's MIN replacement over `stream`, the block addresses that reached
+/// a cache of `n_sets` sets of `way` blocks, each `block_size` bytes long.
+///
+/// A first pass finds when every access is next reused, and a second one replays the
+/// stream evicting the block reused furthest in the future. Coherence invalidations
+/// are not replayed, so this is the bound for the replacement policy alone
+pub fn optimal_misses(stream: &[usize], n_sets: usize, way: usize, block_size: usize) -> usize {
+    let mut next_use = vec![usize::MAX; stream.len()];
+    let mut seen: HashMap<usize, usize> = HashMap::new();
+
+    for (i, block) in stream.iter().enumerate().rev() {
+        if let Some(next) = seen.insert(*block, i) {
+            next_use[i] = next;
+        }
+    }
+
+    // Blocks present in each set, ordered by their next use
+    let mut sets: Vec<BTreeSet<(usize, usize)>> = vec![BTreeSet::new(); n_sets];
+    let mut resident: HashMap<usize, usize> = HashMap::new();
+    let mut misses = 0;
+
+    for (i, block) in stream.iter().enumerate() {
+        let set = &mut sets[(block / block_size) % n_sets];
+
+        match resident.get(block) {
+            Some(current) => {
+                set.remove(&(*current, *block));
+            },
+            None => {
+                misses += 1;
+
+                if set.len() == way {
+                    let (_, evicted) = set.pop_last().expect("full set without blocks");
+                    resident.remove(&evicted);
+                }
+            }
+        }
+
+        set.insert((next_use[i], *block));
+        resident.insert(*block, next_use[i]);
+    }
+
+    misses
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_textbook_reference_string() {
+        let stream: Vec<usize> = [7, 0, 1, 2, 0, 3, 0, 4, 2, 3, 0, 3, 2, 1, 2, 0, 1, 7, 0, 1]
+            .iter()
+            .map(|page| page * 64)
+            .collect();
+
+        assert_eq!(optimal_misses(&stream, 1, 3, 64), 9);
+    }
+
+    #[test]
+    fn blocks_compete_only_within_their_set() {
+        let stream = [0x00, 0x40, 0x00, 0x40];
+
+        assert_eq!(optimal_misses(&stream, 2, 1, 64), 2);
+        assert_eq!(optimal_misses(&stream, 1, 1, 64), 4);
+    }
+}
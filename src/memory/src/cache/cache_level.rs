@@ -1,20 +1,39 @@
+use std::collections::VecDeque;
+
 use narvi_core::{
     CacheLevelConfig, 
     CacheReplacementPolicy, 
+    CacheLatency,
     CacheWritePolicy,
+    CoherenceConfig,
+    CoherenceMechanism,
+    CoherenceProtocol,
     EngineContext,
     Module, 
     ModuleId, 
     event::{
-        Event, EventPayload, JournalEvent, Target
+        AccessKind, AmoOp, CoherenceRequest, CoherenceState, Event, EventPayload, JournalEvent, MissClass, Target
     }
 };
 
-use rand::{random_range};
-
 use super::{
     CacheError, 
-    CacheReturn
+    CacheReturn,
+    miss_classifier::MissClassifier,
+    prefetcher::{
+        self,
+        PrefetchAccess,
+        Prefetcher
+    },
+    replacement::{
+        self,
+        ReplacementPolicy
+    }
+};
+
+use crate::coherence::{
+    granted_state,
+    snoop
 };
 
 macro_rules! mask_from {
@@ -58,34 +77,15 @@ impl CacheLine {
 #[derive(Debug, Clone)]
 struct CacheSet {
     cache_lines: Vec<CacheLine>,
-    policy: CacheReplacementPolicy,
-    policy_list: Vec<usize>,    // List used for LRU, LFU and FIFO logic 
-    way: usize,
 }
 
 impl CacheSet {
     fn new (
         block_size: usize, 
-        set_size: usize,
-        policy: CacheReplacementPolicy
+        set_size: usize
     ) -> Self {
-
-        let policy_list: Vec<usize> = if set_size == 1 {
-                vec![]
-            } else {
-                match policy {
-                    CacheReplacementPolicy::LRU => (0..set_size).rev().collect(),
-                    CacheReplacementPolicy::LFU => vec![0; set_size],
-                    CacheReplacementPolicy::FIFO => vec![],
-                    CacheReplacementPolicy::Random => vec![],
-                }
-        };
-
         CacheSet {
             cache_lines: vec![CacheLine::new(block_size); set_size],
-            policy,
-            policy_list,
-            way: set_size
         }
     }
     
@@ -95,7 +95,6 @@ impl CacheSet {
         replace_i: usize
     ) -> Result<usize, CacheError> {
         self.cache_lines[replace_i].update(data, 0)?;
-        self.policy_update(replace_i, true)?;
         Ok(replace_i)
     }
 
@@ -105,89 +104,13 @@ impl CacheSet {
         offset: usize, 
         i: usize
     ) -> Result<(), CacheError> {
-        self.cache_lines[i].update(data, offset)?;
-        self.policy_update(i, false)?;
-        Ok(())
-    }
-
-    fn policy_next(&mut self) -> Result<usize, CacheError> {
-
-        if self.way == 1 {
-            return Ok(0);
-        }
-
-        match self.policy {
-            CacheReplacementPolicy::LRU => { 
-                match self.policy_list.last() {
-                    Some(&res) => Ok(res),
-                    None => Err(CacheError::PolicyFailed)
-                }
-            },
-            CacheReplacementPolicy::LFU => {
-                match self.policy_list
-                          .iter()
-                          .enumerate()
-                          .min_by_key( |(_, val)| *val)
-                          .map( |(index, _)| index) {
-                    Some(min_idx) => Ok(min_idx),
-                    None => Err(CacheError::PolicyFailed)
-                }
-            },
-            CacheReplacementPolicy::FIFO => {
-                match self.policy_list.pop() {
-                    Some(res) => Ok(res),
-                    None => {
-                        if self.policy_list.len() != 0 {
-                            Err(CacheError::PolicyFailed)
-                        } else {
-                            Ok(0)   // First insertion
-                        }
-                    }
-                }
-            },
-            CacheReplacementPolicy::Random => Ok(random_range(0..self.way))
-        }
-    }
-
-    fn policy_update(
-        &mut self, 
-        idx: usize, 
-        insertion: bool
-    ) -> Result<(), CacheError> {
-        if self.way == 1 {
-            return Ok(());
-        }
-        match self.policy {
-            CacheReplacementPolicy::LRU => { 
-                let old_pos = 
-                    match self.policy_list.iter().position(|x| *x == idx) {
-                        Some(pos) => pos,
-                        None => return Err(CacheError::PolicyFailed)
-                    };
-                self.policy_list.remove(old_pos);
-                self.policy_list.insert(0, idx);
-            },
-            CacheReplacementPolicy::LFU => {
-                if insertion {  // Reset on new block
-                    self.policy_list[idx] = 0;
-                }
-                self.policy_list[idx] += 1;
-            },
-            CacheReplacementPolicy::FIFO => {
-                if insertion {
-                    self.policy_list.insert(0, idx);
-                }
-            },
-            CacheReplacementPolicy::Random => (),
-        }
-        Ok(())
+        self.cache_lines[i].update(data, offset)
     }
 
     pub fn print (&self) {
         for line in &self.cache_lines {
             line.print();
         }
-        println!("policy list: {:?}", self.policy_list);
     }
 }
 
@@ -200,15 +123,91 @@ pub struct CacheStats {
 
 #[derive(Debug)]
 enum PendingRequest {
-    Load { requester: Target, size: usize },
-    Store { data: Vec<u8> }
+    Load { requester: Target, size: usize, kind: AccessKind, pc: Option<u64> },
+    // Atomic operations are stores that also answer their requester with the bytes they replaced
+    Store { data: Vec<u8>, pc: Option<u64>, atomic: Option<(AmoOp, Target)> }
 }
 
+impl PendingRequest {
+    fn kind(&self) -> AccessKind {
+        match self {
+            Self::Load { kind, .. } => *kind,
+            Self::Store { atomic: Some(_), .. } => AccessKind::Amo,
+            Self::Store { atomic: None, .. } => AccessKind::Store,
+        }
+    }
+
+    fn pc(&self) -> Option<u64> {
+        match self {
+            Self::Load { pc, .. } | Self::Store { pc, .. } => *pc,
+        }
+    }
+}
+
+// Miss status holding register, tracking a block on its way from below
+// along with every request waiting for it
 #[derive(Debug)]
-pub struct CacheLevel {
-    backing_store: Option<ModuleId>,
+struct Mshr {
+    block: usize,
+    // Fetches stay fetches all the way down, so that every level can tell them apart
+    fetch_kind: AccessKind,
+    // PC of the instruction behind the miss, passed down to the levels below
+    pc: Option<u64>,
+    // Issued by the prefetcher, and not yet needed by any demand access
+    prefetch: bool,
+    // Coherence request issued for the block, and whether it was granted as shared
+    coherence: Option<(CoherenceRequest, bool)>,
+    // Class of the demand miss that took the MSHR, shared by the misses merged into it
+    miss_class: Option<MissClass>,
+    targets: Vec<(usize, PendingRequest)>,
+}
+
+impl Mshr {
+    fn new(block: usize, kind: AccessKind, pc: Option<u64>) -> Self {
+        Self {
+            block,
+            fetch_kind: if kind == AccessKind::Fetch { AccessKind::Fetch } else { AccessKind::Load },
+            pc,
+            prefetch: false,
+            coherence: None,
+            miss_class: None,
+            targets: Vec::new(),
+        }
+    }
+}
 
-    pending_request: Option<(usize, PendingRequest)>,
+// Address and bytes of an evicted dirty block
+type Writeback = (usize, Vec<u8>);
+
+#[derive(Debug, Clone, Copy)]
+struct Coherence {
+    protocol: CoherenceProtocol,
+    controller: ModuleId,
+    // Directories track sharers, so they must hear about every eviction, even silent ones
+    reports_evictions: bool,
+}
+
+#[derive(Debug)]
+pub struct CacheLevel {
+    backing_stores: Vec<ModuleId>,
+    interleave_size: usize,
+
+    mshrs: Vec<Mshr>,
+    mshr_count: usize,
+    // Requests that found every MSHR in use, in arrival order
+    blocked: VecDeque<Event>,
+
+    coherence: Option<Coherence>,
+
+    prefetcher: Option<Box<dyn Prefetcher>>,
+    // Blocks brought in by the prefetcher that no demand access has touched yet
+    prefetched: Vec<bool>,
+    // Prefetches past the end of memory are never issued
+    memory_size: usize,
+    // Whether demand accesses are journaled, for the optimal replacement replay or reuse histograms
+    record_stream: bool,
+    // Shadows the level to sort its misses into the three Cs, when asked to
+    miss_classifier: Option<MissClassifier>,
 
     offset_mask: usize,
     index_mask: usize,
@@ -218,15 +217,19 @@ pub struct CacheLevel {
     tag_start: usize,
 
     sets: Vec<CacheSet>,
+    replacement: Box<dyn ReplacementPolicy>,
     tags: Vec<usize>,
     valid: Vec<bool>,
     dirty: Vec<bool>,
+    states: Vec<CoherenceState>,
 
     way: usize,
     n_sets: usize,
     
     block_size: usize,
 
+    latency: CacheLatency,
+
     stats: CacheStats,
     pub(super) write_policy: CacheWritePolicy,
 }
@@ -234,116 +237,69 @@ pub struct CacheLevel {
 impl Module for CacheLevel {
     fn process_event(&mut self, event: Event, engine_context: &mut dyn EngineContext) {
         match event.payload() {
-            EventPayload::MemoryLoadReq { address, size_in_bytes, requester } => {
-                match self.read(*address, *size_in_bytes as usize) {
-                    Ok(CacheReturn::Hit(data)) => {
-                        engine_context.schedule(1, *requester, EventPayload::MemoryLoadRes { data: data });
-                        engine_context.record_journal(JournalEvent::CacheHit);
-                    },
-                    Ok(CacheReturn::Miss) | Err(_) => {
-                        self.pending_request = Some((
-                            *address, 
-                            PendingRequest::Load {
-                                requester: *requester,
-                                size: *size_in_bytes
-                            }
-                        ));
-
-                        let block_base_address = *address & (self.offset_mask ^ usize::MAX);
-
-                        engine_context.schedule(
-                            1,
-                            Target::Module(self.backing_store.unwrap()),
-                            EventPayload::MemoryLoadReq { 
-                                address: block_base_address, 
-                                size_in_bytes: self.block_size.div_ceil(8), 
-                                requester: Target::Myself 
-                            }
-                        );
-
-                        engine_context.record_journal(JournalEvent::CacheMiss);
-                    }
-                }
-            },
-            EventPayload::MemoryStoreReq { address, data } => {
-                if let Ok(true) = self.find(*address) {
-                    self.update(*address, data.clone()).expect("failed to update block");
-                    
-                    if matches!(self.write_policy, CacheWritePolicy::WriteThrough) {
-                        engine_context.schedule(
-                            1,
-                            Target::Module(self.backing_store.unwrap()), 
-                            EventPayload::MemoryStoreReq { 
-                                address: *address,
-                                data: data.clone()
-                            }
-                        );
-                    }
-
-                    engine_context.record_journal(JournalEvent::CacheHit);
-                } else {
-                    self.pending_request = Some((*address, PendingRequest::Store { data: data.clone() }));
-
-                    let block_base_address = *address & (self.offset_mask ^ usize::MAX);
-
-                    engine_context.schedule(
-                        1,
-                        Target::Module(self.backing_store.unwrap()), 
-                        EventPayload::MemoryLoadReq { 
-                            address: block_base_address, 
-                            size_in_bytes: self.block_size.div_ceil(8),
-                            requester: Target::Myself 
-                        }
-                    );
-
-                    engine_context.record_journal(JournalEvent::CacheMiss);
+            EventPayload::MemoryLoadReq { .. }
+            | EventPayload::MemoryFetchReq { .. }
+            | EventPayload::MemoryStoreReq { .. }
+            | EventPayload::MemoryAmoReq { .. } => {
+                // Requests keep their order while earlier ones wait for an MSHR
+                if !self.blocked.is_empty() || !self.access(&event, engine_context) {
+                    engine_context.record_journal(JournalEvent::MshrStall);
+                    self.blocked.push_back(event);
                 }
             },
-            EventPayload::MemoryLoadRes { data } => {
-                let (orig_addr, req_type) = self.pending_request.take()
-                    .expect("received memory without a pending request");
-
-                let insert_result = self.insert(orig_addr, data.clone()).expect("failed to insert block");
-
-                if let Some((dirty_addr, dirty_bytes)) = insert_result {
-                    if matches!(self.write_policy, CacheWritePolicy::WriteBack) {
-                        engine_context.schedule(
-                            1,
-                            Target::Module(self.backing_store.unwrap()), 
-                            EventPayload::MemoryStoreReq { 
-                                address: dirty_addr, 
-                                data: dirty_bytes, 
-                            }
-                        );
-                    }
+            EventPayload::MemoryLoadRes { address, data } => {
+                let mshr_idx = self.mshr_for(*address)
+                    .expect("received memory without a pending miss");
+                let prefetch = self.mshrs[mshr_idx].prefetch;
+
+                match self.mshrs[mshr_idx].coherence {
+                    Some((request, shared)) => {
+                        self.fill(*address, data.clone(), Some(granted_state(request, shared)), prefetch, engine_context);
+                        self.release_coherence(*address, engine_context);
+                    },
+                    None => self.fill(*address, data.clone(), None, prefetch, engine_context),
                 }
 
-                match req_type {
-                    PendingRequest::Load { requester, size } => {
-                        if let Ok(CacheReturn::Hit(requested_data)) = self.read(orig_addr, size) {
-                            engine_context.schedule(
-                                1,
-                                requester,
-                                EventPayload::MemoryLoadRes { data: requested_data }
-                            );
-                        }
+                self.retire(mshr_idx, engine_context);
+            },
+            EventPayload::CoherenceGrant { address, shared, data } => {
+                let mshr_idx = self.mshr_for(*address)
+                    .expect("received a coherence grant without a pending miss");
+                let (request, _) = self.mshrs[mshr_idx].coherence
+                    .expect("received a coherence grant without a pending request");
+                let prefetch = self.mshrs[mshr_idx].prefetch;
+
+                match data {
+                    // Upgrades only take the block when they lost their copy to another writer
+                    Some(block) if request != CoherenceRequest::Upgrade || self.position(*address).is_none() => {
+                        self.fill(*address, block.clone(), Some(granted_state(request, *shared)), prefetch, engine_context);
+                        self.release_coherence(*address, engine_context);
+                        self.retire(mshr_idx, engine_context);
                     },
-                    PendingRequest::Store { data: store_data } => {
-                        self.update(orig_addr, store_data.clone()).expect("failed to apply pending store");
-
-                        if matches!(self.write_policy, CacheWritePolicy::WriteThrough) {
-                            engine_context.schedule(
-                                1,
-                                Target::Module(self.backing_store.unwrap()),
-                                EventPayload::MemoryStoreReq {
-                                    address: orig_addr,
-                                    data: store_data,
-                                }
-                            );
+                    _ if request == CoherenceRequest::Upgrade => {
+                        self.release_coherence(*address, engine_context);
+
+                        match self.position(*address) {
+                            Some(block_idx) => {
+                                self.set_state(block_idx, CoherenceState::Modified, engine_context);
+                                self.retire(mshr_idx, engine_context);
+                            },
+                            // The block was invalidated while the upgrade waited for the bus
+                            None => self.request_coherence(mshr_idx, CoherenceRequest::ReadExclusive, engine_context),
                         }
+                    },
+                    _ => {
+                        // Nobody supplied the block, so it comes from the backing store
+                        self.mshrs[mshr_idx].coherence = Some((request, *shared));
+
+                        let Mshr { fetch_kind, pc, .. } = self.mshrs[mshr_idx];
+                        self.fetch_block(*address, fetch_kind, pc, engine_context);
                     }
                 }
             },
+            EventPayload::CoherenceSnoop { address, request, requester } => {
+                self.snoop(*address, *request, *requester, engine_context);
+            },
             EventPayload::Reset => {},
             _ => panic!("unable to process {event}")
         }
@@ -353,7 +309,7 @@ impl Module for CacheLevel {
 impl From<&CacheLevelConfig> for CacheLevel {
     fn from(config: &CacheLevelConfig) -> Self {
         let offset_size = config.block_size.ilog2() as usize;
-        let index_size = config.n_blocks.ilog2() as usize;
+        let index_size = (config.n_blocks / config.set_size).ilog2() as usize;
 
         // Create masks
         let offset_mask = mask_from!(offset_size, 0);
@@ -362,27 +318,35 @@ impl From<&CacheLevelConfig> for CacheLevel {
         let tag_mask = (offset_mask | index_mask) ^ usize::MAX; 
 
         let n_sets = config.n_blocks / config.set_size;
-        let base_set = CacheSet::new(
-            config.block_size, 
-            config.set_size, 
-            config.replacement_policy
-        );
+        let base_set = CacheSet::new(config.block_size, config.set_size);
 
         CacheLevel {
-            backing_store: None,
-            pending_request: None,
+            backing_stores: Vec::new(),
+            interleave_size: usize::MAX,
+            mshrs: Vec::new(),
+            mshr_count: config.mshrs,
+            blocked: VecDeque::new(),
+            coherence: None,
+            prefetcher: config.prefetcher.as_ref().map(prefetcher::from_config),
+            prefetched: vec![false; config.n_blocks],
+            memory_size: usize::MAX,
+            record_stream: config.belady || config.reuse_histogram,
+            miss_classifier: config.classify_misses.then(|| MissClassifier::new(config.n_blocks)),
             index_mask, 
             tag_mask, 
             offset_mask,
             index_start: offset_size,
             tag_start: offset_size + index_size,
             sets: vec![base_set; n_sets],
+            replacement: replacement::from_config(config.replacement_policy, n_sets, config.set_size),
             tags: vec![0; config.n_blocks],
             valid: vec![false ; config.n_blocks], 
             dirty: vec![false ; config.n_blocks], 
+            states: vec![CoherenceState::Invalid; config.n_blocks],
             way: config.set_size,
             n_sets,
             block_size: config.block_size,
+            latency: config.latency,
             stats: Default::default(),
             write_policy: config.write_policy
         }
@@ -408,30 +372,575 @@ impl CacheLevel {
         // remaining bits
         let tag_mask = (offset_mask | index_mask) ^ usize::MAX; 
 
-        let base_set = CacheSet::new(block_size, associativity, replacement_policy);
+        let base_set = CacheSet::new(block_size, associativity);
 
         CacheLevel {
-            backing_store: None,
-            pending_request: None,
+            backing_stores: Vec::new(),
+            interleave_size: usize::MAX,
+            mshrs: Vec::new(),
+            mshr_count: 1,
+            blocked: VecDeque::new(),
+            coherence: None,
+            prefetcher: None,
+            prefetched: vec![false; n_blocks],
+            memory_size: usize::MAX,
+            record_stream: false,
+            miss_classifier: None,
             index_mask,
             tag_mask,
             offset_mask,
             index_start: offset_size,
             tag_start: index_size + offset_size,
             sets: vec![base_set; n_sets],
+            replacement: replacement::from_config(replacement_policy, n_sets, associativity),
             tags: vec![0; n_blocks],
             valid: vec![false ; n_blocks], 
             dirty: vec![false ; n_blocks], 
+            states: vec![CoherenceState::Invalid; n_blocks],
             way: associativity,
             n_sets,
             block_size,
+            latency: CacheLatency::default(),
             stats: Default::default(),
             write_policy
         }
     }
 
+    /// Sets how many misses can be outstanding at once
+    pub fn set_mshrs(&mut self, mshrs: usize) {
+        self.mshr_count = mshrs;
+    }
+
+    pub fn set_latency(&mut self, latency: CacheLatency) {
+        self.latency = latency;
+    }
+
+    pub fn set_prefetcher(&mut self, prefetcher: Box<dyn Prefetcher>) {
+        self.prefetcher = Some(prefetcher);
+    }
+
+    /// Sets the size of the memory behind this level, so that prefetches never run past it
+    pub fn set_memory_size(&mut self, memory_size: usize) {
+        self.memory_size = memory_size;
+    }
+
+    /// Journals the block of every demand access, to replay them with optimal replacement
+    pub fn set_record_stream(&mut self, record_stream: bool) {
+        self.record_stream = record_stream;
+    }
+
+    /// Replaces the policy picked from the configuration, such as with one defined outside this crate
+    pub fn set_replacement_policy(&mut self, replacement: Box<dyn ReplacementPolicy>) {
+        self.replacement = replacement;
+    }
+
     pub fn set_backing_store(&mut self, backing_store: ModuleId) {
-        self.backing_store = Some(backing_store);
+        self.backing_stores = vec![backing_store];
+    }
+
+    /// Connects this level to several backing stores, such as memory controllers,
+    /// each one responsible for every `interleave_size` bytes in turn
+    pub fn set_interleaved_backing_stores(&mut self, backing_stores: Vec<ModuleId>, interleave_size: usize) {
+        self.backing_stores = backing_stores;
+        self.interleave_size = interleave_size;
+    }
+
+    fn backing_store_for(&self, addr: usize) -> ModuleId {
+        let n = self.backing_stores.len();
+        assert!(n > 0, "cache level has no backing store");
+
+        self.backing_stores[(addr / self.interleave_size) % n]
+    }
+
+    // Serves a load, fetch, store or atomic operation, returning false when it misses but no MSHR is free
+    fn access(&mut self, event: &Event, engine_context: &mut dyn EngineContext) -> bool {
+        let (address, request) = match event.payload() {
+            EventPayload::MemoryLoadReq { address, size_in_bytes, requester, pc } => (
+                *address,
+                PendingRequest::Load { requester: *requester, size: *size_in_bytes, kind: AccessKind::Load, pc: *pc }
+            ),
+            EventPayload::MemoryFetchReq { address, size_in_bytes, requester, pc } => (
+                *address,
+                PendingRequest::Load { requester: *requester, size: *size_in_bytes, kind: AccessKind::Fetch, pc: *pc }
+            ),
+            EventPayload::MemoryStoreReq { address, data, pc } => (
+                *address,
+                PendingRequest::Store { data: data.clone(), pc: *pc, atomic: None }
+            ),
+            EventPayload::MemoryAmoReq { address, op, data, requester, pc } => (
+                *address,
+                PendingRequest::Store { data: data.clone(), pc: *pc, atomic: Some((*op, *requester)) }
+            ),
+            _ => unreachable!("{event} is not a memory access"),
+        };
+
+        let kind = request.kind();
+        let pc = request.pc();
+
+        // Secondary miss, served along with the one already on its way
+        if let Some(mshr_idx) = self.mshr_for(address) {
+            let mshr = &mut self.mshrs[mshr_idx];
+
+            // A demand access caught up with a prefetch that was issued too late
+            if mshr.prefetch {
+                mshr.prefetch = false;
+                engine_context.record_journal(JournalEvent::PrefetchLate);
+            }
+
+            mshr.targets.push((address, request));
+
+            // Misses merged into a prefetch are classified on their own
+            let class = self.classify(address, true);
+            let class = self.mshrs[mshr_idx].miss_class.or(class);
+            self.mshrs[mshr_idx].miss_class = class;
+
+            engine_context.record_journal(JournalEvent::CacheMiss { kind, class, pc });
+            engine_context.record_journal(JournalEvent::MshrMerge);
+            self.record_block(address, engine_context);
+            self.prefetch(PrefetchAccess { address, pc, kind, miss: true }, engine_context);
+            return true;
+        }
+
+        let block_idx = self.position(address);
+
+        // Stores to blocks other caches may share must invalidate them first
+        let needs_upgrade = self.coherence.is_some()
+            && matches!(kind, AccessKind::Store | AccessKind::Amo)
+            && block_idx.is_some_and(|idx| matches!(
+                self.states[idx],
+                CoherenceState::Shared | CoherenceState::Owned
+            ));
+
+        if (block_idx.is_none() || needs_upgrade) && self.mshrs.len() == self.mshr_count {
+            return false;
+        }
+
+        if let Some(idx) = block_idx
+            && self.prefetched[idx]
+        {
+            self.prefetched[idx] = false;
+            engine_context.record_journal(JournalEvent::PrefetchUseful);
+        }
+
+        match request {
+            PendingRequest::Load { requester, size, kind, pc } => match self.read(address, size) {
+                Ok(CacheReturn::Hit(data)) => {
+                    engine_context.schedule(self.latency.hit, requester, EventPayload::MemoryLoadRes { address, data });
+                    engine_context.record_journal(JournalEvent::CacheHit { kind });
+                    self.classify(address, false);
+                },
+                Ok(CacheReturn::Miss) | Err(_) => {
+                    let mshr_idx = self.allocate(address, request, engine_context);
+
+                    if self.coherence.is_some() {
+                        self.request_coherence(mshr_idx, CoherenceRequest::Read, engine_context);
+                    } else {
+                        self.fetch_block(address, kind, pc, engine_context);
+                    }
+
+                    let class = self.classify(address, true);
+                    self.mshrs[mshr_idx].miss_class = class;
+                    engine_context.record_journal(JournalEvent::CacheMiss { kind, class, pc });
+                }
+            },
+            PendingRequest::Store { data, pc, atomic } => {
+                if let Ok(true) = self.find(address) {
+                    engine_context.record_journal(JournalEvent::CacheHit { kind });
+                    self.classify(address, false);
+
+                    if needs_upgrade {
+                        let mshr_idx = self.allocate(address, PendingRequest::Store { data, pc, atomic }, engine_context);
+                        self.request_coherence(mshr_idx, CoherenceRequest::Upgrade, engine_context);
+                    } else {
+                        if let Some(idx) = block_idx
+                            && self.states[idx] == CoherenceState::Exclusive
+                        {
+                            self.set_state(idx, CoherenceState::Modified, engine_context);
+                        }
+
+                        self.store(address, data, pc, atomic, self.latency.hit, engine_context);
+                    }
+                } else {
+                    let mshr_idx = self.allocate(address, PendingRequest::Store { data, pc, atomic }, engine_context);
+
+                    if self.coherence.is_some() {
+                        self.request_coherence(mshr_idx, CoherenceRequest::ReadExclusive, engine_context);
+                    } else {
+                        self.fetch_block(address, AccessKind::Load, pc, engine_context);
+                    }
+
+                    let class = self.classify(address, true);
+                    self.mshrs[mshr_idx].miss_class = class;
+                    engine_context.record_journal(JournalEvent::CacheMiss { kind, class, pc });
+                }
+            }
+        }
+
+        self.record_block(address, engine_context);
+        self.prefetch(PrefetchAccess { address, pc, kind, miss: block_idx.is_none() }, engine_context);
+
+        true
+    }
+
+    // The class of a miss, when the level classifies them
+    fn classify(&mut self, address: usize, miss: bool) -> Option<MissClass> {
+        let block = self.block_of(address);
+        self.miss_classifier.as_mut().and_then(|classifier| classifier.access(block, miss))
+    }
+
+    fn record_block(&self, address: usize, engine_context: &mut dyn EngineContext) {
+        if self.record_stream {
+            engine_context.record_journal(JournalEvent::BlockAccess { block: self.block_of(address) });
+        }
+    }
+
+    // Lets the prefetcher see a demand access, and issues the prefetches it asks for.
+    // Prefetches never wait for an MSHR, they are dropped when none is free
+    fn prefetch(&mut self, access: PrefetchAccess, engine_context: &mut dyn EngineContext) {
+        let Some(prefetcher) = self.prefetcher.as_mut() else {
+            return;
+        };
+
+        for address in prefetcher.observe(&access, self.block_size) {
+            let block = self.block_of(address);
+
+            if block >= self.memory_size || self.position(block).is_some() || self.mshr_for(block).is_some() {
+                continue;
+            }
+
+            if self.mshrs.len() == self.mshr_count {
+                break;
+            }
+
+            let mut mshr = Mshr::new(block, access.kind, access.pc);
+            mshr.prefetch = true;
+            let kind = mshr.fetch_kind;
+            self.mshrs.push(mshr);
+
+            engine_context.record_journal(JournalEvent::PrefetchIssued);
+
+            if self.coherence.is_some() {
+                self.request_coherence(self.mshrs.len() - 1, CoherenceRequest::Read, engine_context);
+            } else {
+                self.fetch_block(block, kind, access.pc, engine_context);
+            }
+        }
+    }
+
+    fn block_of(&self, address: usize) -> usize {
+        address & (self.offset_mask ^ usize::MAX)
+    }
+
+    fn mshr_for(&self, address: usize) -> Option<usize> {
+        let block = self.block_of(address);
+        self.mshrs.iter().position(|mshr| mshr.block == block)
+    }
+
+    fn allocate(
+        &mut self,
+        address: usize,
+        request: PendingRequest,
+        engine_context: &mut dyn EngineContext
+    ) -> usize {
+        let mut mshr = Mshr::new(self.block_of(address), request.kind(), request.pc());
+        mshr.targets.push((address, request));
+        self.mshrs.push(mshr);
+
+        engine_context.record_journal(JournalEvent::MshrAllocation { occupancy: self.mshrs.len() });
+
+        self.mshrs.len() - 1
+    }
+
+    // Writes a store into a present block. Atomic operations write the result of combining
+    // the block's bytes with their operand, and answer with the bytes they replaced
+    fn store(
+        &mut self,
+        address: usize,
+        data: Vec<u8>,
+        pc: Option<u64>,
+        atomic: Option<(AmoOp, Target)>,
+        delay: u64,
+        engine_context: &mut dyn EngineContext
+    ) {
+        let data = match atomic {
+            Some((op, requester)) => {
+                let Ok(CacheReturn::Hit(old)) = self.read(address, data.len()) else {
+                    panic!("atomic operation on a block that is not present");
+                };
+                let new = op.apply(&old, &data);
+
+                engine_context.schedule(delay, requester, EventPayload::MemoryLoadRes { address, data: old });
+                new
+            },
+            None => data,
+        };
+
+        self.write(address, data, pc, engine_context);
+    }
+
+    // Updates a present block, forwarding the store when writing through
+    fn write(
+        &mut self,
+        address: usize,
+        data: Vec<u8>,
+        pc: Option<u64>,
+        engine_context: &mut dyn EngineContext
+    ) {
+        self.update(address, data.clone()).expect("failed to update block");
+
+        if matches!(self.write_policy, CacheWritePolicy::WriteThrough) {
+            engine_context.record_journal(JournalEvent::CacheWriteThrough { bytes: data.len() });
+            engine_context.schedule(
+                self.latency.data,
+                Target::Module(self.backing_store_for(address)),
+                EventPayload::MemoryStoreReq { address, data, pc }
+            );
+        }
+    }
+
+    fn fetch_block(
+        &mut self,
+        address: usize,
+        kind: AccessKind,
+        pc: Option<u64>,
+        engine_context: &mut dyn EngineContext
+    ) {
+        let block_base_address = self.block_of(address);
+        let target = Target::Module(self.backing_store_for(block_base_address));
+        let size_in_bytes = self.block_size;
+
+        let payload = match kind {
+            AccessKind::Fetch => EventPayload::MemoryFetchReq {
+                address: block_base_address,
+                size_in_bytes,
+                requester: Target::Myself,
+                pc
+            },
+            _ => EventPayload::MemoryLoadReq {
+                address: block_base_address,
+                size_in_bytes,
+                requester: Target::Myself,
+                pc
+            },
+        };
+
+        engine_context.schedule(self.latency.tag + self.latency.miss_penalty, target, payload);
+    }
+
+    // Inserts a block brought from below, writing back whatever it evicts
+    fn fill(
+        &mut self,
+        address: usize,
+        data: Vec<u8>,
+        state: Option<CoherenceState>,
+        prefetch: bool,
+        engine_context: &mut dyn EngineContext
+    ) {
+        engine_context.record_journal(JournalEvent::CacheFill { bytes: data.len() });
+
+        let (block_idx, writeback, evicted) = self.place(address, data).expect("failed to insert block");
+
+        if let Some(evicted) = evicted {
+            engine_context.record_journal(JournalEvent::CacheEviction);
+
+            if let Some(Coherence { controller, reports_evictions: true, .. }) = self.coherence {
+                engine_context.schedule(
+                    1,
+                    Target::Module(controller),
+                    EventPayload::CoherenceEvict { address: evicted, requester: Target::Myself }
+                );
+            }
+        }
+
+        // The evicted block was prefetched for nothing
+        if self.prefetched[block_idx] {
+            engine_context.record_journal(JournalEvent::PrefetchUseless);
+        }
+        self.prefetched[block_idx] = prefetch;
+
+        if let Some((dirty_addr, dirty_bytes)) = writeback
+            && matches!(self.write_policy, CacheWritePolicy::WriteBack)
+        {
+            engine_context.record_journal(JournalEvent::CacheWriteback { bytes: dirty_bytes.len() });
+            engine_context.schedule(
+                self.latency.data,
+                Target::Module(self.backing_store_for(dirty_addr)),
+                EventPayload::MemoryStoreReq {
+                    address: dirty_addr,
+                    data: dirty_bytes,
+                    pc: None,
+                }
+            );
+        }
+
+        if let Some(state) = state {
+            // The evicted block leaves the cache before the new one arrives
+            self.set_state(block_idx, CoherenceState::Invalid, engine_context);
+            self.set_state(block_idx, state, engine_context);
+        }
+    }
+
+    // Frees an MSHR once its block is present, serving every request waiting on it
+    fn retire(&mut self, mshr_idx: usize, engine_context: &mut dyn EngineContext) {
+        let mshr = self.mshrs.remove(mshr_idx);
+        let mut targets = mshr.targets.into_iter();
+
+        while let Some((address, request)) = targets.next() {
+            match request {
+                PendingRequest::Load { requester, size, .. } => {
+                    if let Ok(CacheReturn::Hit(data)) = self.read(address, size) {
+                        engine_context.schedule(self.latency.data, requester, EventPayload::MemoryLoadRes { address, data });
+                    }
+                },
+                PendingRequest::Store { data, pc, atomic } => {
+                    if self.coherence.is_some() {
+                        let block_idx = self.position(address).expect("retired a block that is not present");
+
+                        match self.states[block_idx] {
+                            // Merged into a read that was granted a shared copy
+                            CoherenceState::Shared | CoherenceState::Owned => {
+                                let mut upgrade = Mshr::new(mshr.block, AccessKind::Store, pc);
+                                upgrade.targets.push((address, PendingRequest::Store { data, pc, atomic }));
+                                upgrade.targets.extend(targets.by_ref());
+
+                                self.mshrs.push(upgrade);
+
+                                let upgrade_idx = self.mshrs.len() - 1;
+                                self.request_coherence(upgrade_idx, CoherenceRequest::Upgrade, engine_context);
+                                break;
+                            },
+                            CoherenceState::Exclusive => {
+                                self.set_state(block_idx, CoherenceState::Modified, engine_context);
+                            },
+                            _ => ()
+                        }
+                    }
+
+                    self.store(address, data, pc, atomic, self.latency.data, engine_context);
+                }
+            }
+        }
+
+        while let Some(event) = self.blocked.pop_front() {
+            if !self.access(&event, engine_context) {
+                self.blocked.push_front(event);
+                break;
+            }
+        }
+    }
+
+    /// Connects this level to the module that keeps it coherent with its peers
+    pub fn set_coherence(&mut self, coherence: CoherenceConfig, controller: ModuleId) {
+        self.coherence = Some(Coherence {
+            protocol: coherence.protocol,
+            controller,
+            reports_evictions: matches!(coherence.mechanism, CoherenceMechanism::Directory { .. }),
+        });
+    }
+
+    fn request_coherence(
+        &mut self,
+        mshr_idx: usize,
+        request: CoherenceRequest,
+        engine_context: &mut dyn EngineContext
+    ) {
+        let coherence = self.coherence.expect("coherence request without a controller");
+        let mshr = &mut self.mshrs[mshr_idx];
+
+        mshr.coherence = Some((request, false));
+
+        engine_context.schedule(
+            self.latency.tag + self.latency.miss_penalty,
+            Target::Module(coherence.controller),
+            EventPayload::CoherenceReq {
+                address: mshr.block,
+                request,
+                requester: Target::Myself
+            }
+        );
+    }
+
+    fn release_coherence(&mut self, address: usize, engine_context: &mut dyn EngineContext) {
+        let coherence = self.coherence.expect("coherence release without a controller");
+
+        engine_context.schedule(
+            1,
+            Target::Module(coherence.controller),
+            EventPayload::CoherenceDone { address: self.block_of(address) }
+        );
+    }
+
+    fn snoop(
+        &mut self,
+        address: usize,
+        request: CoherenceRequest,
+        requester: Target,
+        engine_context: &mut dyn EngineContext
+    ) {
+        let coherence = self.coherence.expect("snooped a cache without coherence");
+
+        let (shared, data) = match self.position(address) {
+            Some(block_idx) => {
+                let outcome = snoop(coherence.protocol, self.states[block_idx], request);
+                let block = self.sets[block_idx / self.way].cache_lines[block_idx % self.way].bytes.clone();
+
+                if outcome.writes_back {
+                    engine_context.record_journal(JournalEvent::CacheWriteback { bytes: block.len() });
+                    engine_context.schedule(
+                        self.latency.data,
+                        Target::Module(self.backing_store_for(address)),
+                        EventPayload::MemoryStoreReq { address, data: block.clone(), pc: None }
+                    );
+                    self.dirty[block_idx] = false;
+                }
+
+                if outcome.supplies_data {
+                    engine_context.record_journal(JournalEvent::Intervention);
+                }
+
+                if outcome.next == CoherenceState::Invalid {
+                    self.valid[block_idx] = false;
+                    self.dirty[block_idx] = false;
+                    engine_context.record_journal(JournalEvent::Invalidation);
+
+                    if self.prefetched[block_idx] {
+                        self.prefetched[block_idx] = false;
+                        engine_context.record_journal(JournalEvent::PrefetchUseless);
+                    }
+                }
+
+                self.set_state(block_idx, outcome.next, engine_context);
+
+                (true, outcome.supplies_data.then_some(block))
+            },
+            None => (false, None),
+        };
+
+        engine_context.schedule(self.latency.tag, requester, EventPayload::CoherenceSnoopRes { address, shared, data });
+    }
+
+    fn set_state(
+        &mut self,
+        block_idx: usize,
+        state: CoherenceState,
+        engine_context: &mut dyn EngineContext
+    ) {
+        let from = self.states[block_idx];
+
+        if from != state {
+            self.states[block_idx] = state;
+            engine_context.record_journal(JournalEvent::CoherenceTransition { from, to: state });
+        }
+    }
+
+    // Index of the valid block holding `addr`, without touching the replacement policy
+    fn position(&self, addr: usize) -> Option<usize> {
+        let idx = ((addr & self.index_mask) >> self.index_start) % self.n_sets;
+        let tag = (addr & self.tag_mask) >> self.tag_start;
+
+        (0..self.way)
+            .map(|i| idx2to1!(idx, i, self.way))
+            .find(|block_idx| self.valid[*block_idx] && self.tags[*block_idx] == tag)
     }
 
     fn get_old (
@@ -460,6 +969,15 @@ impl CacheLevel {
         addr: usize,
         data: Vec<u8>
     ) -> Result<Option<(usize, Vec<u8>)>, CacheError> {
+        self.place(addr, data).map(|(_, writeback, _)| writeback)
+    }
+
+    // Same as insert, also returning where the block was placed and the address of the block it evicted
+    fn place(
+        &mut self, 
+        addr: usize,
+        data: Vec<u8>
+    ) -> Result<(usize, Option<Writeback>, Option<usize>), CacheError> {
         let tmp = (addr & self.index_mask) >> self.index_start;
         let idx = tmp % self.n_sets;
 
@@ -467,16 +985,18 @@ impl CacheLevel {
             Some(_) => {
                 let tag = (addr & self.tag_mask) >> self.tag_start;
 
-                let i = match self.sets[idx].policy_next() {
-                    Ok(sub) => sub,
-                    Err(err) => { println!("{:?}. Defaulting to 0", err); 0 }
-                };
+                // Empty ways are filled before anything gets evicted
+                let i = (0..self.way)
+                    .find(|i| !self.valid[idx2to1!(idx, i, self.way)])
+                    .unwrap_or_else(|| self.replacement.victim(idx));
 
                 let mut res: Option<(usize, Vec<u8>)> = None;
 
                 let block_idx = idx2to1!(idx, i, self.way);
+                let evicted = self.valid[block_idx]
+                    .then(|| (self.tags[block_idx] << self.tag_start) | (idx << self.index_start));
 
-                if self.valid[block_idx] {
+                if evicted.is_some() {
                     self.stats.evictions += 1;
 
                     if self.dirty[block_idx] {
@@ -487,11 +1007,12 @@ impl CacheLevel {
                 }
 
                 self.sets[idx].insert(data, i)?;
+                self.replacement.insert(idx, i);
 
                 self.tags[block_idx] = tag;
                 self.valid[block_idx] = true;
 
-                Ok(res)
+                Ok((block_idx, res, evicted))
             },
             _ => Err(CacheError::OutOfBounds)
         }
@@ -519,6 +1040,7 @@ impl CacheLevel {
                     match control_bits {
                         (Some(&true), Some(&value)) if value == tag => {
                             self.sets[idx].update(data, offset, i)?;
+                            self.replacement.touch(idx, i);
                             self.dirty[block_idx] = true;
                             return Ok(());
                         },
@@ -556,7 +1078,7 @@ impl CacheLevel {
                     let block = self.sets[idx].cache_lines[i].clone();
                     let slice = &block.bytes[offset..offset + bytes];
 
-                    self.sets[idx].policy_update(i, false)?;
+                    self.replacement.touch(idx, i);
                     return Ok(CacheReturn::Hit(slice.to_vec()))
                 },
                 (None, _) | (_, None) => return Err(CacheError::OutOfBounds),
@@ -589,7 +1111,7 @@ impl CacheLevel {
             match control_bits {
                 (Some(&true), Some(&value)) if value == tag => {
                     self.stats.hits += 1;
-                    self.sets[idx].policy_update(i, false)?;
+                    self.replacement.touch(idx, i);
                     return Ok(true);
                 },
                 (None, _) | (_, None) => return Err(CacheError::OutOfBounds),
@@ -618,7 +1140,10 @@ impl CacheLevel {
 #[cfg(test)]
 mod test {
     use super::*;
-    
+
+    use crate::test_util::RecordingContext;
+
+
     #[test]
     fn line_update_success() {
         let mut cache_line = CacheLine::new(4);
@@ -643,42 +1168,6 @@ mod test {
         ).unwrap();
     }
 
-    #[test]
-    fn set_lru() {
-        let mut cache_set = CacheSet::new(4, 4, CacheReplacementPolicy::LRU);
-        cache_set.update(vec![0], 0, 1).unwrap(); 
-        cache_set.update(vec![0], 0, 3).unwrap(); 
-        cache_set.update(vec![0], 0, 1).unwrap(); 
-        cache_set.update(vec![0], 0, 0).unwrap(); 
-        cache_set.update(vec![0], 0, 2).unwrap(); 
-        assert_eq!(cache_set.policy_next(), Ok(3usize));
-    }
-
-    #[test]
-    fn set_lfu() {
-        let mut cache_set = CacheSet::new(4, 4, CacheReplacementPolicy::LFU);
-        cache_set.update(vec![0], 0, 2).unwrap(); 
-        cache_set.update(vec![0], 0, 1).unwrap(); 
-        cache_set.update(vec![0], 0, 3).unwrap(); 
-        cache_set.update(vec![0], 0, 3).unwrap(); 
-        cache_set.update(vec![0], 0, 0).unwrap(); 
-        cache_set.update(vec![0], 0, 1).unwrap(); 
-        cache_set.update(vec![0], 0, 3).unwrap(); 
-        cache_set.update(vec![0], 0, 1).unwrap(); 
-        cache_set.update(vec![0], 0, 2).unwrap(); 
-        assert_eq!(cache_set.policy_next(), Ok(0usize));
-    }
-
-    #[test]
-    fn set_fifo() {
-        let mut cache_set = CacheSet::new(4, 4, CacheReplacementPolicy::FIFO);
-        cache_set.insert(vec![0], 1).unwrap(); 
-        cache_set.insert(vec![0], 3).unwrap(); 
-        cache_set.insert(vec![0], 0).unwrap(); 
-        cache_set.insert(vec![0], 2).unwrap(); 
-        assert_eq!(cache_set.policy_next(), Ok(1usize));
-    }
-
     #[test]
     fn level_lru_eviction() {
         let mut cache_level = 
@@ -700,4 +1189,204 @@ mod test {
 
         assert_eq!(cache_level.stats.evictions, 1);
     }
+
+    fn load(address: usize, requester: ModuleId) -> Event {
+        Event::new(0, 0, EventPayload::MemoryLoadReq {
+            address,
+            size_in_bytes: 4,
+            requester: Target::Module(requester),
+            pc: Some(0x100)
+        })
+    }
+
+    #[test]
+    fn mshr_merges_secondary_misses() {
+        let mut cache_level =
+            CacheLevel::new(64, 2, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack);
+        cache_level.set_mshrs(2);
+        cache_level.set_backing_store(9);
+
+        let mut ctx = RecordingContext::default();
+        cache_level.process_event(load(0x00, 1), &mut ctx);
+        cache_level.process_event(load(0x08, 2), &mut ctx);
+
+        // A single fetch goes down for both misses
+        assert_eq!(ctx.scheduled.len(), 1);
+        assert!(ctx.journal.iter().any(|event| matches!(event, JournalEvent::MshrMerge)));
+
+        ctx.scheduled.clear();
+        let fill = Event::new(0, 0, EventPayload::MemoryLoadRes { address: 0x00, data: vec![7; 64] });
+        cache_level.process_event(fill, &mut ctx);
+
+        let responses: Vec<_> = ctx.scheduled.into_iter().map(|(_, target, payload)| (target, payload)).collect();
+        assert_eq!(responses, vec![
+            (Target::Module(1), EventPayload::MemoryLoadRes { address: 0x00, data: vec![7; 4] }),
+            (Target::Module(2), EventPayload::MemoryLoadRes { address: 0x08, data: vec![7; 4] }),
+        ]);
+    }
+
+    #[test]
+    fn dirty_eviction_is_written_back() {
+        let mut cache_level =
+            CacheLevel::new(64, 2, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack);
+        cache_level.set_backing_store(9);
+
+        let mut ctx = RecordingContext::default();
+        let store = Event::new(0, 0, EventPayload::MemoryStoreReq { address: 0x00, data: vec![1; 4], pc: None });
+        cache_level.process_event(store, &mut ctx);
+
+        for address in [0x00, 0x40, 0x80] {
+            if address != 0x00 {
+                cache_level.process_event(load(address, 1), &mut ctx);
+            }
+            let fill = Event::new(0, 0, EventPayload::MemoryLoadRes { address, data: vec![0; 64] });
+            cache_level.process_event(fill, &mut ctx);
+        }
+
+        let count = |matches: fn(&JournalEvent) -> bool| ctx.journal.iter().filter(|event| matches(event)).count();
+        assert_eq!(count(|event| matches!(event, JournalEvent::CacheFill { bytes: 64 })), 3);
+        assert_eq!(count(|event| matches!(event, JournalEvent::CacheEviction)), 1);
+        assert_eq!(count(|event| matches!(event, JournalEvent::CacheWriteback { bytes: 64 })), 1);
+    }
+
+    #[test]
+    fn directory_hears_of_clean_evictions() {
+        let mut cache_level =
+            CacheLevel::new(64, 2, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack);
+        cache_level.set_coherence(CoherenceConfig {
+            protocol: CoherenceProtocol::Mesi,
+            mechanism: CoherenceMechanism::Directory { sharers: narvi_core::SharerFormat::FullBitVector }
+        }, 5);
+
+        let mut ctx = RecordingContext::default();
+
+        for address in [0x00, 0x40, 0x80] {
+            cache_level.process_event(load(address, 1), &mut ctx);
+            let grant = EventPayload::CoherenceGrant { address, shared: false, data: Some(vec![0; 64]) };
+            cache_level.process_event(Event::new(0, 0, grant), &mut ctx);
+        }
+
+        let evictions: Vec<_> = ctx.scheduled.iter()
+            .filter(|(_, _, payload)| matches!(payload, EventPayload::CoherenceEvict { .. }))
+            .collect();
+        assert_eq!(evictions, vec![
+            &(1, Target::Module(5), EventPayload::CoherenceEvict { address: 0x00, requester: Target::Myself })
+        ]);
+    }
+
+    #[test]
+    fn accesses_take_the_configured_latencies() {
+        let mut cache_level =
+            CacheLevel::new(64, 2, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack);
+        cache_level.set_backing_store(9);
+        cache_level.set_latency(CacheLatency { hit: 4, tag: 2, data: 3, miss_penalty: 5 });
+
+        let mut ctx = RecordingContext::default();
+        cache_level.process_event(load(0x00, 1), &mut ctx);
+        let fill = Event::new(0, 0, EventPayload::MemoryLoadRes { address: 0x00, data: vec![0; 64] });
+        cache_level.process_event(fill, &mut ctx);
+        cache_level.process_event(load(0x08, 1), &mut ctx);
+
+        // The miss goes down after the tags and the penalty, the block is answered once written, the hit right away
+        let delays: Vec<u64> = ctx.scheduled.iter().map(|(delay, _, _)| *delay).collect();
+        assert_eq!(delays, vec![7, 3, 4]);
+    }
+
+    #[test]
+    fn atomics_answer_the_bytes_they_replace() {
+        let mut cache_level =
+            CacheLevel::new(64, 2, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack);
+        cache_level.set_backing_store(9);
+
+        let mut ctx = RecordingContext::default();
+        let amo = EventPayload::MemoryAmoReq {
+            address: 0x04,
+            op: AmoOp::Add,
+            data: vec![1, 0, 0, 0],
+            requester: Target::Module(1),
+            pc: None
+        };
+        cache_level.process_event(Event::new(0, 0, amo), &mut ctx);
+        cache_level.process_event(Event::new(0, 0, EventPayload::MemoryLoadRes { address: 0x00, data: vec![5; 64] }), &mut ctx);
+        cache_level.process_event(load(0x04, 1), &mut ctx);
+
+        let answers: Vec<_> = ctx.scheduled.into_iter()
+            .filter_map(|(_, _, payload)| match payload {
+                EventPayload::MemoryLoadRes { data, .. } => Some(data),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(answers, vec![vec![5, 5, 5, 5], vec![6, 5, 5, 5]]);
+        assert!(ctx.journal.iter().any(|event| matches!(event, JournalEvent::CacheMiss { kind: AccessKind::Amo, .. })));
+    }
+
+    #[test]
+    fn misses_are_classified_on_request() {
+        let classes = |classify_misses: bool| {
+            let config = CacheLevelConfig {
+                classify_misses,
+                ..CacheLevelConfig::new(2, 64, 2, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack)
+            };
+            let mut cache_level = CacheLevel::from(&config);
+            cache_level.set_backing_store(9);
+
+            let mut ctx = RecordingContext::default();
+            cache_level.process_event(load(0x00, 1), &mut ctx);
+            ctx.journal.into_iter()
+                .filter_map(|event| match event {
+                    JournalEvent::CacheMiss { class, .. } => Some(class),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(classes(false), vec![None]);
+        assert_eq!(classes(true), vec![Some(MissClass::Compulsory)]);
+    }
+
+    #[test]
+    fn mshr_stalls_when_full() {
+        let mut cache_level =
+            CacheLevel::new(64, 2, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack);
+        cache_level.set_backing_store(9);
+
+        let mut ctx = RecordingContext::default();
+        cache_level.process_event(load(0x00, 1), &mut ctx);
+        cache_level.process_event(load(0x40, 2), &mut ctx);
+
+        assert_eq!(ctx.scheduled.len(), 1);
+        assert!(ctx.journal.iter().any(|event| matches!(event, JournalEvent::MshrStall)));
+
+        // Retiring the first miss lets the stalled one go down
+        let fill = Event::new(0, 0, EventPayload::MemoryLoadRes { address: 0x00, data: vec![0; 64] });
+        cache_level.process_event(fill, &mut ctx);
+
+        assert!(matches!(
+            ctx.scheduled.last(),
+            Some((_, Target::Module(9), EventPayload::MemoryLoadReq { address: 0x40, .. }))
+        ));
+    }
+
+    #[test]
+    fn prefetched_block_hit_is_useful() {
+        let mut cache_level =
+            CacheLevel::new(64, 4, 1, CacheReplacementPolicy::LRU, CacheWritePolicy::WriteBack);
+        cache_level.set_mshrs(2);
+        cache_level.set_backing_store(9);
+        cache_level.set_prefetcher(Box::new(prefetcher::NextLine::new(1)));
+
+        let mut ctx = RecordingContext::default();
+        cache_level.process_event(load(0x00, 1), &mut ctx);
+
+        assert!(matches!(
+            ctx.scheduled.last(),
+            Some((_, Target::Module(9), EventPayload::MemoryLoadReq { address: 0x40, pc: Some(0x100), .. }))
+        ));
+
+        let fill = Event::new(0, 0, EventPayload::MemoryLoadRes { address: 0x40, data: vec![0; 64] });
+        cache_level.process_event(fill, &mut ctx);
+        cache_level.process_event(load(0x48, 1), &mut ctx);
+
+        assert!(ctx.journal.iter().any(|event| matches!(event, JournalEvent::PrefetchUseful)));
+    }
 }
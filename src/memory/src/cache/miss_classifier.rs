@@ -0,0 +1,92 @@
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet
+};
+
+use narvi_core::event::MissClass;
+
+/// Sorts the misses of a cache level into the three Cs, by shadowing it with a fully
+/// associative LRU cache of the same number of blocks.
+///
+/// A miss on a block never accessed before is compulsory, one the shadow cache also
+/// takes is a capacity miss, and any other is a conflict miss. Blocks invalidated by
+/// other caches are still in the shadow cache, so coherence misses count as conflicts
+#[derive(Debug)]
+pub struct MissClassifier {
+    capacity: usize,
+    seen: HashSet<usize>,
+    // Last use of every block in the shadow cache, and the blocks by last use
+    last_use: HashMap<usize, u64>,
+    by_use: BTreeMap<u64, usize>,
+    clock: u64,
+}
+
+impl MissClassifier {
+    pub fn new(n_blocks: usize) -> Self {
+        Self {
+            capacity: n_blocks,
+            seen: HashSet::new(),
+            last_use: HashMap::new(),
+            by_use: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Records a demand access to `block`, returning its class if the cache missed
+    pub fn access(&mut self, block: usize, miss: bool) -> Option<MissClass> {
+        let first_touch = self.seen.insert(block);
+        let shadow_hit = self.touch(block);
+
+        miss.then_some(if first_touch {
+            MissClass::Compulsory
+        } else if shadow_hit {
+            MissClass::Conflict
+        } else {
+            MissClass::Capacity
+        })
+    }
+
+    // Accesses the shadow cache, returning whether it hit
+    fn touch(&mut self, block: usize) -> bool {
+        self.clock += 1;
+
+        let hit = match self.last_use.insert(block, self.clock) {
+            Some(previous) => {
+                self.by_use.remove(&previous);
+                true
+            },
+            None => false,
+        };
+
+        self.by_use.insert(self.clock, block);
+
+        if self.by_use.len() > self.capacity {
+            let (_, evicted) = self.by_use.pop_first().expect("full shadow cache without blocks");
+            self.last_use.remove(&evicted);
+        }
+
+        hit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classifies_the_three_cs() {
+        let mut classifier = MissClassifier::new(2);
+
+        assert_eq!(classifier.access(0x00, true), Some(MissClass::Compulsory));
+        assert_eq!(classifier.access(0x40, true), Some(MissClass::Compulsory));
+        assert_eq!(classifier.access(0x00, false), None);
+
+        // Both blocks fit in the shadow cache, so missing on them is a conflict
+        assert_eq!(classifier.access(0x40, true), Some(MissClass::Conflict));
+
+        // A third block pushes 0x00 out of the shadow cache too
+        assert_eq!(classifier.access(0x80, true), Some(MissClass::Compulsory));
+        assert_eq!(classifier.access(0x00, true), Some(MissClass::Capacity));
+    }
+}
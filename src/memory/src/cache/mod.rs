@@ -1,6 +1,29 @@
+mod belady;
 mod cache_level;
+mod miss_classifier;
+mod prefetcher;
+mod replacement;
 
+pub use belady::optimal_misses;
 pub use cache_level::CacheLevel;
+pub use miss_classifier::MissClassifier;
+pub use prefetcher::{
+    NextLine,
+    PrefetchAccess,
+    Prefetcher,
+    Stream,
+    Stride,
+};
+pub use replacement::{
+    Fifo,
+    Lfu,
+    Lru,
+    Nru,
+    Random,
+    ReplacementPolicy,
+    Rrip,
+    TreePlru,
+};
 
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug
+};
+
+use narvi_core::{
+    PrefetcherConfig,
+    event::AccessKind
+};
+
+/// Demand access seen by a cache level
+#[derive(Debug, Clone, Copy)]
+pub struct PrefetchAccess {
+    pub address: usize,
+    pub pc: Option<u64>,
+    pub kind: AccessKind,
+    pub miss: bool,
+}
+
+/// Watches the demand accesses of a cache level and picks blocks to bring in ahead of time
+pub trait Prefetcher: Debug {
+    /// Returns the addresses of the blocks worth prefetching after `access`.
+    /// Blocks already present or on their way are filtered out by the cache
+    fn observe(&mut self, access: &PrefetchAccess, block_size: usize) -> Vec<usize>;
+}
+
+pub fn from_config(config: &PrefetcherConfig) -> Box<dyn Prefetcher> {
+    match *config {
+        PrefetcherConfig::NextLine { degree } => Box::new(NextLine::new(degree)),
+        PrefetcherConfig::Stride { table_size, degree } => Box::new(Stride::new(table_size, degree)),
+        PrefetcherConfig::Stream { streams, distance, degree } => Box::new(Stream::new(streams, distance, degree)),
+    }
+}
+
+/// Prefetches the `degree` blocks following every miss
+#[derive(Debug)]
+pub struct NextLine {
+    degree: usize,
+}
+
+impl NextLine {
+    pub fn new(degree: usize) -> Self {
+        Self { degree }
+    }
+}
+
+impl Prefetcher for NextLine {
+    fn observe(&mut self, access: &PrefetchAccess, block_size: usize) -> Vec<usize> {
+        if !access.miss {
+            return Vec::new();
+        }
+
+        let block = access.address - access.address % block_size;
+
+        (1..=self.degree)
+            .map(|i| block + i * block_size)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct StrideEntry {
+    last_address: usize,
+    stride: isize,
+    confidence: u8,
+}
+
+/// Learns the stride between consecutive accesses of each load or store instruction,
+/// and prefetches along it once the same stride was seen twice in a row
+#[derive(Debug)]
+pub struct Stride {
+    table: HashMap<u64, StrideEntry>,
+    // Insertion order, to replace the oldest entry once the table is full
+    order: Vec<u64>,
+    table_size: usize,
+    degree: usize,
+}
+
+impl Stride {
+    pub fn new(table_size: usize, degree: usize) -> Self {
+        Self {
+            table: HashMap::new(),
+            order: Vec::new(),
+            table_size,
+            degree,
+        }
+    }
+}
+
+impl Prefetcher for Stride {
+    fn observe(&mut self, access: &PrefetchAccess, block_size: usize) -> Vec<usize> {
+        let Some(pc) = access.pc else {
+            return Vec::new();
+        };
+
+        let Some(entry) = self.table.get_mut(&pc) else {
+            if self.order.len() == self.table_size {
+                let oldest = self.order.remove(0);
+                self.table.remove(&oldest);
+            }
+
+            self.order.push(pc);
+            self.table.insert(pc, StrideEntry { last_address: access.address, stride: 0, confidence: 0 });
+            return Vec::new();
+        };
+
+        let stride = access.address as isize - entry.last_address as isize;
+        entry.last_address = access.address;
+
+        if stride == entry.stride && stride != 0 {
+            entry.confidence = entry.confidence.saturating_add(1);
+        } else {
+            entry.stride = stride;
+            entry.confidence = 0;
+        }
+
+        if entry.confidence == 0 {
+            return Vec::new();
+        }
+
+        let mut blocks: Vec<usize> = (1..=self.degree as isize)
+            .filter_map(|i| access.address.checked_add_signed(i * stride))
+            .map(|address| address - address % block_size)
+            .collect();
+        blocks.dedup();
+        blocks
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct StreamEntry {
+    last_block: usize,
+    direction: isize,
+    confidence: u8,
+}
+
+/// Detects misses walking through consecutive blocks, in either direction,
+/// and runs `distance` blocks ahead of each confirmed stream
+#[derive(Debug)]
+pub struct Stream {
+    streams: Vec<StreamEntry>,
+    max_streams: usize,
+    distance: usize,
+    degree: usize,
+}
+
+impl Stream {
+    pub fn new(max_streams: usize, distance: usize, degree: usize) -> Self {
+        Self {
+            streams: Vec::new(),
+            max_streams,
+            distance,
+            degree,
+        }
+    }
+}
+
+impl Prefetcher for Stream {
+    fn observe(&mut self, access: &PrefetchAccess, block_size: usize) -> Vec<usize> {
+        if !access.miss {
+            return Vec::new();
+        }
+
+        let block = (access.address / block_size) as isize;
+
+        // A miss close to the end of a stream extends it
+        let found = self.streams.iter().position(|stream| {
+            let step = block - stream.last_block as isize;
+            step != 0 && step.unsigned_abs() <= self.distance
+                && (stream.direction == 0 || step.signum() == stream.direction)
+        });
+
+        let Some(idx) = found else {
+            if self.streams.len() == self.max_streams {
+                self.streams.remove(0);
+            }
+
+            self.streams.push(StreamEntry { last_block: block as usize, direction: 0, confidence: 0 });
+            return Vec::new();
+        };
+
+        // Most recently used streams are kept at the end
+        let mut stream = self.streams.remove(idx);
+        stream.direction = (block - stream.last_block as isize).signum();
+        stream.last_block = block as usize;
+        stream.confidence = stream.confidence.saturating_add(1);
+        self.streams.push(stream);
+
+        if stream.confidence < 2 {
+            return Vec::new();
+        }
+
+        (1..=self.degree)
+            .map(|i| block + stream.direction * (self.distance + i - 1) as isize)
+            .filter(|target| *target >= 0)
+            .map(|target| target as usize * block_size)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn miss(address: usize, pc: u64) -> PrefetchAccess {
+        PrefetchAccess { address, pc: Some(pc), kind: AccessKind::Load, miss: true }
+    }
+
+    #[test]
+    fn next_line_follows_misses() {
+        let mut prefetcher = NextLine::new(2);
+        assert_eq!(prefetcher.observe(&miss(0x48, 0), 64), vec![0x80, 0xC0]);
+    }
+
+    #[test]
+    fn stride_needs_two_matching_strides() {
+        let mut prefetcher = Stride::new(4, 1);
+        assert!(prefetcher.observe(&miss(0x000, 8), 64).is_empty());
+        assert!(prefetcher.observe(&miss(0x100, 8), 64).is_empty());
+        assert_eq!(prefetcher.observe(&miss(0x200, 8), 64), vec![0x300]);
+
+        // Other instructions keep their own history
+        assert!(prefetcher.observe(&miss(0x208, 12), 64).is_empty());
+    }
+
+    #[test]
+    fn stream_runs_ahead_of_descending_misses() {
+        let mut prefetcher = Stream::new(2, 2, 1);
+        assert!(prefetcher.observe(&miss(0x400, 0), 64).is_empty());
+        assert!(prefetcher.observe(&miss(0x3C0, 0), 64).is_empty());
+        assert_eq!(prefetcher.observe(&miss(0x380, 0), 64), vec![0x300]);
+    }
+}
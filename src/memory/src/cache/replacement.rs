@@ -0,0 +1,395 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug
+};
+
+use narvi_core::CacheReplacementPolicy;
+
+use rand::random_range;
+
+/// Picks which block of a set to evict. Each policy keeps whatever metadata it needs
+/// for every set of the level, and is told about every fill and hit.
+///
+/// Invalid ways are always filled first, so `victim` is only asked once a set is full
+pub trait ReplacementPolicy: Debug {
+    fn victim(&mut self, set: usize) -> usize;
+
+    /// A new block was placed in `way`
+    fn insert(&mut self, set: usize, way: usize);
+
+    /// The block in `way` was hit
+    fn touch(&mut self, set: usize, way: usize);
+}
+
+pub fn from_config(policy: CacheReplacementPolicy, n_sets: usize, way: usize) -> Box<dyn ReplacementPolicy> {
+    match policy {
+        CacheReplacementPolicy::LRU => Box::new(Lru::new(n_sets, way)),
+        CacheReplacementPolicy::LFU => Box::new(Lfu::new(n_sets, way)),
+        CacheReplacementPolicy::FIFO => Box::new(Fifo::new(n_sets)),
+        CacheReplacementPolicy::Random => Box::new(Random::new(way)),
+        CacheReplacementPolicy::TreePLRU => Box::new(TreePlru::new(n_sets, way)),
+        CacheReplacementPolicy::NRU => Box::new(Nru::new(n_sets, way)),
+        CacheReplacementPolicy::SRRIP => Box::new(Rrip::srrip(n_sets, way)),
+        CacheReplacementPolicy::BRRIP => Box::new(Rrip::brrip(n_sets, way)),
+        CacheReplacementPolicy::DRRIP => Box::new(Rrip::drrip(n_sets, way)),
+    }
+}
+
+#[derive(Debug)]
+pub struct Lru {
+    // Ways of each set, most recently used first
+    order: Vec<Vec<usize>>,
+}
+
+impl Lru {
+    pub fn new(n_sets: usize, way: usize) -> Self {
+        Self { order: vec![(0..way).rev().collect(); n_sets] }
+    }
+}
+
+impl ReplacementPolicy for Lru {
+    fn victim(&mut self, set: usize) -> usize {
+        *self.order[set].last().expect("set without ways")
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        self.touch(set, way);
+    }
+
+    fn touch(&mut self, set: usize, way: usize) {
+        let order = &mut self.order[set];
+        let pos = order.iter().position(|w| *w == way).expect("touched a way outside the set");
+        order.remove(pos);
+        order.insert(0, way);
+    }
+}
+
+#[derive(Debug)]
+pub struct Lfu {
+    counts: Vec<Vec<usize>>,
+}
+
+impl Lfu {
+    pub fn new(n_sets: usize, way: usize) -> Self {
+        Self { counts: vec![vec![0; way]; n_sets] }
+    }
+}
+
+impl ReplacementPolicy for Lfu {
+    fn victim(&mut self, set: usize) -> usize {
+        self.counts[set].iter()
+            .enumerate()
+            .min_by_key(|(_, count)| **count)
+            .map(|(way, _)| way)
+            .expect("set without ways")
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        // Reset on new block
+        self.counts[set][way] = 1;
+    }
+
+    fn touch(&mut self, set: usize, way: usize) {
+        self.counts[set][way] += 1;
+    }
+}
+
+#[derive(Debug)]
+pub struct Fifo {
+    // Ways of each set, oldest insertion first
+    queues: Vec<VecDeque<usize>>,
+}
+
+impl Fifo {
+    pub fn new(n_sets: usize) -> Self {
+        Self { queues: vec![VecDeque::new(); n_sets] }
+    }
+}
+
+impl ReplacementPolicy for Fifo {
+    fn victim(&mut self, set: usize) -> usize {
+        self.queues[set].front().copied().unwrap_or(0)
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        let queue = &mut self.queues[set];
+        queue.retain(|w| *w != way);
+        queue.push_back(way);
+    }
+
+    fn touch(&mut self, _: usize, _: usize) {}
+}
+
+#[derive(Debug)]
+pub struct Random {
+    way: usize,
+}
+
+impl Random {
+    pub fn new(way: usize) -> Self {
+        Self { way }
+    }
+}
+
+impl ReplacementPolicy for Random {
+    fn victim(&mut self, _: usize) -> usize {
+        random_range(0..self.way)
+    }
+
+    fn insert(&mut self, _: usize, _: usize) {}
+
+    fn touch(&mut self, _: usize, _: usize) {}
+}
+
+/// Binary tree of `way - 1` bits per set, each one pointing to the half of its subtree
+/// to evict from. Needs a power of two ways
+#[derive(Debug)]
+pub struct TreePlru {
+    trees: Vec<Vec<bool>>,
+    way: usize,
+}
+
+impl TreePlru {
+    pub fn new(n_sets: usize, way: usize) -> Self {
+        assert!(way.is_power_of_two(), "tree PLRU needs a power of two ways");
+        Self { trees: vec![vec![false; way - 1]; n_sets], way }
+    }
+}
+
+impl ReplacementPolicy for TreePlru {
+    fn victim(&mut self, set: usize) -> usize {
+        let tree = &self.trees[set];
+        let mut node = 0;
+
+        // Nodes are stored heap-like: the children of `n` are `2n + 1` and `2n + 2`
+        while node < self.way - 1 {
+            node = 2 * node + 1 + tree[node] as usize;
+        }
+
+        node - (self.way - 1)
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        self.touch(set, way);
+    }
+
+    fn touch(&mut self, set: usize, way: usize) {
+        let tree = &mut self.trees[set];
+        let mut node = way + self.way - 1;
+
+        // Every node on the path points away from the way just used
+        while node > 0 {
+            let parent = (node - 1) / 2;
+            tree[parent] = node == 2 * parent + 1;
+            node = parent;
+        }
+    }
+}
+
+/// Not recently used: one reference bit per block, all cleared once every block of the set is referenced
+#[derive(Debug)]
+pub struct Nru {
+    referenced: Vec<Vec<bool>>,
+}
+
+impl Nru {
+    pub fn new(n_sets: usize, way: usize) -> Self {
+        Self { referenced: vec![vec![false; way]; n_sets] }
+    }
+}
+
+impl ReplacementPolicy for Nru {
+    fn victim(&mut self, set: usize) -> usize {
+        self.referenced[set].iter()
+            .position(|referenced| !referenced)
+            .unwrap_or(0)
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        self.touch(set, way);
+    }
+
+    fn touch(&mut self, set: usize, way: usize) {
+        let bits = &mut self.referenced[set];
+        bits[way] = true;
+
+        if bits.iter().all(|referenced| *referenced) {
+            bits.fill(false);
+            bits[way] = true;
+        }
+    }
+}
+
+// Re-reference prediction values are 2 bits wide
+const RRPV_MAX: u8 = 3;
+// Bimodal insertion places one block in 32 at a long re-reference interval instead of a distant one
+const BIMODAL_THROTTLE: usize = 32;
+// Set dueling dedicates one set in 32 to each of SRRIP and BRRIP
+const DUELING_PERIOD: usize = 32;
+const PSEL_MAX: u16 = 1023;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RripInsertion {
+    Static,
+    Bimodal,
+    /// Set dueling between static and bimodal insertion
+    Dynamic,
+}
+
+/// Re-reference interval prediction (Jaleel et al., ISCA 2010)
+#[derive(Debug)]
+pub struct Rrip {
+    rrpv: Vec<Vec<u8>>,
+    insertion: RripInsertion,
+    // Goes up on misses in static leader sets and down on misses in bimodal ones
+    psel: u16,
+}
+
+impl Rrip {
+    fn new(n_sets: usize, way: usize, insertion: RripInsertion) -> Self {
+        Self {
+            rrpv: vec![vec![RRPV_MAX; way]; n_sets],
+            insertion,
+            psel: PSEL_MAX / 2,
+        }
+    }
+
+    pub fn srrip(n_sets: usize, way: usize) -> Self {
+        Self::new(n_sets, way, RripInsertion::Static)
+    }
+
+    pub fn brrip(n_sets: usize, way: usize) -> Self {
+        Self::new(n_sets, way, RripInsertion::Bimodal)
+    }
+
+    pub fn drrip(n_sets: usize, way: usize) -> Self {
+        Self::new(n_sets, way, RripInsertion::Dynamic)
+    }
+
+    fn insertion_for(&mut self, set: usize) -> RripInsertion {
+        if self.insertion != RripInsertion::Dynamic {
+            return self.insertion;
+        }
+
+        match set % DUELING_PERIOD {
+            0 => {
+                self.psel = (self.psel + 1).min(PSEL_MAX);
+                RripInsertion::Static
+            },
+            1 => {
+                self.psel = self.psel.saturating_sub(1);
+                RripInsertion::Bimodal
+            },
+            // Followers use whichever leader misses less
+            _ if self.psel > PSEL_MAX / 2 => RripInsertion::Bimodal,
+            _ => RripInsertion::Static,
+        }
+    }
+}
+
+impl ReplacementPolicy for Rrip {
+    fn victim(&mut self, set: usize) -> usize {
+        let rrpv = &mut self.rrpv[set];
+
+        loop {
+            if let Some(way) = rrpv.iter().position(|value| *value == RRPV_MAX) {
+                return way;
+            }
+
+            rrpv.iter_mut().for_each(|value| *value += 1);
+        }
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        self.rrpv[set][way] = match self.insertion_for(set) {
+            RripInsertion::Bimodal if random_range(0..BIMODAL_THROTTLE) != 0 => RRPV_MAX,
+            _ => RRPV_MAX - 1,
+        };
+    }
+
+    fn touch(&mut self, set: usize, way: usize) {
+        self.rrpv[set][way] = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn set_lru() {
+        let mut policy = Lru::new(1, 4);
+        for way in [1, 3, 1, 0, 2] {
+            policy.touch(0, way);
+        }
+        assert_eq!(policy.victim(0), 3);
+    }
+
+    #[test]
+    fn set_lfu() {
+        let mut policy = Lfu::new(1, 4);
+        for way in [2, 1, 3, 3, 0, 1, 3, 1, 2] {
+            policy.touch(0, way);
+        }
+        assert_eq!(policy.victim(0), 0);
+    }
+
+    #[test]
+    fn set_fifo() {
+        let mut policy = Fifo::new(1);
+        for way in [1, 3, 0, 2] {
+            policy.insert(0, way);
+        }
+        assert_eq!(policy.victim(0), 1);
+    }
+
+    #[test]
+    fn tree_plru_points_away_from_recent_ways() {
+        let mut policy = TreePlru::new(1, 4);
+        for way in [0, 1, 2, 3] {
+            policy.insert(0, way);
+        }
+        assert_eq!(policy.victim(0), 0);
+
+        policy.touch(0, 0);
+        assert_eq!(policy.victim(0), 2);
+    }
+
+    #[test]
+    fn nru_clears_bits_once_all_referenced() {
+        let mut policy = Nru::new(1, 2);
+        policy.insert(0, 0);
+        assert_eq!(policy.victim(0), 1);
+
+        policy.insert(0, 1);
+        assert_eq!(policy.victim(0), 0);
+    }
+
+    #[test]
+    fn srrip_evicts_blocks_never_hit() {
+        let mut policy = Rrip::srrip(1, 4);
+        for way in 0..4 {
+            policy.insert(0, way);
+        }
+        policy.touch(0, 0);
+        policy.touch(0, 2);
+
+        assert_eq!(policy.victim(0), 1);
+    }
+
+    #[test]
+    fn drrip_followers_take_the_better_leader() {
+        let mut policy = Rrip::drrip(64, 2);
+
+        // Misses in the static leader set push followers towards bimodal insertion
+        for _ in 0..PSEL_MAX {
+            policy.insert(0, 0);
+        }
+        assert_eq!(policy.insertion_for(2), RripInsertion::Bimodal);
+
+        for _ in 0..PSEL_MAX {
+            policy.insert(1, 0);
+        }
+        assert_eq!(policy.insertion_for(2), RripInsertion::Static);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+
+use super::Transaction;
+
+use narvi_core::{
+    EngineContext,
+    Module,
+    ModuleId,
+    event::{
+        CoherenceRequest,
+        Event,
+        EventPayload,
+        JournalEvent,
+        Target,
+    }
+};
+
+/// Snooping bus shared by the instances of a cache level.
+///
+/// Transactions are serialized: a request is broadcast to every other cache on the bus,
+/// and the next one only starts after the requester reports it is done with the block
+#[derive(Debug)]
+pub struct SnoopBus {
+    caches: Vec<ModuleId>,
+    queue: VecDeque<(usize, CoherenceRequest, ModuleId)>,
+    current: Option<Transaction>,
+}
+
+impl Module for SnoopBus {
+    fn process_event(&mut self, event: Event, engine_context: &mut dyn EngineContext) {
+        match event.payload() {
+            EventPayload::CoherenceReq { address, request, requester } => {
+                let Target::Module(requester) = *requester else {
+                    panic!("coherence request without a requester");
+                };
+
+                self.queue.push_back((*address, *request, requester));
+
+                if self.current.is_none() {
+                    self.start_next(engine_context);
+                }
+            },
+            EventPayload::CoherenceSnoopRes { address, shared, data } => {
+                let transaction = self.current.as_mut()
+                    .expect("received snoop response without a transaction");
+
+                if transaction.respond(*address, *shared, data.clone()) {
+                    self.grant(engine_context);
+                }
+            },
+            EventPayload::CoherenceDone { .. } => {
+                self.current = None;
+                self.start_next(engine_context);
+            },
+            EventPayload::Reset => {},
+            _ => panic!("cannot process {event}")
+        }
+    }
+}
+
+impl SnoopBus {
+    pub fn new(caches: Vec<ModuleId>) -> Self {
+        Self {
+            caches,
+            queue: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    fn start_next(&mut self, engine_context: &mut dyn EngineContext) {
+        let Some((address, request, requester)) = self.queue.pop_front() else {
+            return;
+        };
+
+        engine_context.record_journal(JournalEvent::CoherenceTransaction { request });
+
+        let mut pending_responses = 0;
+
+        for cache in self.caches.iter().filter(|id| **id != requester) {
+            engine_context.schedule(
+                1,
+                Target::Module(*cache),
+                EventPayload::CoherenceSnoop { address, request, requester: Target::Myself }
+            );
+            pending_responses += 1;
+        }
+
+        self.current = Some(Transaction::new(address, request, requester, pending_responses));
+
+        if pending_responses == 0 {
+            self.grant(engine_context);
+        }
+    }
+
+    fn grant(&mut self, engine_context: &mut dyn EngineContext) {
+        self.current.as_mut()
+            .expect("cannot grant without a transaction")
+            .grant(1, engine_context);
+    }
+}
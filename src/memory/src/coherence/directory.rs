@@ -0,0 +1,422 @@
+use std::collections::{
+    HashMap,
+    VecDeque
+};
+
+use super::Transaction;
+
+use narvi_core::{
+    CoherenceProtocol,
+    EngineContext,
+    Module,
+    ModuleId,
+    SharerFormat,
+    event::{
+        CoherenceRequest,
+        Event,
+        EventPayload,
+        JournalEvent,
+        Target,
+    }
+};
+
+/// Caches that may hold a block, as indices into the directory's caches
+#[derive(Debug, Clone)]
+enum Sharers {
+    FullBitVector(Vec<bool>),
+    LimitedPointer { pointers: Vec<usize>, capacity: usize, overflowed: bool },
+}
+
+impl Sharers {
+    fn new(format: SharerFormat, n_caches: usize) -> Self {
+        match format {
+            SharerFormat::FullBitVector => Self::FullBitVector(vec![false; n_caches]),
+            SharerFormat::LimitedPointer { pointers } => Self::LimitedPointer {
+                pointers: Vec::new(),
+                capacity: pointers as usize,
+                overflowed: false
+            },
+        }
+    }
+
+    fn add(&mut self, cache: usize) {
+        match self {
+            Self::FullBitVector(bits) => bits[cache] = true,
+            Self::LimitedPointer { pointers, capacity, overflowed } => {
+                if pointers.contains(&cache) {
+                    return;
+                }
+
+                if pointers.len() < *capacity {
+                    pointers.push(cache);
+                } else {
+                    *overflowed = true;
+                }
+            }
+        }
+    }
+
+    fn set_only(&mut self, cache: usize) {
+        match self {
+            Self::FullBitVector(bits) => {
+                bits.fill(false);
+                bits[cache] = true;
+            },
+            Self::LimitedPointer { pointers, overflowed, .. } => {
+                pointers.clear();
+                pointers.push(cache);
+                *overflowed = false;
+            }
+        }
+    }
+
+    /// Forgets `cache`. Overflowed pointers cannot tell which caches they lost track of,
+    /// so they keep broadcasting until the block is next held exclusively
+    fn remove(&mut self, cache: usize) {
+        match self {
+            Self::FullBitVector(bits) => bits[cache] = false,
+            Self::LimitedPointer { pointers, .. } => pointers.retain(|pointer| *pointer != cache),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::FullBitVector(bits) => !bits.contains(&true),
+            Self::LimitedPointer { pointers, overflowed, .. } => pointers.is_empty() && !overflowed,
+        }
+    }
+
+    /// Whether `cache` may hold the block, which is always the case once the pointers have overflowed
+    fn contains(&self, cache: usize) -> bool {
+        match self {
+            Self::FullBitVector(bits) => bits[cache],
+            Self::LimitedPointer { pointers, overflowed, .. } => *overflowed || pointers.contains(&cache),
+        }
+    }
+
+    /// Caches to invalidate, every cache once the pointers have overflowed
+    fn targets(&self, n_caches: usize) -> Vec<usize> {
+        match self {
+            Self::FullBitVector(bits) => (0..n_caches).filter(|cache| bits[*cache]).collect(),
+            Self::LimitedPointer { overflowed: true, .. } => (0..n_caches).collect(),
+            Self::LimitedPointer { pointers, .. } => pointers.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    // Cache holding the block in M, O or E, which must answer reads
+    owner: Option<usize>,
+    sharers: Sharers,
+}
+
+/// Directory tracking which caches of a level hold each block.
+///
+/// Instead of broadcasting, requests are forwarded point to point to the owner or the
+/// sharers recorded for the block. The directory sits at the home node of the blocks, so
+/// every lookup takes `lookup_latency` cycles before the snoops or the grant go out. Caches
+/// report every eviction, dirty or not, so that sharers only drift from the truth while
+/// such a report is in flight
+#[derive(Debug)]
+pub struct Directory {
+    caches: Vec<ModuleId>,
+    protocol: CoherenceProtocol,
+    format: SharerFormat,
+    lookup_latency: u64,
+    entries: HashMap<usize, Entry>,
+    queue: VecDeque<(usize, CoherenceRequest, ModuleId)>,
+    current: Option<Transaction>,
+}
+
+impl Module for Directory {
+    fn process_event(&mut self, event: Event, engine_context: &mut dyn EngineContext) {
+        match event.payload() {
+            EventPayload::CoherenceReq { address, request, requester } => {
+                let Target::Module(requester) = *requester else {
+                    panic!("coherence request without a requester");
+                };
+
+                self.queue.push_back((*address, *request, requester));
+
+                if self.current.is_none() {
+                    self.start_next(engine_context);
+                }
+            },
+            EventPayload::CoherenceSnoopRes { address, shared, data } => {
+                let transaction = self.current.as_mut()
+                    .expect("received snoop response without a transaction");
+
+                if transaction.respond(*address, *shared, data.clone()) {
+                    self.grant(1, engine_context);
+                }
+            },
+            EventPayload::CoherenceDone { .. } => {
+                self.current = None;
+                self.start_next(engine_context);
+            },
+            EventPayload::CoherenceEvict { address, requester } => {
+                let Target::Module(requester) = *requester else {
+                    panic!("eviction report without a requester");
+                };
+
+                self.evict(*address, requester);
+            },
+            EventPayload::Reset => {},
+            _ => panic!("cannot process {event}")
+        }
+    }
+}
+
+impl Directory {
+    pub fn new(caches: Vec<ModuleId>, protocol: CoherenceProtocol, format: SharerFormat, lookup_latency: u64) -> Self {
+        Self {
+            caches,
+            protocol,
+            format,
+            lookup_latency,
+            entries: HashMap::new(),
+            queue: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    fn start_next(&mut self, engine_context: &mut dyn EngineContext) {
+        let Some((address, request, requester)) = self.queue.pop_front() else {
+            return;
+        };
+
+        engine_context.record_journal(JournalEvent::CoherenceTransaction { request });
+
+        let n_caches = self.caches.len();
+        let requester_idx = self.index_of(requester);
+        let entry = self.entries.entry(address).or_insert_with(|| Entry {
+            owner: None,
+            sharers: Sharers::new(self.format, n_caches),
+        });
+
+        // An upgrade from a cache that lost its copy to another writer while it waited
+        // has to fetch the block again, and is served as the ReadExclusive it has become
+        let request = match request {
+            CoherenceRequest::Upgrade if entry.owner != Some(requester_idx) && !entry.sharers.contains(requester_idx) => {
+                CoherenceRequest::ReadExclusive
+            },
+            request => request,
+        };
+
+        let others: Vec<usize> = entry.sharers.targets(n_caches).into_iter()
+            .chain(entry.owner)
+            .filter(|cache| *cache != requester_idx)
+            .collect();
+
+        // Reads only need the owner to give up exclusivity, writes invalidate every copy
+        let mut targets = match request {
+            CoherenceRequest::Read => entry.owner.filter(|owner| *owner != requester_idx).into_iter().collect(),
+            CoherenceRequest::ReadExclusive | CoherenceRequest::Upgrade => others.clone(),
+        };
+        targets.sort_unstable();
+        targets.dedup();
+
+        for cache in &targets {
+            engine_context.schedule(
+                self.lookup_latency,
+                Target::Module(self.caches[*cache]),
+                EventPayload::CoherenceSnoop { address, request, requester: Target::Myself }
+            );
+        }
+
+        let mut transaction = Transaction::new(address, request, requester, targets.len());
+        transaction.shared = !others.is_empty();
+        self.current = Some(transaction);
+
+        if targets.is_empty() {
+            self.grant(self.lookup_latency, engine_context);
+        }
+    }
+
+    fn grant(&mut self, delay: u64, engine_context: &mut dyn EngineContext) {
+        let requester_idx = {
+            let transaction = self.current.as_ref()
+                .expect("cannot grant without a transaction");
+            self.index_of(transaction.requester)
+        };
+
+        let transaction = self.current.as_mut()
+            .expect("cannot grant without a transaction");
+        // Evictions reported while the snoops were out may have dropped the entry
+        let n_caches = self.caches.len();
+        let entry = self.entries.entry(transaction.address).or_insert_with(|| Entry {
+            owner: None,
+            sharers: Sharers::new(self.format, n_caches),
+        });
+
+        match transaction.request {
+            CoherenceRequest::Read => {
+                // Only a MOESI owner that supplied the block keeps it
+                let keeps_ownership = transaction.data.is_some() && self.protocol == CoherenceProtocol::Moesi;
+
+                if !keeps_ownership {
+                    entry.owner = None;
+                }
+
+                if transaction.shared {
+                    entry.sharers.add(requester_idx);
+                } else {
+                    entry.owner = Some(requester_idx);
+                    entry.sharers.set_only(requester_idx);
+                }
+            },
+            CoherenceRequest::ReadExclusive | CoherenceRequest::Upgrade => {
+                entry.owner = Some(requester_idx);
+                entry.sharers.set_only(requester_idx);
+            }
+        }
+
+        transaction.grant(delay, engine_context);
+    }
+
+    fn evict(&mut self, address: usize, cache: ModuleId) {
+        let cache = self.index_of(cache);
+
+        let Some(entry) = self.entries.get_mut(&address) else {
+            return;
+        };
+
+        entry.sharers.remove(cache);
+        if entry.owner == Some(cache) {
+            entry.owner = None;
+        }
+
+        // Entries of blocks nobody holds are dropped, so the directory only grows with the cached blocks
+        if entry.owner.is_none() && entry.sharers.is_empty() {
+            self.entries.remove(&address);
+        }
+    }
+
+    fn index_of(&self, cache: ModuleId) -> usize {
+        self.caches.iter()
+            .position(|id| *id == cache)
+            .expect("request from a cache outside the directory")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_util::RecordingContext;
+
+    const A: ModuleId = 10;
+    const B: ModuleId = 11;
+
+    fn request(directory: &mut Directory, cache: ModuleId, request: CoherenceRequest, ctx: &mut RecordingContext) {
+        let requester = Target::Module(cache);
+        directory.process_event(Event::new(0, 0, EventPayload::CoherenceReq { address: 0x40, request, requester }), ctx);
+    }
+
+    fn done(directory: &mut Directory, ctx: &mut RecordingContext) {
+        directory.process_event(Event::new(0, 0, EventPayload::CoherenceDone { address: 0x40 }), ctx);
+    }
+
+    #[test]
+    fn lookups_take_the_home_node_latency() {
+        let mut directory = Directory::new(vec![A, B], CoherenceProtocol::Mesi, SharerFormat::FullBitVector, 30);
+        let mut ctx = RecordingContext::default();
+
+        request(&mut directory, A, CoherenceRequest::Read, &mut ctx);
+        assert_eq!(ctx.scheduled.pop(), Some((
+            30,
+            Target::Module(A),
+            EventPayload::CoherenceGrant { address: 0x40, shared: false, data: None }
+        )));
+        done(&mut directory, &mut ctx);
+
+        // The owner is snooped once the lookup is done
+        request(&mut directory, B, CoherenceRequest::ReadExclusive, &mut ctx);
+        assert_eq!(ctx.scheduled.pop(), Some((
+            30,
+            Target::Module(A),
+            EventPayload::CoherenceSnoop { address: 0x40, request: CoherenceRequest::ReadExclusive, requester: Target::Myself }
+        )));
+    }
+
+    #[test]
+    fn evictions_clear_sharers() {
+        let mut directory = Directory::new(vec![A, B], CoherenceProtocol::Mesi, SharerFormat::FullBitVector, 1);
+        let mut ctx = RecordingContext::default();
+
+        request(&mut directory, A, CoherenceRequest::Read, &mut ctx);
+        done(&mut directory, &mut ctx);
+        request(&mut directory, B, CoherenceRequest::Read, &mut ctx);
+        directory.process_event(Event::new(0, 0, EventPayload::CoherenceSnoopRes { address: 0x40, shared: true, data: None }), &mut ctx);
+        done(&mut directory, &mut ctx);
+
+        // A silently dropped its clean copy, B wrote its own back
+        for cache in [A, B] {
+            let requester = Target::Module(cache);
+            directory.process_event(Event::new(0, 0, EventPayload::CoherenceEvict { address: 0x40, requester }), &mut ctx);
+        }
+        assert!(directory.entries.is_empty());
+
+        ctx.scheduled.clear();
+        request(&mut directory, A, CoherenceRequest::ReadExclusive, &mut ctx);
+        assert_eq!(ctx.scheduled, vec![(
+            1,
+            Target::Module(A),
+            EventPayload::CoherenceGrant { address: 0x40, shared: false, data: None }
+        )]);
+    }
+
+    #[test]
+    fn stale_upgrades_become_read_exclusive() {
+        let mut directory = Directory::new(vec![A, B], CoherenceProtocol::Mesi, SharerFormat::FullBitVector, 1);
+        let mut ctx = RecordingContext::default();
+        let snoop_res = |shared| Event::new(0, 0, EventPayload::CoherenceSnoopRes { address: 0x40, shared, data: None });
+
+        request(&mut directory, A, CoherenceRequest::Read, &mut ctx);
+        done(&mut directory, &mut ctx);
+        request(&mut directory, B, CoherenceRequest::Read, &mut ctx);
+        directory.process_event(snoop_res(true), &mut ctx);
+        done(&mut directory, &mut ctx);
+
+        // Both sharers upgrade at once, and A wins
+        request(&mut directory, A, CoherenceRequest::Upgrade, &mut ctx);
+        request(&mut directory, B, CoherenceRequest::Upgrade, &mut ctx);
+        directory.process_event(snoop_res(true), &mut ctx);
+        ctx.scheduled.clear();
+        done(&mut directory, &mut ctx);
+
+        // B's copy is gone, so the owner is asked for the block
+        assert_eq!(ctx.scheduled, vec![(
+            1,
+            Target::Module(A),
+            EventPayload::CoherenceSnoop { address: 0x40, request: CoherenceRequest::ReadExclusive, requester: Target::Myself }
+        )]);
+    }
+
+    #[test]
+    fn full_bit_vector_tracks_sharers() {
+        let mut sharers = Sharers::new(SharerFormat::FullBitVector, 4);
+        sharers.add(1);
+        sharers.add(3);
+        assert_eq!(sharers.targets(4), vec![1, 3]);
+
+        sharers.set_only(2);
+        assert_eq!(sharers.targets(4), vec![2]);
+    }
+
+    #[test]
+    fn limited_pointer_overflow_broadcasts() {
+        let mut sharers = Sharers::new(SharerFormat::LimitedPointer { pointers: 2 }, 4);
+        sharers.add(0);
+        sharers.add(2);
+        sharers.add(2);
+        assert_eq!(sharers.targets(4), vec![0, 2]);
+
+        sharers.add(3);
+        assert_eq!(sharers.targets(4), vec![0, 1, 2, 3]);
+
+        sharers.set_only(1);
+        assert_eq!(sharers.targets(4), vec![1]);
+    }
+}
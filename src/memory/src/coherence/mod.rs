@@ -0,0 +1,154 @@
+mod bus;
+mod directory;
+
+pub use bus::SnoopBus;
+pub use directory::Directory;
+
+use narvi_core::{
+    CoherenceProtocol,
+    EngineContext,
+    ModuleId,
+    event::{
+        CoherenceRequest,
+        CoherenceState,
+        EventPayload,
+        Target,
+    }
+};
+
+/// Request being served by a coherence controller, gathering the answers of the snooped caches
+#[derive(Debug)]
+pub(crate) struct Transaction {
+    pub address: usize,
+    pub request: CoherenceRequest,
+    pub requester: ModuleId,
+    pub pending_responses: usize,
+    pub shared: bool,
+    pub data: Option<Vec<u8>>,
+}
+
+impl Transaction {
+    pub fn new(address: usize, request: CoherenceRequest, requester: ModuleId, pending_responses: usize) -> Self {
+        Self {
+            address,
+            request,
+            requester,
+            pending_responses,
+            shared: false,
+            data: None,
+        }
+    }
+
+    /// Accounts for a snoop response, returning whether every snooped cache has answered
+    pub fn respond(&mut self, address: usize, shared: bool, data: Option<Vec<u8>>) -> bool {
+        assert_eq!(self.address, address, "snoop response for the wrong block");
+
+        self.pending_responses -= 1;
+        self.shared |= shared;
+
+        if data.is_some() {
+            self.data = data;
+        }
+
+        self.pending_responses == 0
+    }
+
+    pub fn grant(&mut self, delay: u64, engine_context: &mut dyn EngineContext) {
+        engine_context.schedule(
+            delay,
+            Target::Module(self.requester),
+            EventPayload::CoherenceGrant {
+                address: self.address,
+                shared: self.shared,
+                data: self.data.take()
+            }
+        );
+    }
+}
+
+/// Reaction of a cache that holds a block when another cache's request is snooped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SnoopOutcome {
+    pub next: CoherenceState,
+    pub supplies_data: bool,
+    pub writes_back: bool,
+}
+
+pub(crate) fn snoop(
+    protocol: CoherenceProtocol,
+    state: CoherenceState,
+    request: CoherenceRequest
+) -> SnoopOutcome {
+    use CoherenceState::*;
+
+    let (next, supplies_data, writes_back) = match (request, state) {
+        (_, Invalid) => (Invalid, false, false),
+        (CoherenceRequest::Read, Modified) => match protocol {
+            // MESI has no owner, so the dirty block must reach memory before being shared
+            CoherenceProtocol::Mesi => (Shared, true, true),
+            CoherenceProtocol::Moesi => (Owned, true, false),
+        },
+        (CoherenceRequest::Read, Owned) => (Owned, true, false),
+        (CoherenceRequest::Read, Exclusive | Shared) => (Shared, false, false),
+        (CoherenceRequest::ReadExclusive, Modified | Owned) => (Invalid, true, false),
+        (CoherenceRequest::ReadExclusive, Exclusive | Shared) => (Invalid, false, false),
+        // A dirty copy means another writer won the race to upgrade and invalidated the
+        // requester's copy, which must now get the block like a ReadExclusive would
+        (CoherenceRequest::Upgrade, Modified | Owned) => (Invalid, true, false),
+        // The requester already holds the up-to-date block
+        (CoherenceRequest::Upgrade, Exclusive | Shared) => (Invalid, false, false),
+    };
+
+    SnoopOutcome { next, supplies_data, writes_back }
+}
+
+/// State of the block in the requesting cache once its request is granted
+pub(crate) fn granted_state(request: CoherenceRequest, shared: bool) -> CoherenceState {
+    match request {
+        CoherenceRequest::Read if shared => CoherenceState::Shared,
+        CoherenceRequest::Read => CoherenceState::Exclusive,
+        CoherenceRequest::ReadExclusive | CoherenceRequest::Upgrade => CoherenceState::Modified,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mesi_read_on_modified_writes_back() {
+        let outcome = snoop(CoherenceProtocol::Mesi, CoherenceState::Modified, CoherenceRequest::Read);
+        assert_eq!(outcome, SnoopOutcome { next: CoherenceState::Shared, supplies_data: true, writes_back: true });
+    }
+
+    #[test]
+    fn moesi_read_on_modified_keeps_ownership() {
+        let outcome = snoop(CoherenceProtocol::Moesi, CoherenceState::Modified, CoherenceRequest::Read);
+        assert_eq!(outcome, SnoopOutcome { next: CoherenceState::Owned, supplies_data: true, writes_back: false });
+
+        let outcome = snoop(CoherenceProtocol::Moesi, CoherenceState::Owned, CoherenceRequest::ReadExclusive);
+        assert_eq!(outcome, SnoopOutcome { next: CoherenceState::Invalid, supplies_data: true, writes_back: false });
+    }
+
+    #[test]
+    fn upgrade_invalidates_sharers() {
+        let outcome = snoop(CoherenceProtocol::Mesi, CoherenceState::Shared, CoherenceRequest::Upgrade);
+        assert_eq!(outcome.next, CoherenceState::Invalid);
+        assert!(!outcome.supplies_data);
+    }
+
+    #[test]
+    fn upgrade_that_lost_a_race_takes_the_dirty_block() {
+        for state in [CoherenceState::Modified, CoherenceState::Owned] {
+            let outcome = snoop(CoherenceProtocol::Moesi, state, CoherenceRequest::Upgrade);
+            assert_eq!(outcome, SnoopOutcome { next: CoherenceState::Invalid, supplies_data: true, writes_back: false });
+        }
+    }
+
+    #[test]
+    fn granted_states() {
+        assert_eq!(granted_state(CoherenceRequest::Read, false), CoherenceState::Exclusive);
+        assert_eq!(granted_state(CoherenceRequest::Read, true), CoherenceState::Shared);
+        assert_eq!(granted_state(CoherenceRequest::Upgrade, true), CoherenceState::Modified);
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::VecDeque;
+
+use narvi_core::{
+    DramConfig,
+    DramScheduler,
+    EngineContext,
+    Module,
+    PagePolicy,
+    event::{
+        Event,
+        EventPayload,
+        JournalEvent,
+        RowBufferOutcome,
+        Target,
+    }
+};
+
+use crate::Ram;
+
+// Request waiting for its turn in the controller queue. Its data was already
+// read or written on arrival, only its timing is left to model
+#[derive(Debug)]
+struct DramRequest {
+    address: usize,
+    arrival: u64,
+    bytes: usize,
+    response: Option<(Target, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bank {
+    open_row: Option<usize>,
+    // Cycle at which the bank can take its next command
+    ready_at: u64,
+    activated_at: u64,
+}
+
+/// Memory controller driving one DRAM channel.
+///
+/// Rows are interleaved across every bank of every bank group and rank of the channel,
+/// and each bank keeps its own row buffer. Storage is the channel's slice of the `Ram`
+#[derive(Debug)]
+pub struct DramController {
+    storage: Ram,
+    config: DramConfig,
+    banks: Vec<Bank>,
+    queue: VecDeque<DramRequest>,
+    bus_free_at: u64,
+    // Next refresh of each rank, staggered so that ranks never refresh together
+    next_refresh: Vec<u64>,
+    // Requests are issued one per cycle at most
+    next_issue_at: u64,
+    // Cycle of the pending wake-up, later ones being superseded by earlier ones
+    wake_at: Option<u64>,
+}
+
+impl Module for DramController {
+    fn process_event(&mut self, event: Event, engine_context: &mut dyn EngineContext) {
+        match event.payload() {
+            EventPayload::MemoryLoadReq { address, size_in_bytes, requester, .. } |
+            EventPayload::MemoryFetchReq { address, size_in_bytes, requester, .. } => {
+                let data = self.storage.read_bytes(*address, *size_in_bytes).unwrap();
+
+                self.enqueue(DramRequest {
+                    address: *address,
+                    arrival: engine_context.current_time(),
+                    bytes: *size_in_bytes,
+                    response: Some((*requester, data)),
+                }, engine_context);
+            },
+            EventPayload::MemoryStoreReq { address, data, .. } => {
+                self.storage.write_bytes(*address, data.to_owned()).unwrap();
+
+                self.enqueue(DramRequest {
+                    address: *address,
+                    arrival: engine_context.current_time(),
+                    bytes: data.len(),
+                    response: None,
+                }, engine_context);
+            },
+            EventPayload::MemoryAmoReq { address, op, data, requester, .. } => {
+                let old = self.storage.amo(*address, *op, data).unwrap();
+
+                self.enqueue(DramRequest {
+                    address: *address,
+                    arrival: engine_context.current_time(),
+                    bytes: data.len(),
+                    response: Some((*requester, old)),
+                }, engine_context);
+            },
+            EventPayload::DramSchedule => {
+                if self.wake_at == Some(event.timestamp()) {
+                    self.wake_at = None;
+                    self.issue(engine_context);
+                }
+            },
+            EventPayload::Reset => {},
+            _ => panic!("cannot process {event}")
+        }
+    }
+}
+
+impl DramController {
+    pub fn new(storage: Ram, config: DramConfig) -> Self {
+        let n_banks = config.ranks * config.bank_groups * config.banks_per_group;
+
+        Self {
+            storage,
+            config,
+            banks: vec![Bank::default(); n_banks],
+            queue: VecDeque::new(),
+            bus_free_at: 0,
+            next_refresh: (0..config.ranks)
+                .map(|rank| config.timing.t_refi * (rank as u64 + 1) / config.ranks as u64)
+                .collect(),
+            next_issue_at: 0,
+            wake_at: None,
+        }
+    }
+
+    fn enqueue(&mut self, request: DramRequest, engine_context: &mut dyn EngineContext) {
+        self.queue.push_back(request);
+        self.schedule_issue(engine_context);
+    }
+
+    // Issues the next request as soon as both the controller and the bank of a request
+    // the scheduling policy may pick are ready, right away if they already are
+    fn schedule_issue(&mut self, engine_context: &mut dyn EngineContext) {
+        let now = engine_context.current_time();
+
+        let candidates = match self.config.scheduler {
+            DramScheduler::Fcfs => self.queue.len().min(1),
+            DramScheduler::FrFcfs => self.queue.len(),
+        };
+
+        let Some(ready_at) = self.queue.iter()
+            .take(candidates)
+            .map(|request| self.banks[self.locate(request.address).0].ready_at)
+            .min()
+        else {
+            return;
+        };
+
+        let issue_at = ready_at.max(self.next_issue_at).max(now);
+
+        if issue_at == now {
+            self.issue(engine_context);
+        } else if self.wake_at.is_none_or(|wake_at| issue_at < wake_at) {
+            self.wake_at = Some(issue_at);
+            engine_context.schedule(issue_at - now, Target::Myself, EventPayload::DramSchedule);
+        }
+    }
+
+    // Bank and row holding `address`
+    fn locate(&self, address: usize) -> (usize, usize) {
+        let row_index = self.storage.local_address(address) / self.config.row_size;
+
+        (row_index % self.banks.len(), row_index / self.banks.len())
+    }
+
+    fn banks_per_rank(&self) -> usize {
+        self.config.bank_groups * self.config.banks_per_group
+    }
+
+    fn refresh(&mut self, now: u64, engine_context: &mut dyn EngineContext) {
+        let timing = self.config.timing;
+        let banks_per_rank = self.banks_per_rank();
+
+        for rank in 0..self.config.ranks {
+            while now >= self.next_refresh[rank] {
+                let start = self.next_refresh[rank];
+
+                for bank in &mut self.banks[rank * banks_per_rank..(rank + 1) * banks_per_rank] {
+                    bank.open_row = None;
+                    bank.ready_at = bank.ready_at.max(start) + timing.t_rfc;
+                }
+
+                self.next_refresh[rank] += timing.t_refi;
+                engine_context.record_journal(JournalEvent::DramRefresh);
+            }
+        }
+    }
+
+    // Issues the request the scheduling policy picks among those whose bank is ready
+    fn issue(&mut self, engine_context: &mut dyn EngineContext) {
+        let now = engine_context.current_time();
+        self.refresh(now, engine_context);
+
+        let ready = |request: &DramRequest| self.banks[self.locate(request.address).0].ready_at <= now;
+
+        let pick = match self.config.scheduler {
+            DramScheduler::Fcfs => self.queue.front()
+                .filter(|request| ready(request))
+                .map(|_| 0),
+            DramScheduler::FrFcfs => self.queue.iter()
+                .position(|request| {
+                    let (bank, row) = self.locate(request.address);
+                    ready(request) && self.banks[bank].open_row == Some(row)
+                })
+                .or_else(|| self.queue.iter().position(ready)),
+        };
+
+        // A refresh may have pushed the bank back, in which case nothing is ready
+        if let Some(idx) = pick {
+            let request = self.queue.remove(idx).expect("picked a request outside the queue");
+            self.serve(request, now, engine_context);
+            self.next_issue_at = now + 1;
+        }
+
+        self.schedule_issue(engine_context);
+    }
+
+    fn serve(&mut self, request: DramRequest, now: u64, engine_context: &mut dyn EngineContext) {
+        let timing = self.config.timing;
+        let (bank_idx, row) = self.locate(request.address);
+        let bank = &mut self.banks[bank_idx];
+
+        // Cycle at which the column command can be sent
+        let (outcome, column_at) = match bank.open_row {
+            Some(open_row) if open_row == row => (RowBufferOutcome::Hit, now),
+            Some(_) => {
+                let precharge_at = now.max(bank.activated_at + timing.t_ras);
+                bank.activated_at = precharge_at + timing.t_rp;
+                (RowBufferOutcome::Conflict, bank.activated_at + timing.t_rcd)
+            },
+            None => {
+                bank.activated_at = now;
+                (RowBufferOutcome::Miss, now + timing.t_rcd)
+            }
+        };
+
+        let done = (column_at + timing.t_cas).max(self.bus_free_at) + timing.t_burst;
+        self.bus_free_at = done;
+
+        match self.config.page_policy {
+            PagePolicy::Open => {
+                bank.open_row = Some(row);
+                bank.ready_at = column_at + timing.t_burst;
+            },
+            PagePolicy::Closed => {
+                bank.open_row = None;
+                bank.ready_at = done.max(bank.activated_at + timing.t_ras) + timing.t_rp;
+            }
+        }
+
+        engine_context.record_journal(JournalEvent::DramAccess {
+            row: outcome,
+            queued: now - request.arrival,
+            bytes: request.bytes,
+        });
+
+        if let Some((requester, data)) = request.response {
+            engine_context.schedule(
+                done - now,
+                requester,
+                EventPayload::MemoryLoadRes { address: request.address, data }
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_util::RecordingContext;
+
+    fn outcomes(ctx: &RecordingContext) -> Vec<RowBufferOutcome> {
+        ctx.journal.iter()
+            .filter_map(|event| match event {
+                JournalEvent::DramAccess { row, .. } => Some(*row),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn load(address: usize) -> EventPayload {
+        EventPayload::MemoryLoadReq { address, size_in_bytes: 8, requester: Target::Module(1), pc: None }
+    }
+
+    // Plays the loads arriving at the given cycles, returning the cycle each address is answered at
+    fn serve_all(controller: &mut DramController, loads: &[(u64, usize)], ctx: &mut RecordingContext) -> Vec<(usize, u64)> {
+        let mut pending: Vec<(u64, EventPayload)> = loads.iter()
+            .map(|(arrival, address)| (*arrival, load(*address)))
+            .collect();
+        let mut answered = Vec::new();
+
+        while let Some(first) = pending.iter().map(|(at, _)| *at).min() {
+            let (now, payload) = pending.remove(pending.iter().position(|(at, _)| *at == first).unwrap());
+            ctx.now = now;
+            controller.process_event(Event::new(now, 0, payload), ctx);
+
+            for (delay, _, payload) in ctx.scheduled.drain(..) {
+                match payload {
+                    EventPayload::MemoryLoadRes { address, .. } => answered.push((address, now + delay)),
+                    payload => pending.push((now + delay, payload)),
+                }
+            }
+        }
+
+        answered
+    }
+
+    fn at_once(addresses: &[usize]) -> Vec<(u64, usize)> {
+        addresses.iter().map(|address| (0, *address)).collect()
+    }
+
+    #[test]
+    fn open_page_hits_row_buffer() {
+        let mut controller = DramController::new(Ram::new(1 << 16), DramConfig::default());
+        let mut ctx = RecordingContext::default();
+
+        serve_all(&mut controller, &at_once(&[0x0, 0x40, 0x80]), &mut ctx);
+
+        assert_eq!(outcomes(&ctx), vec![RowBufferOutcome::Miss, RowBufferOutcome::Hit, RowBufferOutcome::Hit]);
+    }
+
+    #[test]
+    fn closed_page_never_hits() {
+        let config = DramConfig { page_policy: PagePolicy::Closed, ..DramConfig::default() };
+        let mut controller = DramController::new(Ram::new(1 << 16), config);
+        let mut ctx = RecordingContext::default();
+
+        serve_all(&mut controller, &at_once(&[0x0, 0x40]), &mut ctx);
+
+        assert_eq!(outcomes(&ctx), vec![RowBufferOutcome::Miss, RowBufferOutcome::Miss]);
+    }
+
+    #[test]
+    fn fr_fcfs_serves_row_hits_first() {
+        // With 16 banks, rows 0 and 16 of the channel share bank 0
+        let conflicting = 16 * 1024;
+        let mut controller = DramController::new(Ram::new(1 << 16), DramConfig::default());
+        let mut ctx = RecordingContext::default();
+
+        serve_all(&mut controller, &at_once(&[0x0, conflicting, 0x40]), &mut ctx);
+
+        assert_eq!(
+            outcomes(&ctx),
+            vec![RowBufferOutcome::Miss, RowBufferOutcome::Hit, RowBufferOutcome::Conflict]
+        );
+    }
+
+    #[test]
+    fn idle_controller_issues_on_arrival() {
+        let mut controller = DramController::new(Ram::new(1 << 16), DramConfig::default());
+        let mut ctx = RecordingContext::default();
+
+        // tRCD + tCAS + tBurst
+        assert_eq!(serve_all(&mut controller, &[(0, 0x0)], &mut ctx), vec![(0x0, 32)]);
+    }
+
+    #[test]
+    fn ready_banks_do_not_wait_for_busy_ones() {
+        // Rows 0 and 16 share bank 0, row 1 lives in bank 1
+        let (row_0, row_16, row_1) = (0x0, 16 * 1024, 1024);
+        let loads = [(0, row_0), (0, row_16), (5, row_1)];
+
+        // Row 1 issues on arrival while row 16 waits for bank 0, then for tRAS and tRP
+        let mut controller = DramController::new(Ram::new(1 << 16), DramConfig::default());
+        let mut ctx = RecordingContext::default();
+        assert_eq!(
+            serve_all(&mut controller, &loads, &mut ctx),
+            vec![(row_0, 32), (row_1, 37), (row_16, 78)]
+        );
+
+        // In order, row 1 waits behind row 16 and then for the data bus
+        let config = DramConfig { scheduler: DramScheduler::Fcfs, ..DramConfig::default() };
+        let mut controller = DramController::new(Ram::new(1 << 16), config);
+        let mut ctx = RecordingContext::default();
+        assert_eq!(
+            serve_all(&mut controller, &loads, &mut ctx),
+            vec![(row_0, 32), (row_16, 78), (row_1, 82)]
+        );
+    }
+}
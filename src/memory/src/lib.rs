@@ -1,12 +1,36 @@
 mod ram;
 mod cache;
+mod coherence;
+mod dram;
+#[cfg(test)]
+mod test_util;
 
 use ram::RamError;
 
 pub use ram::Ram;
+pub use dram::DramController;
+pub use coherence::{
+    Directory,
+    SnoopBus,
+};
 pub use cache::{
     CacheLevel,
     CacheError,
+    Fifo,
+    Lfu,
+    Lru,
+    MissClassifier,
+    NextLine,
+    Nru,
+    PrefetchAccess,
+    Prefetcher,
+    Random,
+    ReplacementPolicy,
+    Rrip,
+    Stream,
+    Stride,
+    TreePlru,
+    optimal_misses,
 };
 
 #[derive(Debug, PartialEq, Eq)]
@@ -2,35 +2,79 @@ use narvi_core::{
     EngineContext,
     Module, 
     ModuleId, 
+    RamLatency,
     event::{
+        AmoOp,
         Event,
         EventPayload,
+        StatUpdate,
     }
 };
 
+use rand::{
+    RngExt,
+    SeedableRng,
+    rngs::SmallRng,
+};
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum RamError {
-    OutOfBounds
+    OutOfBounds,
+    /// The interleaving does not split the memory into whole chunks of at least a block
+    InvalidInterleave
+}
+
+/// Address interleaving used when the RAM is split across several memory controllers
+#[derive(Debug, Clone, Copy)]
+struct Interleave {
+    channel: usize,
+    channels: usize,
+    size: usize,
 }
 
 /// Simple byte-addressable little-endian RAM implementation
-#[derive(Debug, Default)]
-pub struct Ram(Vec<u8>);
+#[derive(Debug)]
+pub struct Ram {
+    bytes: Vec<u8>,
+    interleave: Option<Interleave>,
+    latency: RamLatency,
+    // Draws variable latencies, seeded so that runs can be reproduced
+    rng: SmallRng,
+}
 
 impl Module for Ram {
     fn process_event(&mut self, event: Event, engine_context: &mut dyn EngineContext) {
         match event.payload() {
-            EventPayload::MemoryLoadReq { address, size_in_bytes, requester } => {
+            EventPayload::MemoryLoadReq { address, size_in_bytes, requester, .. } |
+            EventPayload::MemoryFetchReq { address, size_in_bytes, requester, .. } => {
                 let data = self.read_bytes(*address, *size_in_bytes).unwrap();
 
+                engine_context.record_stat("reads", StatUpdate::Count(1));
+                engine_context.record_stat("access_size", StatUpdate::Distribute(*size_in_bytes as u64));
+
                 engine_context.schedule(
-                    1,
+                    self.access_latency(),
                     *requester,
-                    EventPayload::MemoryLoadRes { data: data }
+                    EventPayload::MemoryLoadRes { address: *address, data }
                 ); 
             },
-            EventPayload::MemoryStoreReq { address, data } => {
+            EventPayload::MemoryStoreReq { address, data, .. } => {
                 self.write_bytes(*address, data.to_owned()).unwrap();
+
+                engine_context.record_stat("writes", StatUpdate::Count(1));
+                engine_context.record_stat("access_size", StatUpdate::Distribute(data.len() as u64));
+            },
+            EventPayload::MemoryAmoReq { address, op, data, requester, .. } => {
+                let old = self.amo(*address, *op, data).unwrap();
+
+                engine_context.record_stat("atomics", StatUpdate::Count(1));
+                engine_context.record_stat("access_size", StatUpdate::Distribute(data.len() as u64));
+
+                engine_context.schedule(
+                    self.access_latency(),
+                    *requester,
+                    EventPayload::MemoryLoadRes { address: *address, data: old }
+                );
             },
             EventPayload::Reset => {},
             _ => panic!("cannot process {event}")
@@ -40,11 +84,96 @@ impl Module for Ram {
 
 impl Ram {
     pub fn new(size: usize) -> Self {
-        Ram(vec![0; size])
+        Ram {
+            bytes: vec![0; size],
+            interleave: None,
+            latency: RamLatency::default(),
+            rng: SmallRng::seed_from_u64(0),
+        }
+    }
+
+    /// Creates the slice of a `size` bytes memory owned by controller `channel`,
+    /// out of `channels`, when addresses are interleaved every `interleave_size` bytes.
+    /// Chunks must be whole and hold at least a `block_size` bytes block, so that no
+    /// block is split across controllers
+    pub fn interleaved(size: usize, channel: usize, channels: usize, interleave_size: usize, block_size: usize) -> Result<Self, RamError> {
+        if channel >= channels
+            || interleave_size == 0
+            || interleave_size < block_size
+            || !size.is_multiple_of(interleave_size)
+        {
+            return Err(RamError::InvalidInterleave);
+        }
+
+        // The first chunks of the last stride go to the lowest channels
+        let chunks = size / interleave_size;
+        let owned_chunks = chunks / channels + usize::from(channel < chunks % channels);
+
+        Ok(Ram {
+            bytes: vec![0; owned_chunks * interleave_size],
+            interleave: Some(Interleave {
+                channel,
+                channels,
+                size: interleave_size,
+            }),
+            latency: RamLatency::default(),
+            rng: SmallRng::seed_from_u64(0),
+        })
+    }
+
+    /// Sets how long requests take, variable latencies being drawn from a generator seeded with `seed`
+    pub fn set_latency(&mut self, latency: RamLatency, seed: u64) {
+        self.latency = latency;
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    fn access_latency(&mut self) -> u64 {
+        match self.latency {
+            RamLatency::Fixed { cycles } => cycles,
+            RamLatency::Variable { min, max } => self.rng.random_range(min..=max),
+        }
+    }
+
+    /// Returns whether this controller is responsible for `addr`
+    pub fn owns(&self, addr: usize) -> bool {
+        match self.interleave {
+            Some(interleave) => (addr / interleave.size) % interleave.channels == interleave.channel,
+            None => true,
+        }
+    }
+
+    pub(crate) fn local_address(&self, addr: usize) -> usize {
+        match self.interleave {
+            Some(interleave) => {
+                let stride = interleave.size * interleave.channels;
+                (addr / stride) * interleave.size + addr % interleave.size
+            },
+            None => addr,
+        }
+    }
+
+    /// Writes every byte of `val` owned by this controller, skipping the others.
+    /// Used to spread a program image over interleaved controllers
+    pub fn load(&mut self, addr: usize, val: &[u8]) -> Result<(), RamError> {
+        for (i, byte) in val.iter().enumerate() {
+            if self.owns(addr + i) {
+                self.write_8(addr + i, *byte)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `op` with `operand` to the bytes at `addr`, returning the bytes it replaced
+    pub fn amo(&mut self, addr: usize, op: AmoOp, operand: &[u8]) -> Result<Vec<u8>, RamError> {
+        let old = self.read_bytes(addr, operand.len())?;
+        self.write_bytes(addr, op.apply(&old, operand))?;
+        Ok(old)
     }
 
     pub fn read_bytes(&self, addr: usize, bytes: usize) -> Result<Vec<u8>, RamError> {
-        if let Some(v) = self.0.get(addr..addr+bytes) {
+        let local = self.local_address(addr);
+
+        if let Some(v) = self.bytes.get(local..local+bytes) {
             return Ok(v.to_vec());
         }
 
@@ -52,7 +181,7 @@ impl Ram {
     }
 
     pub fn read_8(&self, addr: usize) -> Result<u8, RamError> {
-        if let Some(v) = self.0.get(addr).copied() {
+        if let Some(v) = self.bytes.get(self.local_address(addr)).copied() {
             return Ok(v);
         }
 
@@ -91,7 +220,8 @@ impl Ram {
 
     pub fn write_bytes(&mut self, addr: usize, val: Vec<u8>) -> Result<(), RamError> {
         let bytes = val.len();
-        if let Some(slice) = self.0.get_mut(addr..addr+bytes) {
+        let local = self.local_address(addr);
+        if let Some(slice) = self.bytes.get_mut(local..local+bytes) {
             slice.copy_from_slice(&val);
             return Ok(());
         }
@@ -99,7 +229,8 @@ impl Ram {
     }
 
     pub fn write_8(&mut self, addr: usize, val: u8) -> Result<(), RamError> {
-        if let Some(cell) = self.0.get_mut(addr) {
+        let local = self.local_address(addr);
+        if let Some(cell) = self.bytes.get_mut(local) {
             *cell = val;
             return Ok(());
         }
@@ -133,3 +264,70 @@ impl Ram {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use narvi_core::event::Target;
+
+    use crate::test_util::RecordingContext;
+
+    fn load_latencies(ram: &mut Ram, loads: usize) -> Vec<u64> {
+        let mut ctx = RecordingContext::default();
+        for _ in 0..loads {
+            let load = EventPayload::MemoryLoadReq { address: 0, size_in_bytes: 8, requester: Target::Module(1), pc: None };
+            ram.process_event(Event::new(0, 0, load), &mut ctx);
+        }
+        ctx.scheduled.into_iter().map(|(delay, _, _)| delay).collect()
+    }
+
+    #[test]
+    fn fixed_latency_delays_every_response() {
+        let mut ram = Ram::new(64);
+        ram.set_latency(RamLatency::Fixed { cycles: 40 }, 0);
+
+        assert_eq!(load_latencies(&mut ram, 3), vec![40, 40, 40]);
+    }
+
+    #[test]
+    fn variable_latency_is_reproducible_from_its_seed() {
+        let latency = RamLatency::Variable { min: 20, max: 80 };
+        let draw = |seed| {
+            let mut ram = Ram::new(64);
+            ram.set_latency(latency, seed);
+            load_latencies(&mut ram, 32)
+        };
+
+        let latencies = draw(7);
+        assert_eq!(latencies, draw(7));
+        assert_ne!(latencies, draw(8));
+        assert!(latencies.iter().all(|cycles| (20..=80).contains(cycles)));
+    }
+
+    #[test]
+    fn interleaved_channels_hold_every_chunk_they_own() {
+        // Three chunks over two channels: channel 0 owns chunks 0 and 2, channel 1 chunk 1
+        let mut channels: Vec<Ram> = (0..2)
+            .map(|channel| Ram::interleaved(3 * 64, channel, 2, 64, 64).unwrap())
+            .collect();
+
+        for addr in (0..3 * 64).step_by(8) {
+            let owner = channels.iter_mut().find(|ram| ram.owns(addr)).unwrap();
+            owner.write_64(addr, addr as u64).unwrap();
+            assert_eq!(owner.read_64(addr), Ok(addr as u64));
+        }
+
+        assert_eq!(channels[0].bytes.len(), 128);
+        assert_eq!(channels[1].bytes.len(), 64);
+        assert_eq!(channels[1].read_8(3 * 64), Err(RamError::OutOfBounds));
+    }
+
+    #[test]
+    fn interleaving_must_split_memory_into_whole_blocks() {
+        assert_eq!(Ram::interleaved(4096, 0, 2, 32, 64).err(), Some(RamError::InvalidInterleave));
+        assert_eq!(Ram::interleaved(4096 + 32, 0, 2, 64, 64).err(), Some(RamError::InvalidInterleave));
+        assert_eq!(Ram::interleaved(4096, 2, 2, 64, 64).err(), Some(RamError::InvalidInterleave));
+        assert!(Ram::interleaved(4096, 1, 3, 64, 64).is_ok());
+    }
+}
@@ -0,0 +1,33 @@
+use narvi_core::{
+    EngineContext,
+    event::{
+        EventPayload,
+        JournalEvent,
+        StatUpdate,
+        Target,
+    }
+};
+
+/// Engine context that keeps everything a module schedules and journals, for tests to inspect
+#[derive(Default)]
+pub(crate) struct RecordingContext {
+    pub now: u64,
+    pub scheduled: Vec<(u64, Target, EventPayload)>,
+    pub journal: Vec<JournalEvent>,
+}
+
+impl EngineContext for RecordingContext {
+    fn schedule(&mut self, delay: u64, target: Target, payload: EventPayload) {
+        self.scheduled.push((delay, target, payload));
+    }
+
+    fn record_journal(&mut self, event: JournalEvent) {
+        self.journal.push(event);
+    }
+
+    fn record_stat(&mut self, _: &str, _: StatUpdate) {}
+
+    fn current_time(&self) -> u64 {
+        self.now
+    }
+}
@@ -2,41 +2,264 @@ use std::{
     env,
     fs,
     fs::File,
-    io::prelude::*
+    io::prelude::*,
+    ops::Range
 };
 
 use core::error::Error;
 
 use narvi_core::{
+    elf::{
+        self,
+        SymbolTable
+    },
     serialization::{
         MachineConfig
+    },
+    trace::{
+        self,
+        TraceAccess,
+        TraceFormat
     }
 };
 
-use engine::Engine;
+use engine::{CommitFilter, Engine, SampleInterval};
+use harts::disasm;
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .map(|i| args.get(i + 1).unwrap_or_else(|| panic!("Expected a value after {flag}")))
+}
+
+// `<start>:<end>`, the end excluded and either bound left out to leave it open,
+// with decimal or 0x-prefixed hexadecimal bounds
+fn parse_range(flag: &str, value: &str) -> Range<u128> {
+    let bound = |bound: &str, default: u128| match bound.strip_prefix("0x") {
+        _ if bound.is_empty() => default,
+        Some(hex) => u128::from_str_radix(hex, 16).unwrap_or_else(|_| panic!("Expected a number in {flag} {value}")),
+        None => bound.parse().unwrap_or_else(|_| panic!("Expected a number in {flag} {value}")),
+    };
+    let (start, end) = value.split_once(':').unwrap_or_else(|| panic!("Expected <start>:<end> after {flag}"));
+
+    bound(start, 0)..bound(end, u128::MAX)
+}
+
+// Every instruction of `segments`, under the label of each symbol starting one
+fn print_disassembly(segments: &[(u64, Vec<u8>)], symbols: &SymbolTable) {
+    for (address, code) in segments {
+        for (pc, inst, text) in disasm::disassemble_all(code, *address) {
+            if let Some(symbol) = symbols.lookup(pc).filter(|symbol| symbol.address == pc) {
+                println!("\n{pc:016x} <{}>:", symbol.name);
+            }
+
+            let encoding = if disasm::instruction_size(inst as u16) == 2 { format!("{inst:04x}") } else { format!("{inst:08x}") };
+            println!("{pc:8x}:  {encoding:<8}  {text}");
+        }
+    }
+}
+
+fn write_trace(path: &str, format: TraceFormat, accesses: &[TraceAccess]) -> Result<(), Box<dyn Error>> {
+    let mut writer = std::io::BufWriter::new(File::create(path)?);
+
+    if format == TraceFormat::Narvi {
+        trace::write_narvi_header(&mut writer)?;
+    }
+
+    for access in accesses {
+        match format {
+            TraceFormat::Narvi => trace::write_narvi_record(&mut writer, access)?,
+            _ => trace::write_dinero_record(&mut writer, access)?,
+        }
+    }
+
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let config = MachineConfig::default();
+    let mut config = MachineConfig::default();
 
     let args: Vec<String> = env::args().collect();
 
+    // --classify-misses sorts the misses of every cache level into the three Cs
+    if args.iter().any(|arg| arg == "--classify-misses") {
+        for level in &mut config.cache_config {
+            level.classify_misses = true;
+        }
+    }
+
     if args.len() == 1 {
         panic!("Expected a file path");
     }
     
-    let mut engine = {
+    let mut symbols = SymbolTable::default();
+
+    if args[1] == "disasm" {
+        // narvi disasm <file>, an ELF or a raw binary loaded at 0
+        let bytes = fs::read(args.get(2).expect("Expected a file path")).expect("Could not read file");
+
+        if elf::is_elf(&bytes) {
+            let image = elf::parse_elf(&bytes)?;
+            print_disassembly(&image.segments, &image.symbols);
+        } else {
+            print_disassembly(&[(0, bytes)], &symbols);
+        }
+
+        return Ok(());
+    }
+
+    // --verbose writes diagnostics, such as every processed event, to stderr
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+
+    let mut engine = if args[1] == "cachesim" {
+        // narvi cachesim <trace> [--format dinero|csv|narvi]
+        let path = args.get(2).expect("Expected a trace path");
+        let format = match flag_value(&args, "--format") {
+            Some(name) => TraceFormat::from_name(name).expect("Expected dinero, csv or narvi after --format"),
+            None => TraceFormat::from_path(path),
+        };
+
+        let accesses = trace::read_trace(File::open(path)?, format)?;
+        Engine::build_for_trace(&config, accesses)?
+    } else {
         let assembly = fs::read(&args[1]).expect("Could not read file");
-        println!("{assembly:X?}");
-        Engine::build_from_config(&config, assembly)
+
+        if elf::is_elf(&assembly) {
+            let image = elf::parse_elf(&assembly)?;
+            symbols = image.symbols.clone();
+            Engine::build_from_elf(&config, &image)
+        } else {
+            if verbose {
+                eprintln!("{assembly:X?}");
+            }
+            Engine::build_from_config(&config, assembly)
+        }
     };
 
-    let mut is_running = true;
+    if verbose {
+        engine.log_events();
+    }
 
-    while is_running {
-        is_running = engine.update();
+    // --trace <path> [--trace-format narvi|dinero] [--trace-arrivals]
+    let trace_out = flag_value(&args, "--trace");
+    let trace_format = match flag_value(&args, "--trace-format") {
+        Some(name) => TraceFormat::from_name(name)
+            .filter(|format| *format != TraceFormat::Csv)
+            .expect("Expected narvi or dinero after --trace-format"),
+        None => trace_out.map_or(TraceFormat::Narvi, |path| match TraceFormat::from_path(path) {
+            TraceFormat::Dinero => TraceFormat::Dinero,
+            _ => TraceFormat::Narvi,
+        }),
+    };
+
+    if trace_out.is_some() {
+        engine.record_trace(args.iter().any(|arg| arg == "--trace-arrivals"));
+    }
+
+    // --samples-out <path.csv|path.jsonl> (--sample-cycles <n> | --sample-instructions <n>)
+    let samples_out = flag_value(&args, "--samples-out");
+    if samples_out.is_some() {
+        let interval = match (flag_value(&args, "--sample-cycles"), flag_value(&args, "--sample-instructions")) {
+            (Some(cycles), None) => SampleInterval::Cycles(cycles.parse().expect("Expected a number of cycles")),
+            (None, Some(instructions)) => SampleInterval::Instructions(instructions.parse().expect("Expected a number of instructions")),
+            _ => panic!("Expected either --sample-cycles or --sample-instructions with --samples-out"),
+        };
+        engine.sample_every(interval);
+    }
+
+    // --profile <path> writes the hotspots, --profile-folded <path> the folded call stacks
+    let profile_out = flag_value(&args, "--profile");
+    let folded_out = flag_value(&args, "--profile-folded");
+    if profile_out.is_some() || folded_out.is_some() {
+        engine.profile();
+    }
+
+    // --commit-log <path> [--commit-pcs <start>:<end>] [--commit-instructions <start>:<end>]
+    let commit_log_out = flag_value(&args, "--commit-log");
+    if commit_log_out.is_some() {
+        let pcs = flag_value(&args, "--commit-pcs").map(|value| parse_range("--commit-pcs", value));
+        engine.log_commits(CommitFilter {
+            pcs: pcs.map(|pcs| pcs.start as u64..pcs.end.min(u64::MAX as u128) as u64),
+            instructions: flag_value(&args, "--commit-instructions").map(|value| parse_range("--commit-instructions", value)),
+        });
+    }
+
+    // --warmup <instructions> resets the statistics once that many instructions have retired
+    if let Some(instructions) = flag_value(&args, "--warmup") {
+        engine.reset_stats_after(instructions.parse().expect("Expected a number of instructions after --warmup"));
+    }
+
+    engine.run();
+
+    // --stats-format json|csv|yaml|text|stats|stats-json [--stats-out <path>], the format defaulting to
+    // the extension of the path. stats and stats-json list every statistic of the registry by path
+    let stats_out = flag_value(&args, "--stats-out");
+    let stats_format = flag_value(&args, "--stats-format")
+        .map(String::as_str)
+        .or_else(|| stats_out
+            .and_then(|path| path.rsplit_once('.'))
+            .map(|(_, extension)| extension)
+            .filter(|extension| ["json", "csv", "yaml", "yml"].contains(extension))
+        )
+        .unwrap_or("text");
+
+    let journal = engine.get_journal();
+    let stats = match stats_format {
+        "json" => serde_json::to_string_pretty(journal)? + "\n",
+        "yaml" | "yml" => serde_yaml::to_string(journal)?,
+        "csv" => journal.registry().to_csv(),
+        "text" => journal.dump(),
+        "stats" => journal.registry().to_text(),
+        "stats-json" => journal.registry().to_json(),
+        format => panic!("Expected json, csv, yaml, text, stats or stats-json after --stats-format, found {format}"),
+    };
+
+    match stats_out {
+        Some(path) => fs::write(path, stats)?,
+        None => print!("{stats}"),
+    }
+
+    // --reuse-out <path.csv|path.json>
+    if let Some(path) = flag_value(&args, "--reuse-out") {
+        let journal = engine.get_journal();
+        let histograms = if path.ends_with(".json") { journal.reuse_json() } else { journal.reuse_csv() };
+        fs::write(path, histograms)?;
+    }
+
+    if let Some(profile) = engine.take_profile() {
+        if let Some(path) = profile_out {
+            fs::write(path, profile.hotspots(&symbols))?;
+        }
+        if let Some(path) = folded_out {
+            fs::write(path, profile.folded(&symbols))?;
+        }
+    }
+
+    if let Some(path) = commit_log_out
+        && let Some(log) = engine.take_commit_log()
+    {
+        fs::write(path, log)?;
+    }
+
+    if let Some(path) = samples_out
+        && let Some(series) = engine.take_time_series()
+    {
+        let samples = if path.ends_with(".jsonl") || path.ends_with(".json") { series.to_json_lines() } else { series.to_csv() };
+        fs::write(path, samples)?;
     }
 
-    println!("{:?}", engine.get_journal());
+    if let Some(path) = trace_out
+        && let Some(recorded) = engine.take_trace()
+    {
+        write_trace(path, trace_format, &recorded.harts)?;
+
+        // Arrivals at module <id> go next to the hart trace, as <stem>-m<id>.<extension>
+        let (stem, extension) = path.rsplit_once('.').unwrap_or((path, ""));
+        for (id, accesses) in &recorded.arrivals {
+            let arrivals_path = if extension.is_empty() { format!("{stem}-m{id}") } else { format!("{stem}-m{id}.{extension}") };
+            write_trace(&arrivals_path, trace_format, accesses)?;
+        }
+    }
 
     let yaml = serde_yaml::to_string(&config).unwrap();
     {
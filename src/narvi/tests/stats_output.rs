@@ -0,0 +1,37 @@
+use std::{
+    env,
+    fs,
+    process::Command
+};
+
+// Runs narvi on a raw program in a scratch directory, returning its stdout
+fn run(name: &str, program: &[u32], args: &[&str]) -> String {
+    let dir = env::temp_dir().join(format!("narvi-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("program.bin"), program.iter().flat_map(|inst| inst.to_le_bytes()).collect::<Vec<u8>>()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_narvi"))
+        .current_dir(&dir)
+        .arg("program.bin")
+        .args(args)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// addi a0, a0, 1 twice, then ebreak
+const PROGRAM: [u32; 3] = [0x00150513, 0x00150513, 0x00100073];
+
+#[test]
+fn stdout_holds_only_the_stats() {
+    let json: serde_json::Value = serde_json::from_str(&run("json", &PROGRAM, &["--stats-format", "json", "--verbose"])).unwrap();
+    assert_eq!(json["harts"].as_object().unwrap().values().next().unwrap()["num_inst"], 3);
+
+    let csv = run("csv", &PROGRAM, &["--stats-format", "csv"]);
+    let mut rows = csv.lines().map(|line| line.split_once(',').unwrap());
+    assert_eq!(rows.next(), Some(("stat", "value")));
+    assert!(rows.any(|row| row == ("system.instructions", "3")));
+}
@@ -0,0 +1,356 @@
+use std::{
+    error::Error,
+    fmt::Display
+};
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_REL: u16 = 1;
+const EM_RISCV: u16 = 0xF3;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const STT_NOTYPE: u8 = 0;
+const STT_FUNC: u8 = 2;
+
+const PROGRAM_HEADER_SIZE: usize = 56;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+
+pub enum ElfError {
+    /// Not a 64-bit little-endian RISC-V ELF
+    Header,
+    /// A header or section reaches past the end of the file
+    Truncated,
+}
+
+impl ElfError {
+    fn as_str(&self) -> String {
+        match self {
+            Self::Header => "Header".to_string(),
+            Self::Truncated => "Truncated".to_string(),
+        }
+    }
+}
+
+impl Display for ElfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ElfError: {}", self.as_str())
+    }
+}
+
+impl std::fmt::Debug for ElfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ElfError: {}", self.as_str())
+    }
+}
+
+impl Error for ElfError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u64,
+    /// Zero for labels, which then extend up to the next symbol
+    pub size: u64,
+}
+
+/// Functions of a program, to tell which one an address belongs to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    // Sorted by address, a single symbol per address
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|symbol| symbol.address);
+        symbols.dedup_by_key(|symbol| symbol.address);
+
+        Self { symbols }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn lookup(&self, address: u64) -> Option<&Symbol> {
+        let idx = self.symbols.partition_point(|symbol| symbol.address <= address).checked_sub(1)?;
+        let symbol = &self.symbols[idx];
+
+        (symbol.size == 0 || address < symbol.address + symbol.size).then_some(symbol)
+    }
+
+    /// Name of the function `address` belongs to, or the address itself outside any
+    pub fn name_of(&self, address: u64) -> String {
+        self.lookup(address).map_or(format!("{address:#x}"), |symbol| symbol.name.clone())
+    }
+}
+
+/// What a RISC-V ELF puts in memory, and where execution starts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ElfImage {
+    pub entry: u64,
+    /// Bytes to load at each address. Zero-initialized parts are left out, memory starting zeroed
+    pub segments: Vec<(u64, Vec<u8>)>,
+    pub symbols: SymbolTable,
+}
+
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(ELF_MAGIC)
+}
+
+fn slice(bytes: &[u8], offset: u64, size: u64) -> Result<&[u8], ElfError> {
+    let start = usize::try_from(offset).map_err(|_| ElfError::Truncated)?;
+    let end = start.checked_add(usize::try_from(size).map_err(|_| ElfError::Truncated)?).ok_or(ElfError::Truncated)?;
+
+    bytes.get(start..end).ok_or(ElfError::Truncated)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn name_at(strings: &[u8], offset: u32) -> String {
+    let name = strings.get(offset as usize..).unwrap_or_default();
+    let end = name.iter().position(|byte| *byte == 0).unwrap_or(name.len());
+
+    String::from_utf8_lossy(&name[..end]).into_owned()
+}
+
+/// Reads the loadable segments and function symbols of an executable.
+/// Relocatable objects have no segments and leave every section at address 0, so their
+/// allocated sections are laid out one after the other from address 0 instead, each at
+/// its alignment. Their relocations are not applied
+pub fn parse_elf(bytes: &[u8]) -> Result<ElfImage, ElfError> {
+    let header = slice(bytes, 0, 64).map_err(|_| ElfError::Header)?;
+    if !is_elf(header) || header[4] != ELFCLASS64 || header[5] != ELFDATA2LSB || u16_at(header, 18) != EM_RISCV {
+        return Err(ElfError::Header);
+    }
+
+    let entry = u64_at(header, 24);
+    let program_headers = slice(bytes, u64_at(header, 32), (u16_at(header, 56) as usize * PROGRAM_HEADER_SIZE) as u64)?;
+    let section_headers = slice(bytes, u64_at(header, 40), (u16_at(header, 60) as usize * SECTION_HEADER_SIZE) as u64)?;
+    let sections: Vec<&[u8]> = section_headers.chunks_exact(SECTION_HEADER_SIZE).collect();
+
+    // Address of each section, their own unless the object is relocatable
+    let mut addresses: Vec<u64> = sections.iter().map(|section| u64_at(section, 16)).collect();
+
+    let mut segments = Vec::new();
+    for program_header in program_headers.chunks_exact(PROGRAM_HEADER_SIZE) {
+        if u32_at(program_header, 0) == PT_LOAD {
+            let data = slice(bytes, u64_at(program_header, 8), u64_at(program_header, 32))?;
+            segments.push((u64_at(program_header, 16), data.to_vec()));
+        }
+    }
+
+    if u16_at(header, 16) == ET_REL {
+        let mut next = 0u64;
+
+        for (section, address) in sections.iter().zip(&mut addresses) {
+            if u64_at(section, 8) & SHF_ALLOC == 0 {
+                continue;
+            }
+
+            *address = next.next_multiple_of(u64_at(section, 48).max(1));
+            next = *address + u64_at(section, 32);
+
+            // Zero-initialized sections only take room, memory starting zeroed
+            if u32_at(section, 4) != SHT_NOBITS {
+                let data = slice(bytes, u64_at(section, 24), u64_at(section, 32))?;
+                segments.push((*address, data.to_vec()));
+            }
+        }
+    }
+
+    let mut functions = Vec::new();
+    let mut labels = Vec::new();
+    for section in sections.iter().filter(|section| u32_at(section, 4) == SHT_SYMTAB) {
+        let strings = sections.get(u32_at(section, 40) as usize).ok_or(ElfError::Truncated)?;
+        let strings = slice(bytes, u64_at(strings, 24), u64_at(strings, 32))?;
+
+        for symbol in slice(bytes, u64_at(section, 24), u64_at(section, 32))?.chunks_exact(SYMBOL_SIZE) {
+            let name = name_at(strings, u32_at(symbol, 0));
+            let kind = symbol[4] & 0xF;
+            let section_idx = u16_at(symbol, 6) as usize;
+            let in_code = sections
+                .get(section_idx)
+                .is_some_and(|section| u64_at(section, 8) & SHF_EXECINSTR != 0);
+
+            if name.is_empty() || name.starts_with(".L") || name.starts_with('$') {
+                continue;
+            }
+
+            // Symbols of relocatable objects are offsets into their section
+            let mut address = u64_at(symbol, 8);
+            if u16_at(header, 16) == ET_REL {
+                address += addresses.get(section_idx).copied().unwrap_or_default();
+            }
+
+            let entry = Symbol { name, address, size: u64_at(symbol, 16) };
+            match kind {
+                STT_FUNC => functions.push(entry),
+                STT_NOTYPE if in_code => labels.push(entry),
+                _ => (),
+            }
+        }
+    }
+
+    // Hand-written assembly seldom types its functions, so its labels stand for them
+    Ok(ElfImage {
+        entry,
+        segments,
+        symbols: SymbolTable::new(if functions.is_empty() { labels } else { functions }),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn section_header(kind: u32, flags: u64, address: u64, offset: usize, size: usize, link: u32, align: u64) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend(0u32.to_le_bytes());
+        header.extend(kind.to_le_bytes());
+        header.extend(flags.to_le_bytes());
+        header.extend(address.to_le_bytes());
+        header.extend((offset as u64).to_le_bytes());
+        header.extend((size as u64).to_le_bytes());
+        header.extend(link.to_le_bytes());
+        header.extend([0; 4]);
+        header.extend(align.to_le_bytes());
+        header.extend([0; 8]);
+        header
+    }
+
+    fn symbol(name: u32, info: u8, section: u16, value: u64, size: u64) -> Vec<u8> {
+        let mut entry = Vec::new();
+        entry.extend(name.to_le_bytes());
+        entry.extend([info, 0]);
+        entry.extend(section.to_le_bytes());
+        entry.extend(value.to_le_bytes());
+        entry.extend(size.to_le_bytes());
+        entry
+    }
+
+    // ELF header of an `kind` file with `program_headers` right after it and `sections` headers at `sections_at`
+    fn elf_header(kind: u16, entry: u64, program_headers: u16, sections_at: usize, sections: u16) -> Vec<u8> {
+        let mut elf = vec![0x7F, b'E', b'L', b'F', ELFCLASS64, ELFDATA2LSB, 1];
+        elf.resize(16, 0);
+        elf.extend(kind.to_le_bytes());
+        elf.extend(EM_RISCV.to_le_bytes());
+        elf.extend(1u32.to_le_bytes());
+        elf.extend(entry.to_le_bytes());
+        elf.extend(64u64.to_le_bytes());
+        elf.extend((sections_at as u64).to_le_bytes());
+        elf.extend([0; 4]);
+        elf.extend([64, 0, PROGRAM_HEADER_SIZE as u8, 0]);
+        elf.extend(program_headers.to_le_bytes());
+        elf.extend([SECTION_HEADER_SIZE as u8, 0]);
+        elf.extend(sections.to_le_bytes());
+        elf.extend([0; 2]);
+        elf
+    }
+
+    // An executable with a single segment holding `code` at 0x1000,
+    // and the symbols `main` at its start and `leaf` 8 bytes in
+    fn executable(code: &[u8]) -> Vec<u8> {
+        let strings = b"\0main\0leaf\0$x\0";
+        let symbol_table = [
+            vec![0; SYMBOL_SIZE],
+            symbol(1, 0x10 | STT_FUNC, 1, 0x1000, 8),
+            symbol(6, 0x10 | STT_FUNC, 1, 0x1008, 0),
+            symbol(11, STT_NOTYPE, 1, 0x1000, 0),
+        ].concat();
+
+        let code_at = 64 + PROGRAM_HEADER_SIZE;
+        let symbols_at = code_at + code.len();
+        let strings_at = symbols_at + symbol_table.len();
+        let sections_at = strings_at + strings.len();
+
+        let mut elf = elf_header(2, 0x1000, 1, sections_at, 4);
+
+        elf.extend(PT_LOAD.to_le_bytes());
+        elf.extend(5u32.to_le_bytes());
+        elf.extend((code_at as u64).to_le_bytes());
+        elf.extend(0x1000u64.to_le_bytes());
+        elf.extend(0x1000u64.to_le_bytes());
+        elf.extend((code.len() as u64).to_le_bytes());
+        elf.extend((code.len() as u64).to_le_bytes());
+        elf.extend(0x1000u64.to_le_bytes());
+
+        elf.extend(code);
+        elf.extend(&symbol_table);
+        elf.extend(strings);
+        elf.extend(vec![0; SECTION_HEADER_SIZE]);
+        elf.extend(section_header(1, SHF_ALLOC | SHF_EXECINSTR, 0x1000, code_at, code.len(), 0, 4));
+        elf.extend(section_header(SHT_SYMTAB, 0, 0, symbols_at, symbol_table.len(), 3, 8));
+        elf.extend(section_header(3, 0, 0, strings_at, strings.len(), 0, 1));
+
+        elf
+    }
+
+    // A relocatable object with, in order, 3 bytes of .rodata aligned to 8, 6 bytes of .text
+    // aligned to 4 starting with `main`, 16 bytes of .bss aligned to 16 and 4 bytes of .data
+    fn relocatable() -> Vec<u8> {
+        let (rodata, text, data) = ([1u8; 3], [2u8; 6], [3u8; 4]);
+        let strings = b"\0main\0";
+        let symbol_table = [vec![0; SYMBOL_SIZE], symbol(1, 0x10 | STT_FUNC, 2, 0, 6)].concat();
+
+        let contents = [&rodata[..], &text, &data, &symbol_table, strings].concat();
+        let at = |part: usize| 64 + [0, rodata.len(), text.len(), data.len(), symbol_table.len()][..=part].iter().sum::<usize>();
+
+        let mut elf = elf_header(ET_REL, 0, 0, 64 + contents.len(), 7);
+        elf.extend(&contents);
+        elf.extend(vec![0; SECTION_HEADER_SIZE]);
+        elf.extend(section_header(1, SHF_ALLOC, 0, at(0), rodata.len(), 0, 8));
+        elf.extend(section_header(1, SHF_ALLOC | SHF_EXECINSTR, 0, at(1), text.len(), 0, 4));
+        elf.extend(section_header(SHT_NOBITS, SHF_ALLOC, 0, at(3), 16, 0, 16));
+        elf.extend(section_header(1, SHF_ALLOC, 0, at(2), data.len(), 0, 4));
+        elf.extend(section_header(SHT_SYMTAB, 0, 0, at(3), symbol_table.len(), 6, 8));
+        elf.extend(section_header(3, 0, 0, at(4), strings.len(), 0, 1));
+
+        elf
+    }
+
+    #[test]
+    fn reads_segments_and_functions() {
+        let code = [0x13, 0, 0, 0, 0xEF, 0, 0x40, 0, 0x67, 0x80, 0, 0];
+        let image = parse_elf(&executable(&code)).unwrap();
+
+        assert_eq!(image.entry, 0x1000);
+        assert_eq!(image.segments, vec![(0x1000, code.to_vec())]);
+        assert_eq!(image.symbols.name_of(0x1004), "main");
+        assert_eq!(image.symbols.name_of(0x1010), "leaf");
+        assert_eq!(image.symbols.name_of(0x800), "0x800");
+    }
+
+    #[test]
+    fn lays_relocatable_sections_out_one_after_the_other() {
+        let image = parse_elf(&relocatable()).unwrap();
+
+        assert_eq!(image.segments, vec![(0, vec![1; 3]), (4, vec![2; 6]), (32, vec![3; 4])]);
+        assert_eq!(image.symbols.lookup(6).map(|symbol| (symbol.name.as_str(), symbol.address)), Some(("main", 4)));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(parse_elf(b"\x7fELF"), Err(ElfError::Header)));
+
+        let mut elf = executable(&[0; 4]);
+        elf.truncate(100);
+        assert!(matches!(parse_elf(&elf), Err(ElfError::Truncated)));
+    }
+}
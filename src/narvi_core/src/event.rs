@@ -4,12 +4,230 @@ use crate::{
     ModuleId
 };
 
+/// Kind of memory access that reached a cache level
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessKind {
+    Fetch,
+    Load,
+    Store,
+    /// Atomic read-modify-write. Caches serve it as a store. Harts do not execute the A
+    /// extension, so only replayed traces issue atomics and only those record them
+    Amo
+}
+
+/// Operation an atomic memory access applies to the value in memory
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AmoOp {
+    Swap,
+    Add,
+    Xor,
+    And,
+    Or,
+    Min,
+    Max,
+    MinU,
+    MaxU
+}
+
+impl AmoOp {
+    /// Bytes left in memory once the operation combines the little-endian `old` value with `operand`
+    pub fn apply(self, old: &[u8], operand: &[u8]) -> Vec<u8> {
+        assert_eq!(old.len(), operand.len(), "atomic operands of different sizes");
+
+        let bits = old.len() as u32 * 8;
+        let value = |bytes: &[u8]| bytes.iter().rev().fold(0u64, |value, byte| value << 8 | *byte as u64);
+        let signed = |value: u64| ((value << (64 - bits)) as i64) >> (64 - bits);
+        let (old, operand) = (value(old), value(operand));
+
+        let new = match self {
+            Self::Swap => operand,
+            Self::Add => old.wrapping_add(operand),
+            Self::Xor => old ^ operand,
+            Self::And => old & operand,
+            Self::Or => old | operand,
+            Self::Min => if signed(operand) < signed(old) { operand } else { old },
+            Self::Max => if signed(operand) > signed(old) { operand } else { old },
+            Self::MinU => old.min(operand),
+            Self::MaxU => old.max(operand),
+        };
+
+        new.to_le_bytes()[..bits as usize / 8].to_vec()
+    }
+}
+
+/// Why a cache missed, in the three Cs model
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MissClass {
+    /// First access to the block
+    Compulsory,
+    /// A fully associative cache of the same size would have missed too
+    Capacity,
+    /// Any other miss, due to blocks competing for the same set
+    Conflict
+}
+
+/// Coherence state of a cache line, as in the MOESI protocol family
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum CoherenceState {
+    Modified,
+    Owned,
+    Exclusive,
+    Shared,
+    #[default]
+    Invalid
+}
+
+impl CoherenceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Modified => "M",
+            Self::Owned => "O",
+            Self::Exclusive => "E",
+            Self::Shared => "S",
+            Self::Invalid => "I"
+        }
+    }
+}
+
+/// Coherence transactions a cache may request for a block
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CoherenceRequest {
+    Read,
+    ReadExclusive,
+    Upgrade
+}
+
+impl CoherenceRequest {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "BusRd",
+            Self::ReadExclusive => "BusRdX",
+            Self::Upgrade => "BusUpgr"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterFile {
+    /// Integer registers
+    X,
+    /// Floating point registers, their raw bits being written back
+    F,
+}
+
+/// What an executed instruction did, for instruction mixes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionClass {
+    /// Integer arithmetic, logic, shifts and comparisons of the base ISA
+    Alu,
+    BranchTaken,
+    BranchNotTaken,
+    /// jal and jalr
+    Jump,
+    /// Loads of any extension, floating point ones included
+    Load,
+    /// Stores of any extension, floating point ones included
+    Store,
+    M,
+    F,
+    D,
+    Csr,
+    /// ecall, ebreak and fences
+    System,
+}
+
+impl InstructionClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Alu => "alu",
+            Self::BranchTaken => "branch_taken",
+            Self::BranchNotTaken => "branch_not_taken",
+            Self::Jump => "jump",
+            Self::Load => "load",
+            Self::Store => "store",
+            Self::M => "m",
+            Self::F => "f",
+            Self::D => "d",
+            Self::Csr => "csr",
+            Self::System => "system",
+        }
+    }
+}
+
 pub enum JournalEvent {
-    CacheHit,
-    CacheMiss,
+    CacheHit { kind: AccessKind },
+    /// `pc` is that of the instruction behind the access, when known
+    CacheMiss { kind: AccessKind, class: Option<MissClass>, pc: Option<u64> },
+    /// A block of `bytes` was brought in from the level below
+    CacheFill { bytes: usize },
+    /// A valid block was replaced to make room for another
+    CacheEviction,
+    /// A dirty block of `bytes` was written back to the level below, on eviction or snoop
+    CacheWriteback { bytes: usize },
+    /// A store of `bytes` was forwarded to the level below by a write-through cache
+    CacheWriteThrough { bytes: usize },
     Cycles { cycles: usize },
     CyclesLost { cycles: usize },
-    HartInstruction
+    /// The instruction `inst` at `pc` was executed
+    HartInstruction { pc: u64, inst: u32, class: InstructionClass, mnemonic: &'static str },
+    /// The instruction last executed wrote `value` to a register, once it had the value
+    HartWriteback { file: RegisterFile, index: u8, value: u64 },
+    CoherenceTransition { from: CoherenceState, to: CoherenceState },
+    CoherenceTransaction { request: CoherenceRequest },
+    Invalidation,
+    Intervention,
+    /// A miss took an MSHR, leaving `occupancy` of them in use
+    MshrAllocation { occupancy: usize },
+    /// A miss to a block already on its way was merged into its MSHR
+    MshrMerge,
+    /// A request found every MSHR in use and had to wait
+    MshrStall,
+    /// The prefetcher sent a block request down
+    PrefetchIssued,
+    /// A demand access hit a prefetched block for the first time
+    PrefetchUseful,
+    /// A demand access missed on a block whose prefetch was still on its way
+    PrefetchLate,
+    /// A prefetched block left the cache without being used
+    PrefetchUseless,
+    /// A demand access reached a cache recording its block stream
+    BlockAccess { block: usize },
+    /// A DRAM access moving `bytes`, after waiting `queued` cycles in the controller
+    DramAccess { row: RowBufferOutcome, queued: u64, bytes: usize },
+    DramRefresh
+}
+
+/// Update to a statistic a module publishes under its own name, for the ones
+/// that are not worth a `JournalEvent`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatUpdate {
+    /// Adds to a counter
+    Count(u128),
+    /// Adds a sample to an average
+    Sample(f64),
+    /// Adds a value to a distribution
+    Distribute(u64),
+}
+
+/// What a DRAM access found in the row buffer of its bank
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowBufferOutcome {
+    /// The row was already open
+    Hit,
+    /// No row was open
+    Miss,
+    /// Another row had to be closed first
+    Conflict,
+}
+
+impl RowBufferOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hit => "hit",
+            Self::Miss => "miss",
+            Self::Conflict => "conflict",
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -21,9 +239,25 @@ pub enum Target {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum EventPayload {
     HartExecute,
-    MemoryLoadReq { address: usize, size_in_bytes: usize, requester: Target },
-    MemoryLoadRes { data: Vec<u8> },
-    MemoryStoreReq { address: usize, data: Vec<u8> },
+    // Memory requests carry the PC of the instruction behind them, when there is one
+    MemoryFetchReq { address: usize, size_in_bytes: usize, requester: Target, pc: Option<u64> },
+    MemoryLoadReq { address: usize, size_in_bytes: usize, requester: Target, pc: Option<u64> },
+    MemoryLoadRes { address: usize, data: Vec<u8> },
+    MemoryStoreReq { address: usize, data: Vec<u8>, pc: Option<u64> },
+    /// Applies `op` with the `data` operand, answered like a load with the bytes it replaced.
+    /// Only trace replayers send it, as harts reject the instructions of the A extension
+    MemoryAmoReq { address: usize, op: AmoOp, data: Vec<u8>, requester: Target, pc: Option<u64> },
+    CoherenceReq { address: usize, request: CoherenceRequest, requester: Target },
+    CoherenceSnoop { address: usize, request: CoherenceRequest, requester: Target },
+    CoherenceSnoopRes { address: usize, shared: bool, data: Option<Vec<u8>> },
+    CoherenceGrant { address: usize, shared: bool, data: Option<Vec<u8>> },
+    CoherenceDone { address: usize },
+    /// Tells a directory that `requester` no longer holds the block
+    CoherenceEvict { address: usize, requester: Target },
+    /// Wakes a DRAM controller up to issue its next request
+    DramSchedule,
+    /// Wakes a trace replayer up to issue its next access
+    ReplayNext,
     Reset
 }
 
@@ -31,9 +265,19 @@ impl EventPayload {
     fn as_str(&self) -> &'static str {
         match self {
             Self::HartExecute => "HartExecute",
+            Self::MemoryFetchReq { .. } => "MemoryFetchReq",
             Self::MemoryLoadReq { .. } => "MemoryLoadReq",
             Self::MemoryLoadRes { .. } => "MemoryLoadRes",
             Self::MemoryStoreReq { .. } => "MemoryStoreReq",
+            Self::MemoryAmoReq { .. } => "MemoryAmoReq",
+            Self::CoherenceReq { .. } => "CoherenceReq",
+            Self::CoherenceSnoop { .. } => "CoherenceSnoop",
+            Self::CoherenceSnoopRes { .. } => "CoherenceSnoopRes",
+            Self::CoherenceGrant { .. } => "CoherenceGrant",
+            Self::CoherenceDone { .. } => "CoherenceDone",
+            Self::CoherenceEvict { .. } => "CoherenceEvict",
+            Self::DramSchedule => "DramSchedule",
+            Self::ReplayNext => "ReplayNext",
             Self::Reset => "Reset"
         }
     }
@@ -95,3 +339,18 @@ impl Event {
         &self.payload
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn amo_ops_respect_the_access_width() {
+        let word = |value: i32| value.to_le_bytes().to_vec();
+
+        assert_eq!(AmoOp::Add.apply(&word(-1), &word(1)), word(0));
+        assert_eq!(AmoOp::Min.apply(&word(-5), &word(3)), word(-5));
+        assert_eq!(AmoOp::MaxU.apply(&word(-5), &word(3)), word(-5));
+        assert_eq!(AmoOp::Swap.apply(&[1, 2], &[3, 4]), vec![3, 4]);
+    }
+}
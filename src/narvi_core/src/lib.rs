@@ -1,8 +1,10 @@
-use crate::event::{Event, JournalEvent, EventPayload, Target};
+use crate::event::{Event, JournalEvent, EventPayload, StatUpdate, Target};
 
 pub mod event;
 pub mod bytes;
+pub mod elf;
 pub mod serialization;
+pub mod trace;
 
 pub type ModuleId = usize;
 
@@ -10,6 +12,11 @@ pub trait EngineContext {
     fn schedule(&mut self, timestamp: u64, target: Target, payload: EventPayload);
 
     fn record_journal(&mut self, event: JournalEvent);
+
+    /// Updates the statistic `name` of the current module, kept as `system.<module>.<name>`
+    fn record_stat(&mut self, name: &str, update: StatUpdate);
+
+    fn current_time(&self) -> u64;
 }
 
 pub trait Module { 
@@ -22,6 +29,16 @@ pub enum CacheReplacementPolicy {
     LFU,
     FIFO,
     Random,
+    /// Tree pseudo-LRU, for a power of two ways
+    TreePLRU,
+    /// Not recently used
+    NRU,
+    /// Static re-reference interval prediction
+    SRRIP,
+    /// Bimodal re-reference interval prediction
+    BRRIP,
+    /// Dynamic RRIP, set dueling between SRRIP and BRRIP
+    DRRIP,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -35,9 +52,143 @@ pub struct CacheLevelConfig {
     pub n_blocks: usize,
     pub block_size: usize,
     pub set_size: usize,
+    /// Number of harts sharing each instance of this level
     pub share_config: u8,
     pub replacement_policy: CacheReplacementPolicy,
-    pub write_policy: CacheWritePolicy
+    pub write_policy: CacheWritePolicy,
+    /// Whether each instance is split into separate instruction and data caches
+    pub split: bool,
+    /// Number of misses each instance can have outstanding at once
+    pub mshrs: usize,
+    pub latency: CacheLatency,
+    pub prefetcher: Option<PrefetcherConfig>,
+    /// Records the block addresses reaching each instance, to report the misses
+    /// Belady's optimal replacement would have had over them at the end of the run
+    pub belady: bool,
+    /// Collects the reuse and LRU stack distance histograms of the blocks reaching each instance
+    pub reuse_histogram: bool,
+    /// Sorts the misses of each instance into compulsory, capacity and conflict misses,
+    /// at the cost of a fully associative shadow cache per instance
+    pub classify_misses: bool
+}
+
+/// Hardware prefetcher attached to each instance of a cache level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefetcherConfig {
+    /// Fetches the `degree` blocks following every miss
+    NextLine { degree: usize },
+    /// Tracks the stride of up to `table_size` load and store instructions, indexed by PC
+    Stride { table_size: usize, degree: usize },
+    /// Follows up to `streams` sequential miss streams, running `distance` blocks ahead
+    Stream { streams: usize, distance: usize, degree: usize },
+}
+
+/// Cycles spent by a cache level on each step of an access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheLatency {
+    /// Until a hit answers
+    pub hit: u64,
+    /// Looking up the tags, before a miss is sent down
+    pub tag: u64,
+    /// Writing a block into the data array, before the requests waiting on it are answered
+    pub data: u64,
+    /// Added to every miss on top of the time spent by the levels below
+    pub miss_penalty: u64,
+}
+
+impl Default for CacheLatency {
+    fn default() -> Self {
+        Self {
+            hit: 1,
+            tag: 1,
+            data: 1,
+            miss_penalty: 0
+        }
+    }
+}
+
+/// Cycles the RAM takes to answer a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RamLatency {
+    Fixed { cycles: u64 },
+    /// Uniformly distributed between `min` and `max`, both included
+    Variable { min: u64, max: u64 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagePolicy {
+    /// Rows stay open after an access, betting on the next one hitting them
+    Open,
+    /// Rows are precharged right after every access
+    Closed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DramScheduler {
+    /// First come, first served
+    Fcfs,
+    /// First ready, first come, first served: row buffer hits go before older requests
+    FrFcfs
+}
+
+/// DRAM timing parameters, in cycles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DramTiming {
+    /// Row activation to column access
+    pub t_rcd: u64,
+    /// Precharge
+    pub t_rp: u64,
+    /// Column access to data
+    pub t_cas: u64,
+    /// Minimum time a row stays open after being activated
+    pub t_ras: u64,
+    /// Refresh duration
+    pub t_rfc: u64,
+    /// Interval between refreshes
+    pub t_refi: u64,
+    /// Cycles a transfer keeps the data bus busy
+    pub t_burst: u64,
+}
+
+/// Organization and timing of the DRAM behind each memory controller, one channel each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DramConfig {
+    pub ranks: usize,
+    pub bank_groups: usize,
+    pub banks_per_group: usize,
+    /// Bytes in a row of a bank
+    pub row_size: usize,
+    pub page_policy: PagePolicy,
+    pub scheduler: DramScheduler,
+    pub timing: DramTiming,
+}
+
+impl Default for DramConfig {
+    fn default() -> Self {
+        Self {
+            ranks: 1,
+            bank_groups: 4,
+            banks_per_group: 4,
+            row_size: 1024,
+            page_policy: PagePolicy::Open,
+            scheduler: DramScheduler::FrFcfs,
+            timing: DramTiming {
+                t_rcd: 14,
+                t_rp: 14,
+                t_cas: 14,
+                t_ras: 32,
+                t_rfc: 350,
+                t_refi: 7800,
+                t_burst: 4,
+            }
+        }
+    }
+}
+
+impl Default for RamLatency {
+    fn default() -> Self {
+        Self::Fixed { cycles: 1 }
+    }
 }
 
 impl CacheLevelConfig {
@@ -55,11 +206,44 @@ impl CacheLevelConfig {
             set_size,
             share_config,
             replacement_policy,
-            write_policy
+            write_policy,
+            split: false,
+            mshrs: 4,
+            latency: CacheLatency::default(),
+            prefetcher: None,
+            belady: false,
+            reuse_histogram: false,
+            classify_misses: false
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoherenceProtocol {
+    Mesi,
+    Moesi
+}
+
+/// How a directory remembers which caches hold a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharerFormat {
+    FullBitVector,
+    /// Tracks up to `pointers` sharers, broadcasting once more caches share the block
+    LimitedPointer { pointers: u8 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoherenceMechanism {
+    Snooping,
+    Directory { sharers: SharerFormat }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CoherenceConfig {
+    pub protocol: CoherenceProtocol,
+    pub mechanism: CoherenceMechanism
+}
+
 #[allow(dead_code, unused_variables)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Extensions {
@@ -10,15 +10,35 @@ use std::fs::File;
 use std::io::Write;
 
 use crate::{
+    CacheLatency,
     CacheLevelConfig,
     CacheReplacementPolicy,
     CacheWritePolicy,
-    Extensions
+    CoherenceConfig,
+    CoherenceMechanism,
+    CoherenceProtocol,
+    DramConfig,
+    DramScheduler,
+    DramTiming,
+    Extensions,
+    PagePolicy,
+    PrefetcherConfig,
+    RamLatency,
+    SharerFormat
 };
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum MachineError {
-    InvalidConfig,
+    /// A machine that cannot be built, for the reason given
+    InvalidConfig(&'static str),
+}
+
+impl std::fmt::Display for MachineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidConfig(reason) => write!(f, "invalid machine config: {reason}"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -26,7 +46,18 @@ pub struct MachineConfig {
     pub hart_count: u8,
     pub extensions: Extensions,
     pub ram_size: usize,
-    pub cache_config: Vec<CacheLevelConfig>
+    pub ram_latency: RamLatency,
+    /// Seeds every random draw of the run, such as variable RAM latencies, so that runs are reproducible
+    pub seed: u64,
+    /// Models DRAM timing behind each memory controller instead of a flat `ram_latency`
+    pub dram: Option<DramConfig>,
+    /// Number of memory controllers the RAM is split across
+    pub memory_controllers: u8,
+    /// Amount of contiguous bytes mapped to a controller before moving to the next one
+    pub interleave_size: usize,
+    pub cache_config: Vec<CacheLevelConfig>,
+    /// Keeps private cache instances coherent when set
+    pub coherence: Option<CoherenceConfig>
 }
 
 impl Default for MachineConfig {
@@ -35,22 +66,141 @@ impl Default for MachineConfig {
             hart_count: 1,
             extensions: Extensions::default(),
             ram_size: 16384,
+            ram_latency: RamLatency::Fixed { cycles: 100 },
+            seed: 0,
+            dram: None,
+            memory_controllers: 1,
+            interleave_size: 4096,
             cache_config: {
                 let r = CacheReplacementPolicy::LRU;
                 let w = CacheWritePolicy::WriteBack;
                 vec![
-                    CacheLevelConfig::new(4, 64, 4, 1, r, w),
-                    CacheLevelConfig::new(16, 64, 8, 2, r, w),
-                    CacheLevelConfig::new(64, 64, 16, 4, r, w)
+                    CacheLevelConfig {
+                        split: true,
+                        latency: CacheLatency { hit: 2, tag: 1, data: 1, miss_penalty: 0 },
+                        ..CacheLevelConfig::new(4, 64, 4, 1, r, w)
+                    },
+                    CacheLevelConfig {
+                        latency: CacheLatency { hit: 10, tag: 3, data: 4, miss_penalty: 1 },
+                        ..CacheLevelConfig::new(16, 64, 8, 2, r, w)
+                    },
+                    CacheLevelConfig {
+                        latency: CacheLatency { hit: 30, tag: 8, data: 12, miss_penalty: 2 },
+                        ..CacheLevelConfig::new(64, 64, 16, 4, r, w)
+                    }
                 ]
-            }
+            },
+            coherence: Some(CoherenceConfig {
+                protocol: CoherenceProtocol::Mesi,
+                mechanism: CoherenceMechanism::Snooping
+            })
         }
     }
 }
 
 impl MachineConfig {
     pub fn is_valid(&self) -> bool {
-        !self.cache_config.is_empty()
+        self.validate().is_ok()
+    }
+
+    /// Checks whether the described memory hierarchy can actually be built
+    pub fn validate(&self) -> Result<(), MachineError> {
+        if self.hart_count == 0 || self.cache_config.is_empty() {
+            return Err(MachineError::InvalidConfig("a machine needs at least one hart and one cache level"));
+        }
+
+        if self.memory_controllers == 0 || !self.interleave_size.is_power_of_two() {
+            return Err(MachineError::InvalidConfig("memory_controllers must be nonzero and interleave_size a power of two"));
+        }
+
+        // Controllers own whole chunks, each holding whole blocks of the level above them
+        if !self.ram_size.is_multiple_of(self.interleave_size)
+            || self.cache_config.last().is_some_and(|level| level.block_size > self.interleave_size)
+        {
+            return Err(MachineError::InvalidConfig("ram_size must be a multiple of interleave_size, which must hold a whole block of the last cache level"));
+        }
+
+        let mut previous_share = 1;
+        let mut previous_split = true;
+
+        // Every instance of a level must be backed by a single instance of the next one,
+        // and a split level can never be placed below a unified one
+        for level in &self.cache_config {
+            if level.share_config == 0 || level.share_config % previous_share != 0 {
+                return Err(MachineError::InvalidConfig("every cache level must be shared by a nonzero multiple of the harts sharing the previous one"));
+            }
+            if level.split && !previous_split {
+                return Err(MachineError::InvalidConfig("a split cache level cannot be placed below a unified one"));
+            }
+            if level.mshrs == 0 || level.latency.hit == 0 {
+                return Err(MachineError::InvalidConfig("cache levels need at least one MSHR and a nonzero hit latency"));
+            }
+            if matches!(level.replacement_policy, CacheReplacementPolicy::TreePLRU)
+                && !level.set_size.is_power_of_two()
+            {
+                return Err(MachineError::InvalidConfig("TreePLRU needs a power of two set size"));
+            }
+            if let Some(
+                PrefetcherConfig::NextLine { degree } |
+                PrefetcherConfig::Stride { degree, .. } |
+                PrefetcherConfig::Stream { degree, .. }
+            ) = level.prefetcher
+                && degree == 0
+            {
+                return Err(MachineError::InvalidConfig("prefetchers need a nonzero degree"));
+            }
+            if let Some(
+                PrefetcherConfig::Stride { table_size: 0, .. } |
+                PrefetcherConfig::Stream { streams: 0, .. } |
+                PrefetcherConfig::Stream { distance: 0, .. }
+            ) = level.prefetcher {
+                return Err(MachineError::InvalidConfig("stride prefetchers need a nonzero table size, and stream prefetchers nonzero streams and distance"));
+            }
+            previous_share = level.share_config;
+            previous_split = level.split;
+        }
+
+        if let Some(CoherenceConfig {
+            mechanism: CoherenceMechanism::Directory { sharers: SharerFormat::LimitedPointer { pointers: 0 } },
+            ..
+        }) = self.coherence {
+            return Err(MachineError::InvalidConfig("limited pointer directories need at least one pointer"));
+        }
+
+        if let RamLatency::Variable { min, max } = self.ram_latency
+            && min > max
+        {
+            return Err(MachineError::InvalidConfig("the minimum variable RAM latency cannot exceed the maximum"));
+        }
+
+        if let Some(dram) = &self.dram
+            && (dram.ranks == 0 || dram.bank_groups == 0 || dram.banks_per_group == 0
+                || !dram.row_size.is_power_of_two() || dram.timing.t_refi <= dram.timing.t_rfc)
+        {
+            return Err(MachineError::InvalidConfig("DRAM needs nonzero ranks, bank groups and banks, a power of two row size, and t_refi above t_rfc"));
+        }
+
+        Ok(())
+    }
+
+    /// Number of instances of a cache level, one for each group of `share_config` harts
+    pub fn instances_of(&self, level: usize) -> usize {
+        (self.hart_count as usize).div_ceil(self.cache_config[level].share_config as usize)
+    }
+
+    /// Cycles a directory keeping `level` coherent takes to look a block up. The directory lives
+    /// at the home node of the level's blocks: the tags of the level below, or the memory
+    /// controllers below the last level, where reading an entry costs a row access
+    pub fn home_latency(&self, level: usize) -> u64 {
+        if let Some(below) = self.cache_config.get(level + 1) {
+            return below.latency.tag;
+        }
+
+        match (self.dram, self.ram_latency) {
+            (Some(dram), _) => dram.timing.t_rcd + dram.timing.t_cas,
+            (None, RamLatency::Fixed { cycles }) => cycles,
+            (None, RamLatency::Variable { min, max }) => (min + max) / 2,
+        }
     }
 }
 
@@ -59,7 +209,35 @@ struct MachineConfigData {
     hart_count: u8,
     extensions: ExtensionsData,
     ram_size: usize,
+    #[serde(default = "default_ram_latency")]
+    ram_latency: RamLatencyData,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    dram: Option<DramConfigData>,
+    #[serde(default = "default_memory_controllers")]
+    memory_controllers: u8,
+    #[serde(default = "default_interleave_size")]
+    interleave_size: usize,
     cache_config: Vec<CacheLevelConfigData>,
+    #[serde(default)]
+    coherence: Option<CoherenceConfigData>,
+}
+
+fn default_ram_latency() -> RamLatencyData {
+    RamLatencyData::Fixed { cycles: 100 }
+}
+
+fn default_memory_controllers() -> u8 {
+    1
+}
+
+fn default_interleave_size() -> usize {
+    4096
+}
+
+fn default_mshrs() -> usize {
+    4
 }
 
 #[derive(Serialize, Deserialize)]
@@ -79,14 +257,125 @@ struct CacheLevelConfigData {
     share_config: u8,
     replacement_policy: CacheReplacementPolicyData,
     write_policy: CacheWritePolicyData,
+    #[serde(default)]
+    split: bool,
+    #[serde(default = "default_mshrs")]
+    mshrs: usize,
+    #[serde(default)]
+    latency: CacheLatencyData,
+    #[serde(default)]
+    prefetcher: Option<PrefetcherConfigData>,
+    #[serde(default)]
+    belady: bool,
+    #[serde(default)]
+    reuse_histogram: bool,
+    #[serde(default)]
+    classify_misses: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+enum PrefetcherConfigData {
+    NextLine { degree: usize },
+    Stride { table_size: usize, degree: usize },
+    Stream { streams: usize, distance: usize, degree: usize },
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheLatencyData {
+    hit: u64,
+    tag: u64,
+    data: u64,
+    miss_penalty: u64,
+}
+
+impl Default for CacheLatencyData {
+    fn default() -> Self {
+        Self::from(CacheLatency::default())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DramConfigData {
+    ranks: usize,
+    bank_groups: usize,
+    banks_per_group: usize,
+    row_size: usize,
+    page_policy: PagePolicyData,
+    scheduler: DramSchedulerData,
+    timing: DramTimingData,
+}
+
+#[derive(Serialize, Deserialize)]
+enum PagePolicyData {
+    Open,
+    Closed,
+}
+
+#[derive(Serialize, Deserialize)]
+enum DramSchedulerData {
+    Fcfs,
+    FrFcfs,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DramTimingData {
+    t_rcd: u64,
+    t_rp: u64,
+    t_cas: u64,
+    t_ras: u64,
+    t_rfc: u64,
+    t_refi: u64,
+    t_burst: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+enum RamLatencyData {
+    Fixed { cycles: u64 },
+    Variable { min: u64, max: u64 },
 }
 
+// Variant names are the ones written in configuration files
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Serialize, Deserialize)]
 enum CacheReplacementPolicyData {
     LRU,
     LFU,
     FIFO,
     Random,
+    TreePLRU,
+    NRU,
+    SRRIP,
+    BRRIP,
+    DRRIP,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CoherenceConfigData {
+    protocol: CoherenceProtocolData,
+    #[serde(default = "default_coherence_mechanism")]
+    mechanism: CoherenceMechanismData,
+}
+
+fn default_coherence_mechanism() -> CoherenceMechanismData {
+    CoherenceMechanismData::Snooping
+}
+
+#[derive(Serialize, Deserialize)]
+enum CoherenceMechanismData {
+    Snooping,
+    Directory { sharers: SharerFormatData },
+}
+
+#[derive(Serialize, Deserialize)]
+enum SharerFormatData {
+    FullBitVector,
+    LimitedPointer { pointers: u8 },
+}
+
+#[derive(Serialize, Deserialize)]
+enum CoherenceProtocolData {
+    Mesi,
+    Moesi,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -101,9 +390,15 @@ impl From<&MachineConfig> for MachineConfigData {
             hart_count: config.hart_count,
             extensions: ExtensionsData::from(&config.extensions),
             ram_size: config.ram_size,
+            ram_latency: RamLatencyData::from(config.ram_latency),
+            seed: config.seed,
+            dram: config.dram.as_ref().map(DramConfigData::from),
+            memory_controllers: config.memory_controllers,
+            interleave_size: config.interleave_size,
             cache_config: config.cache_config.iter()
                 .map(CacheLevelConfigData::from)
                 .collect(),
+            coherence: config.coherence.as_ref().map(CoherenceConfigData::from),
         }
     }
 }
@@ -114,9 +409,15 @@ impl From<MachineConfigData> for MachineConfig {
             hart_count: data.hart_count,
             extensions: Extensions::from(data.extensions),
             ram_size: data.ram_size,
+            ram_latency: RamLatency::from(data.ram_latency),
+            seed: data.seed,
+            dram: data.dram.map(DramConfig::from),
+            memory_controllers: data.memory_controllers,
+            interleave_size: data.interleave_size,
             cache_config: data.cache_config.into_iter()
                 .map(CacheLevelConfig::from)
                 .collect(),
+            coherence: data.coherence.map(CoherenceConfig::from),
         }
     }
 }
@@ -154,20 +455,152 @@ impl From<&CacheLevelConfig> for CacheLevelConfigData {
             share_config: config.share_config,
             replacement_policy: CacheReplacementPolicyData::from(config.replacement_policy),
             write_policy: CacheWritePolicyData::from(config.write_policy),
+            split: config.split,
+            mshrs: config.mshrs,
+            latency: CacheLatencyData::from(config.latency),
+            prefetcher: config.prefetcher.map(PrefetcherConfigData::from),
+            belady: config.belady,
+            reuse_histogram: config.reuse_histogram,
+            classify_misses: config.classify_misses,
         }
     }
 }
 
 impl From<CacheLevelConfigData> for CacheLevelConfig {
     fn from(data: CacheLevelConfigData) -> Self {
-        Self::new(
-            data.n_blocks,
-            data.block_size,
-            data.set_size,
-            data.share_config,
-            CacheReplacementPolicy::from(data.replacement_policy),
-            CacheWritePolicy::from(data.write_policy),
-        )
+        Self {
+            split: data.split,
+            mshrs: data.mshrs,
+            latency: CacheLatency::from(data.latency),
+            prefetcher: data.prefetcher.map(PrefetcherConfig::from),
+            belady: data.belady,
+            reuse_histogram: data.reuse_histogram,
+            classify_misses: data.classify_misses,
+            ..Self::new(
+                data.n_blocks,
+                data.block_size,
+                data.set_size,
+                data.share_config,
+                CacheReplacementPolicy::from(data.replacement_policy),
+                CacheWritePolicy::from(data.write_policy),
+            )
+        }
+    }
+}
+
+impl From<CacheLatency> for CacheLatencyData {
+    fn from(latency: CacheLatency) -> Self {
+        Self {
+            hit: latency.hit,
+            tag: latency.tag,
+            data: latency.data,
+            miss_penalty: latency.miss_penalty,
+        }
+    }
+}
+
+impl From<CacheLatencyData> for CacheLatency {
+    fn from(data: CacheLatencyData) -> Self {
+        Self {
+            hit: data.hit,
+            tag: data.tag,
+            data: data.data,
+            miss_penalty: data.miss_penalty,
+        }
+    }
+}
+
+impl From<PrefetcherConfig> for PrefetcherConfigData {
+    fn from(config: PrefetcherConfig) -> Self {
+        match config {
+            PrefetcherConfig::NextLine { degree } => Self::NextLine { degree },
+            PrefetcherConfig::Stride { table_size, degree } => Self::Stride { table_size, degree },
+            PrefetcherConfig::Stream { streams, distance, degree } => Self::Stream { streams, distance, degree },
+        }
+    }
+}
+
+impl From<PrefetcherConfigData> for PrefetcherConfig {
+    fn from(data: PrefetcherConfigData) -> Self {
+        match data {
+            PrefetcherConfigData::NextLine { degree } => Self::NextLine { degree },
+            PrefetcherConfigData::Stride { table_size, degree } => Self::Stride { table_size, degree },
+            PrefetcherConfigData::Stream { streams, distance, degree } => Self::Stream { streams, distance, degree },
+        }
+    }
+}
+
+impl From<&DramConfig> for DramConfigData {
+    fn from(config: &DramConfig) -> Self {
+        Self {
+            ranks: config.ranks,
+            bank_groups: config.bank_groups,
+            banks_per_group: config.banks_per_group,
+            row_size: config.row_size,
+            page_policy: match config.page_policy {
+                PagePolicy::Open => PagePolicyData::Open,
+                PagePolicy::Closed => PagePolicyData::Closed,
+            },
+            scheduler: match config.scheduler {
+                DramScheduler::Fcfs => DramSchedulerData::Fcfs,
+                DramScheduler::FrFcfs => DramSchedulerData::FrFcfs,
+            },
+            timing: DramTimingData {
+                t_rcd: config.timing.t_rcd,
+                t_rp: config.timing.t_rp,
+                t_cas: config.timing.t_cas,
+                t_ras: config.timing.t_ras,
+                t_rfc: config.timing.t_rfc,
+                t_refi: config.timing.t_refi,
+                t_burst: config.timing.t_burst,
+            },
+        }
+    }
+}
+
+impl From<DramConfigData> for DramConfig {
+    fn from(data: DramConfigData) -> Self {
+        Self {
+            ranks: data.ranks,
+            bank_groups: data.bank_groups,
+            banks_per_group: data.banks_per_group,
+            row_size: data.row_size,
+            page_policy: match data.page_policy {
+                PagePolicyData::Open => PagePolicy::Open,
+                PagePolicyData::Closed => PagePolicy::Closed,
+            },
+            scheduler: match data.scheduler {
+                DramSchedulerData::Fcfs => DramScheduler::Fcfs,
+                DramSchedulerData::FrFcfs => DramScheduler::FrFcfs,
+            },
+            timing: DramTiming {
+                t_rcd: data.timing.t_rcd,
+                t_rp: data.timing.t_rp,
+                t_cas: data.timing.t_cas,
+                t_ras: data.timing.t_ras,
+                t_rfc: data.timing.t_rfc,
+                t_refi: data.timing.t_refi,
+                t_burst: data.timing.t_burst,
+            },
+        }
+    }
+}
+
+impl From<RamLatency> for RamLatencyData {
+    fn from(latency: RamLatency) -> Self {
+        match latency {
+            RamLatency::Fixed { cycles } => Self::Fixed { cycles },
+            RamLatency::Variable { min, max } => Self::Variable { min, max },
+        }
+    }
+}
+
+impl From<RamLatencyData> for RamLatency {
+    fn from(data: RamLatencyData) -> Self {
+        match data {
+            RamLatencyData::Fixed { cycles } => Self::Fixed { cycles },
+            RamLatencyData::Variable { min, max } => Self::Variable { min, max },
+        }
     }
 }
 
@@ -178,6 +611,11 @@ impl From<CacheReplacementPolicy> for CacheReplacementPolicyData {
             CacheReplacementPolicy::LFU => Self::LFU,
             CacheReplacementPolicy::FIFO => Self::FIFO,
             CacheReplacementPolicy::Random => Self::Random,
+            CacheReplacementPolicy::TreePLRU => Self::TreePLRU,
+            CacheReplacementPolicy::NRU => Self::NRU,
+            CacheReplacementPolicy::SRRIP => Self::SRRIP,
+            CacheReplacementPolicy::BRRIP => Self::BRRIP,
+            CacheReplacementPolicy::DRRIP => Self::DRRIP,
         }
     }
 }
@@ -189,6 +627,75 @@ impl From<CacheReplacementPolicyData> for CacheReplacementPolicy {
             CacheReplacementPolicyData::LFU => Self::LFU,
             CacheReplacementPolicyData::FIFO => Self::FIFO,
             CacheReplacementPolicyData::Random => Self::Random,
+            CacheReplacementPolicyData::TreePLRU => Self::TreePLRU,
+            CacheReplacementPolicyData::NRU => Self::NRU,
+            CacheReplacementPolicyData::SRRIP => Self::SRRIP,
+            CacheReplacementPolicyData::BRRIP => Self::BRRIP,
+            CacheReplacementPolicyData::DRRIP => Self::DRRIP,
+        }
+    }
+}
+
+impl From<&CoherenceConfig> for CoherenceConfigData {
+    fn from(config: &CoherenceConfig) -> Self {
+        Self {
+            protocol: CoherenceProtocolData::from(config.protocol),
+            mechanism: CoherenceMechanismData::from(config.mechanism),
+        }
+    }
+}
+
+impl From<CoherenceConfigData> for CoherenceConfig {
+    fn from(data: CoherenceConfigData) -> Self {
+        Self {
+            protocol: CoherenceProtocol::from(data.protocol),
+            mechanism: CoherenceMechanism::from(data.mechanism),
+        }
+    }
+}
+
+impl From<CoherenceMechanism> for CoherenceMechanismData {
+    fn from(mechanism: CoherenceMechanism) -> Self {
+        match mechanism {
+            CoherenceMechanism::Snooping => Self::Snooping,
+            CoherenceMechanism::Directory { sharers } => Self::Directory {
+                sharers: match sharers {
+                    SharerFormat::FullBitVector => SharerFormatData::FullBitVector,
+                    SharerFormat::LimitedPointer { pointers } => SharerFormatData::LimitedPointer { pointers },
+                }
+            },
+        }
+    }
+}
+
+impl From<CoherenceMechanismData> for CoherenceMechanism {
+    fn from(mechanism: CoherenceMechanismData) -> Self {
+        match mechanism {
+            CoherenceMechanismData::Snooping => Self::Snooping,
+            CoherenceMechanismData::Directory { sharers } => Self::Directory {
+                sharers: match sharers {
+                    SharerFormatData::FullBitVector => SharerFormat::FullBitVector,
+                    SharerFormatData::LimitedPointer { pointers } => SharerFormat::LimitedPointer { pointers },
+                }
+            },
+        }
+    }
+}
+
+impl From<CoherenceProtocol> for CoherenceProtocolData {
+    fn from(protocol: CoherenceProtocol) -> Self {
+        match protocol {
+            CoherenceProtocol::Mesi => Self::Mesi,
+            CoherenceProtocol::Moesi => Self::Moesi,
+        }
+    }
+}
+
+impl From<CoherenceProtocolData> for CoherenceProtocol {
+    fn from(protocol: CoherenceProtocolData) -> Self {
+        match protocol {
+            CoherenceProtocolData::Mesi => Self::Mesi,
+            CoherenceProtocolData::Moesi => Self::Moesi,
         }
     }
 }
@@ -227,10 +734,31 @@ impl<'de> Deserialize<'de> for MachineConfig {
     {
         let data = MachineConfigData::deserialize(deserializer)?;
         let config = MachineConfig::from(data);
-        if config.is_valid() {
-            Ok(config)
-        } else {
-            Err(de::Error::custom("machine config must include at least one cache level"))
-        }
+        config.validate().map_err(de::Error::custom)?;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_configs_say_what_is_wrong() {
+        let mut config = MachineConfig::default();
+        assert_eq!(config.validate(), Ok(()));
+
+        config.cache_config[0].mshrs = 0;
+        assert_eq!(
+            config.validate().map_err(|error| error.to_string()),
+            Err("invalid machine config: cache levels need at least one MSHR and a nonzero hit latency".to_string())
+        );
+
+        config.interleave_size = 48;
+        assert_eq!(
+            config.validate(),
+            Err(MachineError::InvalidConfig("memory_controllers must be nonzero and interleave_size a power of two"))
+        );
     }
 }
@@ -0,0 +1,358 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{
+        self,
+        Read,
+        Write
+    }
+};
+
+use crate::event::AccessKind;
+
+/// A single memory access, as recorded during a run or read from a trace file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceAccess {
+    pub timestamp: u64,
+    pub hart: usize,
+    pub pc: Option<u64>,
+    pub address: usize,
+    pub size: usize,
+    pub kind: AccessKind,
+    /// Cache level that served the access, counting from 1. None when it reached memory or is unknown
+    pub hit_level: Option<u8>,
+}
+
+impl TraceAccess {
+    pub fn new(hart: usize, kind: AccessKind, address: usize, size: usize) -> Self {
+        Self {
+            timestamp: 0,
+            hart,
+            pc: None,
+            address,
+            size,
+            kind,
+            hit_level: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Dinero III/IV "din" text: `<label> <hex address> [<hex size>]` per line
+    Dinero,
+    /// `<kind>,<address>[,<size>[,<hart>]]` per line, kind being one of R, W, I or A
+    Csv,
+    /// Narvi's binary traces
+    Narvi,
+}
+
+impl TraceFormat {
+    /// Guesses the format of a trace from its file name
+    pub fn from_path(path: &str) -> Self {
+        match path.rsplit('.').next() {
+            Some("din") => Self::Dinero,
+            Some("csv") => Self::Csv,
+            _ => Self::Narvi,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dinero" | "din" => Some(Self::Dinero),
+            "csv" => Some(Self::Csv),
+            "narvi" => Some(Self::Narvi),
+            _ => None,
+        }
+    }
+}
+
+pub enum TraceError {
+    Io(io::Error),
+    /// Line of a text trace that could not be parsed, counting from 1
+    Parse(usize),
+    /// Not a Narvi binary trace, or one from an unsupported version
+    Header,
+    /// The file ends in the middle of a record
+    Truncated,
+    /// Record of a binary trace, counting from 1, with fields that could not be parsed
+    InvalidRecord(usize),
+    /// Record, counting from 1, of an access from a hart the machine does not have
+    UnknownHart(usize),
+    /// Record, counting from 1, of an access beyond the end of memory
+    OutOfBounds(usize),
+}
+
+impl TraceError {
+    fn as_str(&self) -> String {
+        match self {
+            Self::Io(err) => format!("Io({err})"),
+            Self::Parse(line) => format!("Parse(line {line})"),
+            Self::Header => "Header".to_string(),
+            Self::Truncated => "Truncated".to_string(),
+            Self::InvalidRecord(record) => format!("InvalidRecord(record {record})"),
+            Self::UnknownHart(record) => format!("UnknownHart(record {record})"),
+            Self::OutOfBounds(record) => format!("OutOfBounds(record {record})"),
+        }
+    }
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TraceError: {}", self.as_str())
+    }
+}
+
+impl std::fmt::Debug for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TraceError: {}", self.as_str())
+    }
+}
+
+impl Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+pub fn read_trace(reader: impl Read, format: TraceFormat) -> Result<Vec<TraceAccess>, TraceError> {
+    match format {
+        TraceFormat::Dinero => parse_dinero(&read_text(reader)?),
+        TraceFormat::Csv => parse_csv(&read_text(reader)?),
+        TraceFormat::Narvi => read_narvi(reader),
+    }
+}
+
+fn read_text(mut reader: impl Read) -> Result<String, TraceError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+fn parse_number(text: &str, hex: bool) -> Option<usize> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => usize::from_str_radix(digits, 16).ok(),
+        None if hex => usize::from_str_radix(text, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parses a Dinero din trace. Escape (3) and flush (4) records carry no access and are skipped
+pub fn parse_dinero(text: &str) -> Result<Vec<TraceAccess>, TraceError> {
+    let mut accesses = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+
+        let Some(label) = fields.next() else {
+            continue;
+        };
+
+        let kind = match label {
+            "0" => AccessKind::Load,
+            "1" => AccessKind::Store,
+            "2" => AccessKind::Fetch,
+            "3" | "4" => continue,
+            _ => return Err(TraceError::Parse(i + 1)),
+        };
+
+        let address = fields.next()
+            .and_then(|field| parse_number(field, true))
+            .ok_or(TraceError::Parse(i + 1))?;
+        let size = match fields.next() {
+            Some(field) => parse_number(field, true).ok_or(TraceError::Parse(i + 1))?,
+            None => 4,
+        };
+
+        accesses.push(TraceAccess::new(0, kind, address, size));
+    }
+
+    Ok(accesses)
+}
+
+/// Parses a CSV trace. Empty lines, lines starting with `#` and a header line are skipped
+pub fn parse_csv(text: &str) -> Result<Vec<TraceAccess>, TraceError> {
+    let mut accesses = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || (i == 0 && line.starts_with("kind")) {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+
+        let kind = match fields[0] {
+            "R" | "r" | "load" => AccessKind::Load,
+            "W" | "w" | "store" => AccessKind::Store,
+            "I" | "i" | "fetch" => AccessKind::Fetch,
+            "A" | "a" | "amo" => AccessKind::Amo,
+            _ => return Err(TraceError::Parse(i + 1)),
+        };
+
+        let field = |idx: usize, default: usize| match fields.get(idx) {
+            Some(field) => parse_number(field, false).ok_or(TraceError::Parse(i + 1)),
+            None => Ok(default),
+        };
+
+        let address = fields.get(1)
+            .and_then(|field| parse_number(field, false))
+            .ok_or(TraceError::Parse(i + 1))?;
+
+        accesses.push(TraceAccess::new(field(3, 0)?, kind, address, field(2, 4)?));
+    }
+
+    Ok(accesses)
+}
+
+const NARVI_MAGIC: &[u8; 4] = b"NRVT";
+const NARVI_VERSION: u8 = 1;
+const NARVI_RECORD_SIZE: usize = 32;
+
+/// Writes the header of a Narvi binary trace, to be followed by `write_narvi_record` for each access
+pub fn write_narvi_header(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(NARVI_MAGIC)?;
+    writer.write_all(&[NARVI_VERSION])
+}
+
+/// Every record takes 32 little-endian bytes: timestamp (8), PC (8, all ones when unknown),
+/// address (8), size (4), hart (2), kind (1, 0 fetch, 1 load, 2 store, 3 AMO) and hit level (1, 0 for memory)
+pub fn write_narvi_record(writer: &mut impl Write, access: &TraceAccess) -> io::Result<()> {
+    let mut record = [0u8; NARVI_RECORD_SIZE];
+
+    record[0..8].copy_from_slice(&access.timestamp.to_le_bytes());
+    record[8..16].copy_from_slice(&access.pc.unwrap_or(u64::MAX).to_le_bytes());
+    record[16..24].copy_from_slice(&(access.address as u64).to_le_bytes());
+    record[24..28].copy_from_slice(&(access.size as u32).to_le_bytes());
+    record[28..30].copy_from_slice(&(access.hart as u16).to_le_bytes());
+    record[30] = match access.kind {
+        AccessKind::Fetch => 0,
+        AccessKind::Load => 1,
+        AccessKind::Store => 2,
+        AccessKind::Amo => 3,
+    };
+    record[31] = access.hit_level.unwrap_or(0);
+
+    writer.write_all(&record)
+}
+
+/// Writes `access` as Dinero din records. Dinero has no atomics, so an AMO is written as a read followed by a write
+pub fn write_dinero_record(writer: &mut impl Write, access: &TraceAccess) -> io::Result<()> {
+    let labels: &[u8] = match access.kind {
+        AccessKind::Load => &[0],
+        AccessKind::Store => &[1],
+        AccessKind::Fetch => &[2],
+        AccessKind::Amo => &[0, 1],
+    };
+
+    for label in labels {
+        writeln!(writer, "{label} {:x} {:x}", access.address, access.size)?;
+    }
+
+    Ok(())
+}
+
+pub fn read_narvi(mut reader: impl Read) -> Result<Vec<TraceAccess>, TraceError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let header_size = NARVI_MAGIC.len() + 1;
+    if bytes.len() < header_size || &bytes[..4] != NARVI_MAGIC || bytes[4] != NARVI_VERSION {
+        return Err(TraceError::Header);
+    }
+
+    let records = &bytes[header_size..];
+    if records.len() % NARVI_RECORD_SIZE != 0 {
+        return Err(TraceError::Truncated);
+    }
+
+    records.chunks_exact(NARVI_RECORD_SIZE)
+        .enumerate()
+        .map(|(i, record)| {
+            let u64_at = |at: usize| u64::from_le_bytes(record[at..at + 8].try_into().unwrap());
+            let pc = u64_at(8);
+
+            Ok(TraceAccess {
+                timestamp: u64_at(0),
+                pc: (pc != u64::MAX).then_some(pc),
+                address: u64_at(16) as usize,
+                size: u32::from_le_bytes(record[24..28].try_into().unwrap()) as usize,
+                hart: u16::from_le_bytes(record[28..30].try_into().unwrap()) as usize,
+                kind: match record[30] {
+                    0 => AccessKind::Fetch,
+                    1 => AccessKind::Load,
+                    2 => AccessKind::Store,
+                    3 => AccessKind::Amo,
+                    _ => return Err(TraceError::InvalidRecord(i + 1)),
+                },
+                hit_level: (record[31] != 0).then_some(record[31]),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dinero_skips_non_accesses() {
+        let accesses = parse_dinero("2 0\n0 1000 8\n3 0\n1 7ffc\n").unwrap();
+
+        assert_eq!(accesses, vec![
+            TraceAccess::new(0, AccessKind::Fetch, 0x0, 4),
+            TraceAccess::new(0, AccessKind::Load, 0x1000, 8),
+            TraceAccess::new(0, AccessKind::Store, 0x7ffc, 4),
+        ]);
+    }
+
+    #[test]
+    fn csv_reads_optional_columns() {
+        let accesses = parse_csv("kind,address,size,hart\nR,0x40,8,1\nW,128\n").unwrap();
+
+        assert_eq!(accesses.len(), 2);
+        assert_eq!((accesses[0].hart, accesses[0].address, accesses[0].size), (1, 0x40, 8));
+        assert_eq!((accesses[1].kind, accesses[1].address, accesses[1].size), (AccessKind::Store, 128, 4));
+        assert!(matches!(parse_csv("X,0"), Err(TraceError::Parse(1))));
+    }
+
+    #[test]
+    fn narvi_round_trip() {
+        let access = TraceAccess {
+            timestamp: 42,
+            hart: 3,
+            pc: Some(0x80),
+            address: 0x1040,
+            size: 8,
+            kind: AccessKind::Store,
+            hit_level: Some(2),
+        };
+
+        let mut bytes = Vec::new();
+        write_narvi_header(&mut bytes).unwrap();
+        write_narvi_record(&mut bytes, &access).unwrap();
+        write_narvi_record(&mut bytes, &TraceAccess::new(0, AccessKind::Fetch, 0, 4)).unwrap();
+
+        assert_eq!(read_narvi(bytes.as_slice()).unwrap(), vec![access, TraceAccess::new(0, AccessKind::Fetch, 0, 4)]);
+        assert!(matches!(read_narvi(&bytes[..10]), Err(TraceError::Truncated)));
+
+        // An unknown access kind is blamed on its record
+        let kind_at = bytes.len() - NARVI_RECORD_SIZE + 30;
+        bytes[kind_at] = 9;
+        assert!(matches!(read_narvi(bytes.as_slice()), Err(TraceError::InvalidRecord(2))));
+    }
+
+    #[test]
+    fn dinero_round_trip_splits_amos() {
+        let mut text = Vec::new();
+        write_dinero_record(&mut text, &TraceAccess::new(0, AccessKind::Fetch, 0x80, 4)).unwrap();
+        write_dinero_record(&mut text, &TraceAccess::new(0, AccessKind::Amo, 0x1000, 8)).unwrap();
+
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(text, "2 80 4\n0 1000 8\n1 1000 8\n");
+        assert_eq!(parse_dinero(&text).unwrap()[2], TraceAccess::new(0, AccessKind::Store, 0x1000, 8));
+    }
+}